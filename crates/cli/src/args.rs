@@ -69,7 +69,8 @@ pub struct Args {
     #[arg(long, value_name="COLS", num_args(0..), verbatim_doc_comment, help_heading="Content Options")]
     pub columns: Option<Vec<String>>,
 
-    /// Set output datatype(s) of U256 integers
+    /// Set output datatype(s) of U256 integers,
+    /// use d128:<SCALE> for decimals with a scale
     /// [default: binary, string, f64]
     #[arg(long, num_args(1..), help_heading = "Content Options", verbatim_doc_comment)]
     pub u256_types: Option<Vec<String>>,
@@ -86,18 +87,58 @@ pub struct Args {
     #[arg(long, help_heading = "Content Options")]
     pub exclude_failed: bool,
 
-    /// RPC url [default: 1. MESC 2. ETH_RPC_URL]
-    #[arg(short, long, help_heading = "Source Options")]
-    pub rpc: Option<String>,
+    /// RPC url(s) or MESC endpoint(s), requests are balanced across
+    /// multiple endpoints [default: 1. MESC 2. ETH_RPC_URL]
+    #[arg(short, long, num_args(1..), help_heading = "Source Options", verbatim_doc_comment)]
+    pub rpc: Option<Vec<String>>,
+
+    /// Relative share of requests sent to each RPC endpoint
+    /// [default: rate limit of endpoint, or 1]
+    #[arg(long, num_args(1..), value_name = "W", help_heading = "Source Options", verbatim_doc_comment)]
+    pub rpc_weights: Option<Vec<f64>>,
+
+    /// Ratelimit on requests per second of each RPC endpoint
+    #[arg(long, num_args(1..), value_name = "limit", help_heading = "Source Options")]
+    pub rpc_rate_limits: Option<Vec<u32>>,
+
+    /// Blocks an RPC endpoint may lag behind the others before it is ejected
+    #[arg(long, default_value_t = 5, value_name = "BLOCKS", help_heading = "Source Options")]
+    pub rpc_max_lag: u64,
+
+    /// Seconds that failing or lagging RPC endpoints are ejected for
+    #[arg(long, default_value_t = 30, value_name = "SECONDS", help_heading = "Source Options")]
+    pub rpc_ejection_time: u64,
 
     /// Network name [default: name of eth_getChainId]
     #[arg(long, help_heading = "Source Options")]
     pub network_name: Option<String>,
 
+    /// Record RPC requests and responses to fixture file
+    #[arg(long, value_name = "PATH", help_heading = "Source Options")]
+    pub record_rpc: Option<String>,
+
+    /// Replay RPC responses from fixture file or directory instead of an RPC
+    #[arg(
+        long,
+        value_name = "PATH",
+        help_heading = "Source Options",
+        conflicts_with = "record_rpc"
+    )]
+    pub replay_rpc: Option<String>,
+
     /// Ratelimit on requests per second
     #[arg(short('l'), long, value_name = "limit", help_heading = "Acquisition Options")]
     pub requests_per_second: Option<u32>,
 
+    /// Adapt request rate to provider feedback, starting from
+    /// --requests-per-second [default start: 10]
+    #[arg(long, help_heading = "Acquisition Options", verbatim_doc_comment)]
+    pub adaptive_rate: bool,
+
+    /// Maximum requests per second of adaptive rate limiting
+    #[arg(long, value_name = "limit", help_heading = "Acquisition Options")]
+    pub max_requests_per_second: Option<u32>,
+
     /// Max retries for provider errors
     #[arg(long, default_value_t = 5, value_name = "R", help_heading = "Acquisition Options")]
     pub max_retries: u32,
@@ -106,6 +147,41 @@ pub struct Args {
     #[arg(long, default_value_t = 500, value_name = "B", help_heading = "Acquisition Options")]
     pub initial_backoff: u64,
 
+    /// Number of times to retry a failed chunk
+    #[arg(long, default_value_t = 0, value_name = "N", help_heading = "Acquisition Options")]
+    pub chunk_retries: u32,
+
+    /// Initial backoff before retrying a failed chunk (ms)
+    #[arg(long, default_value_t = 1000, value_name = "B", help_heading = "Acquisition Options")]
+    pub chunk_backoff: u64,
+
+    /// Fraction of chunk retry backoff to randomize
+    #[arg(long, default_value_t = 0.5, value_name = "J", help_heading = "Acquisition Options")]
+    pub chunk_jitter: f64,
+
+    /// Kinds of chunk errors to retry
+    /// [default: provider rpc too_many_requests task]
+    #[arg(
+        long,
+        num_args(1..),
+        value_name = "KINDS",
+        help_heading = "Acquisition Options",
+        verbatim_doc_comment
+    )]
+    pub retry_errors: Option<Vec<String>>,
+
+    /// Cache RPC responses of finalized blocks on disk
+    #[arg(long, help_heading = "Acquisition Options")]
+    pub cache: bool,
+
+    /// Directory of RPC response cache [default: {output_dir}/.cryo/cache]
+    #[arg(long, value_name = "DIR", help_heading = "Acquisition Options")]
+    pub cache_dir: Option<String>,
+
+    /// Maximum size of RPC response cache (MB)
+    #[arg(long, default_value_t = 10000, value_name = "MB", help_heading = "Acquisition Options")]
+    pub cache_size: u64,
+
     /// Global number of concurrent requests
     #[arg(long, value_name = "M", help_heading = "Acquisition Options")]
     pub max_concurrent_requests: Option<u64>,
@@ -122,6 +198,22 @@ pub struct Args {
     #[arg(short, long, help_heading = "Acquisition Options")]
     pub dry: bool,
 
+    /// Keep running and collect new chunks as the chain advances,
+    /// chunks are collected once older than --reorg-buffer
+    #[arg(long, help_heading = "Acquisition Options", verbatim_doc_comment)]
+    pub follow: bool,
+
+    /// Seconds between polls for new blocks in follow mode
+    /// (ws and ipc providers subscribe to new heads instead)
+    #[arg(
+        long,
+        default_value_t = 12,
+        value_name = "SECONDS",
+        help_heading = "Acquisition Options",
+        verbatim_doc_comment
+    )]
+    pub poll_interval: u64,
+
     /// Remember current command for future use
     #[arg(long)]
     pub remember: bool,
@@ -146,15 +238,50 @@ pub struct Args {
     #[arg(long, help_heading = "Output Options")]
     pub partition_by: Option<Vec<String>>,
 
-    /// Directory for output files
+    /// Directory for output files, or s3://bucket/prefix
     #[arg(short, long, default_value = ".", help_heading = "Output Options")]
     pub output_dir: String,
 
+    /// Local directory where files are staged before uploading to an
+    /// s3:// output dir [default: temporary directory]
+    #[arg(long, value_name = "DIR", help_heading = "Output Options", verbatim_doc_comment)]
+    pub staging_dir: Option<String>,
+
     /// Subdirectories for output files
-    /// can be `datatype`, `network`, or custom string
+    /// can be `datatype`, `network`, `chain_id`, `block_bucket`,
+    /// `date`, `month`, or custom string
     #[arg(long, help_heading = "Output Options", verbatim_doc_comment, num_args(1..))]
     pub subdirs: Vec<String>,
 
+    /// Name subdirectories as hive-style key=value pairs
+    /// [default subdirs: chain_id datatype block_bucket]
+    #[arg(long, help_heading = "Output Options", verbatim_doc_comment)]
+    pub hive: bool,
+
+    /// Record output files in a delta lake table per datatype
+    /// [default subdirs: datatype]
+    #[arg(long, help_heading = "Output Options", verbatim_doc_comment)]
+    pub delta: bool,
+
+    /// Write rows into a SQLite or DuckDB database file instead of
+    /// output files, with one table per datatype
+    #[arg(long, value_name = "PATH", help_heading = "Output Options", verbatim_doc_comment)]
+    pub db: Option<PathBuf>,
+
+    /// Bulk load rows into a PostgreSQL database instead of output
+    /// files, given as a connection string
+    #[arg(long, value_name = "URL", help_heading = "Output Options", verbatim_doc_comment)]
+    pub postgres: Option<String>,
+
+    /// Number of blocks per block_bucket subdirectory
+    #[arg(
+        long,
+        default_value_t = 1_000_000,
+        value_name = "BLOCKS",
+        help_heading = "Output Options"
+    )]
+    pub bucket_size: u64,
+
     /// Label to add to each filename
     #[arg(long, help_heading = "Output Options")]
     pub label: Option<String>,
@@ -163,6 +290,18 @@ pub struct Args {
     #[arg(long, help_heading = "Output Options")]
     pub overwrite: bool,
 
+    /// Record chunk block hashes and re-collect chunks whose hashes no longer match the chain
+    #[arg(long, help_heading = "Output Options")]
+    pub verify_reorgs: bool,
+
+    /// Re-collect existing files written with a different schema or cryo version
+    #[arg(long, help_heading = "Output Options")]
+    pub recollect_stale: bool,
+
+    /// Save progress every N blocks within each chunk, so interrupted chunks resume
+    #[arg(long, value_name = "N_BLOCKS", help_heading = "Output Options")]
+    pub checkpoint_size: Option<u64>,
+
     /// Save as csv instead of parquet
     #[arg(long, help_heading = "Output Options")]
     pub csv: bool,
@@ -171,6 +310,18 @@ pub struct Args {
     #[arg(long, help_heading = "Output Options")]
     pub json: bool,
 
+    /// Save as newline-delimited json instead of parquet
+    #[arg(long, help_heading = "Output Options")]
+    pub ndjson: bool,
+
+    /// Save as arrow ipc (feather) instead of parquet
+    #[arg(long, visible_alias = "ipc", help_heading = "Output Options")]
+    pub arrow: bool,
+
+    /// Stream rows to stdout as csv, ndjson (default), or arrow ipc stream
+    #[arg(long, help_heading = "Output Options")]
+    pub stdout: bool,
+
     /// Number of rows per row group in parquet file
     #[arg(long, value_name = "GROUP_SIZE", help_heading = "Output Options")]
     pub row_group_size: Option<usize>,
@@ -187,6 +338,39 @@ pub struct Args {
     #[arg(long, help_heading="Output Options", value_name="NAME [#]", num_args(1..=2), default_value = "lz4")]
     pub compression: Vec<String>,
 
+    /// Compression of arrow ipc buffers, one of uncompressed, lz4, or zstd
+    #[arg(
+        long,
+        help_heading = "Output Options",
+        value_name = "NAME",
+        default_value = "uncompressed"
+    )]
+    pub ipc_compression: String,
+
+    /// Compression of csv and json files, one of uncompressed, gzip, zstd, or bzip2,
+    /// with an optional level
+    #[arg(
+        long,
+        help_heading = "Output Options",
+        value_name = "NAME [#]",
+        num_args(1..=2),
+        default_value = "uncompressed",
+        verbatim_doc_comment
+    )]
+    pub text_compression: Vec<String>,
+
+    /// Blocks spanned by each file merged by cryo compact
+    #[arg(long, help_heading = "Output Options", value_name = "BLOCKS")]
+    pub target_blocks: Option<u64>,
+
+    /// Rows of each file merged by cryo compact
+    #[arg(long, help_heading = "Output Options", value_name = "ROWS")]
+    pub target_rows: Option<u64>,
+
+    /// Bytes of each file merged by cryo compact [default: 128MB]
+    #[arg(long, help_heading = "Output Options", value_name = "BYTES")]
+    pub target_bytes: Option<u64>,
+
     /// Directory to save summary report
     /// [default: {output_dir}/.cryo/reports]
     #[arg(long, help_heading = "Output Options", verbatim_doc_comment)]
@@ -212,7 +396,7 @@ pub struct Args {
     #[arg(long, help_heading = "Dataset-specific Options", num_args(1..))]
     pub call_data: Option<Vec<String>>,
 
-    /// Function(s) to use for eth_calls
+    /// Function selector(s) or signature(s) to use for eth_calls
     #[arg(long, help_heading = "Dataset-specific Options", num_args(1..))]
     pub function: Option<Vec<String>>,
 
@@ -248,6 +432,14 @@ pub struct Args {
     #[arg(long, value_name = "SIG", help_heading = "Dataset-specific Options", num_args(1..))]
     pub event_signature: Option<String>,
 
+    /// Function signature(s) or ABI file(s) for calldata decoding
+    #[arg(long, value_name = "SIG", help_heading = "Dataset-specific Options", num_args(1..))]
+    pub function_signature: Option<Vec<String>>,
+
+    /// ABI file, or directory of ABIs named by address, for a table per event
+    #[arg(long, value_name = "PATH", help_heading = "Dataset-specific Options")]
+    pub abi: Option<PathBuf>,
+
     /// Blocks per request (eth_getLogs)
     #[arg(
         long,
@@ -304,7 +496,7 @@ pub(crate) fn get_styles() -> clap_cryo::builder::Styles {
 }
 
 fn get_about_str() -> String {
-    cstr!(r#"<white><bold>cryo</bold></white> extracts blockchain data to parquet, csv, or json"#)
+    cstr!(r#"<white><bold>cryo</bold></white> extracts blockchain data to parquet, csv, json, or arrow"#)
         .to_string()
 }
 
@@ -318,7 +510,21 @@ fn get_after_str() -> String {
       <white><bold>cryo help</bold></white>"#
     );
     let post_subcommands = " <DATASET(S)>         display info about a dataset";
-    format!("{}{}{}", header, subcommands, post_subcommands)
+    let validate = cstr!(
+        r#"
+      <white><bold>cryo validate</bold></white>"#
+    );
+    let post_validate =
+        " <DATASET(S)>     check existing files for gaps, duplicates, and schema drift";
+    let compact = cstr!(
+        r#"
+      <white><bold>cryo compact</bold></white>"#
+    );
+    let post_compact = " <DATASET(S)>      merge adjacent small files into larger files";
+    format!(
+        "{}{}{}{}{}{}{}",
+        header, subcommands, post_subcommands, validate, post_validate, compact, post_compact
+    )
 }
 
 fn get_datatype_help() -> &'static str {
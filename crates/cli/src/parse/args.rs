@@ -14,6 +14,9 @@ pub async fn parse_args(
     let source = source::parse_source(args).await?;
     let query = query::parse_query(args, Arc::new(source.clone())).await?;
     let sink = file_output::parse_file_output(args, &source)?;
+    if sink.stdout && query.schemas.len() > 1 {
+        return Err(ParseError::ParseError("--stdout requires a single datatype".to_string()))
+    }
     let env = execution::parse_execution_env(args, query.n_tasks() as u64)?;
     Ok((query, source, sink, env))
 }
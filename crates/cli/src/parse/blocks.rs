@@ -31,7 +31,7 @@ pub(crate) async fn parse_blocks(
             let chunk_label = path
                 .split("__")
                 .last()
-                .and_then(|s| s.strip_suffix(".parquet").map(|s| s.to_string()));
+                .and_then(|s| s.split_once('.').map(|(label, _)| label.to_string()));
             file_labels.push(chunk_label);
             file_chunks.push(chunk);
         }
@@ -68,13 +68,7 @@ pub(crate) async fn parse_blocks(
 }
 
 fn read_integer_column(path: &str, column: &str) -> Result<Vec<u64>, ParseError> {
-    let file = std::fs::File::open(path)
-        .map_err(|_e| ParseError::ParseError("could not open file path".to_string()))?;
-
-    let df = ParquetReader::new(file)
-        .with_columns(Some(vec![column.to_string()]))
-        .finish()
-        .map_err(|_e| ParseError::ParseError("could not read data from column".to_string()))?;
+    let df = cryo_freeze::read_column(path, column)?;
 
     let series = df
         .column(column)
@@ -411,6 +405,9 @@ mod tests {
             inner_request_size: 1,
             max_concurrent_chunks: None,
             rpc_url: "".to_string(),
+            adaptive_rate_limiter: None,
+            cache: None,
+            endpoints: None,
             labels: cryo_freeze::SourceLabels::default(),
         };
         let source = Arc::new(source);
@@ -469,6 +466,9 @@ mod tests {
             provider: provider.into(),
             chain_id: 1,
             rpc_url: "".to_string(),
+            adaptive_rate_limiter: None,
+            cache: None,
+            endpoints: None,
             inner_request_size: 1,
             semaphore: Arc::new(None),
             max_concurrent_chunks: Some(1),
@@ -546,6 +546,9 @@ mod tests {
             inner_request_size: 1,
             max_concurrent_chunks: Some(1),
             rpc_url: "".to_string(),
+            adaptive_rate_limiter: None,
+            cache: None,
+            endpoints: None,
             labels: cryo_freeze::SourceLabels::default(),
         };
         let source = Arc::new(source);
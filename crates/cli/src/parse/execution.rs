@@ -1,5 +1,9 @@
 use crate::args::Args;
-use cryo_freeze::{ExecutionEnv, ExecutionEnvBuilder, ParseError};
+use cryo_freeze::{
+    retries::default_retryable_errors, CollectError, ExecutionEnv, ExecutionEnvBuilder, ParseError,
+    RetryPolicy,
+};
+use std::time::Duration;
 
 pub(crate) fn parse_execution_env(args: &Args, n_tasks: u64) -> Result<ExecutionEnv, ParseError> {
     let args_str =
@@ -12,11 +16,35 @@ pub(crate) fn parse_execution_env(args: &Args, n_tasks: u64) -> Result<Execution
         (false, false) => 1,
     };
 
+    let retryable_errors = match &args.retry_errors {
+        Some(kinds) => {
+            if let Some(kind) =
+                kinds.iter().find(|kind| !CollectError::KINDS.contains(&kind.as_str()))
+            {
+                return Err(ParseError::ParseError(format!(
+                    "unknown --retry-errors kind: {}, use one of: {}",
+                    kind,
+                    CollectError::KINDS.join(" ")
+                )))
+            }
+            kinds.clone()
+        }
+        None => default_retryable_errors(),
+    };
+    let retry_policy = RetryPolicy {
+        max_attempts: args.chunk_retries + 1,
+        initial_backoff: Duration::from_millis(args.chunk_backoff),
+        jitter: args.chunk_jitter,
+        retryable_errors,
+        ..Default::default()
+    };
+
     let builder = ExecutionEnvBuilder::new()
         .dry(args.dry)
         .verbose(verbose)
-        .report(!args.no_report)
+        .report(!args.no_report && !args.stdout)
         .report_dir(args.report_dir.clone())
+        .retry_policy(retry_policy)
         .args(args_str);
 
     let builder = if !args.no_verbose {
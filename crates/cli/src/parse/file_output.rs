@@ -1,13 +1,26 @@
 use crate::args::Args;
-use cryo_freeze::{FileFormat, FileOutput, ParseError, Source, SubDir};
+use cryo_freeze::{
+    Database, FileFormat, FileOutput, ParseError, PostgresSink, RemoteStore, Source, SubDir,
+    TextCompression,
+};
 use polars::prelude::*;
-use std::fs;
+use std::{fs, path::PathBuf};
 
 pub(crate) fn parse_file_output(args: &Args, source: &Source) -> Result<FileOutput, ParseError> {
-    // process output directory
-    std::fs::create_dir_all(args.output_dir.clone())
+    let file_prefix = parse_network_name(args, source.chain_id);
+    parse_file_output_with_prefix(args, file_prefix, Some(source.chain_id))
+}
+
+pub(crate) fn parse_file_output_with_prefix(
+    args: &Args,
+    file_prefix: String,
+    chain_id: Option<u64>,
+) -> Result<FileOutput, ParseError> {
+    // process output directory, files of remote output directories are staged locally
+    let output_dir = parse_local_output_dir(args)?;
+    std::fs::create_dir_all(output_dir.clone())
         .map_err(|_| ParseError::ParseError("could not create dir".to_string()))?;
-    let output_dir = std::fs::canonicalize(args.output_dir.clone()).map_err(|_e| {
+    let output_dir = std::fs::canonicalize(output_dir).map_err(|_e| {
         ParseError::ParseError("Failed to canonicalize output directory".to_string())
     })?;
     match fs::create_dir_all(&output_dir) {
@@ -18,6 +31,7 @@ pub(crate) fn parse_file_output(args: &Args, source: &Source) -> Result<FileOutp
     let label = &args.label;
 
     let parquet_compression = parse_compression(&args.compression)?;
+    let ipc_compression = parse_ipc_compression(&args.ipc_compression)?;
 
     let row_group_size = parse_row_group_size(
         args.row_group_size,
@@ -26,32 +40,141 @@ pub(crate) fn parse_file_output(args: &Args, source: &Source) -> Result<FileOutp
     );
 
     let format = parse_output_format(args)?;
-    let file_prefix = parse_network_name(args, source.chain_id);
+    if args.stdout && args.checkpoint_size.is_some() {
+        return Err(ParseError::ParseError(
+            "--checkpoint-size cannot be used with --stdout".to_string(),
+        ))
+    }
+    // every freeze round ends the arrow stream it writes, so later rounds could not be read
+    if args.stdout && args.follow && format == FileFormat::Arrow {
+        return Err(ParseError::ParseError(
+            "--follow cannot be used with --stdout and --arrow".to_string(),
+        ))
+    }
 
     let subdirs = parse_subdirs(args);
+    if args.delta {
+        if format != FileFormat::Parquet || args.stdout {
+            return Err(ParseError::ParseError("--delta requires parquet output files".to_string()))
+        }
+        if !subdirs.iter().any(|subdir| matches!(subdir, SubDir::Datatype)) {
+            return Err(ParseError::ParseError(
+                "--delta requires a datatype subdirectory for each table".to_string(),
+            ))
+        }
+        // the delta schema only holds the columns of the table, not decoded params
+        let conflicts = [
+            (args.event_signature.is_some(), "--event-signature"),
+            (args.function_signature.is_some(), "--function-signature"),
+        ];
+        if let Some((_, flag)) = conflicts.iter().find(|(conflict, _)| *conflict) {
+            return Err(ParseError::ParseError(format!("--delta cannot be used with {}", flag)))
+        }
+    }
+
+    let database_flag = match (&args.db, &args.postgres) {
+        (Some(_), Some(_)) => {
+            return Err(ParseError::ParseError("--db cannot be used with --postgres".to_string()))
+        }
+        (Some(_), None) => Some("--db"),
+        (None, Some(_)) => Some("--postgres"),
+        (None, None) => None,
+    };
+    if let Some(database_flag) = database_flag {
+        let conflicts = [
+            (args.delta, "--delta"),
+            (args.stdout, "--stdout"),
+            (args.verify_reorgs, "--verify-reorgs"),
+            (args.checkpoint_size.is_some(), "--checkpoint-size"),
+        ];
+        if let Some((_, flag)) = conflicts.iter().find(|(conflict, _)| *conflict) {
+            let message = format!("{} cannot be used with {}", database_flag, flag);
+            return Err(ParseError::ParseError(message))
+        }
+    }
+    let remote = match RemoteStore::is_remote(&args.output_dir) {
+        true => {
+            let conflicts = [
+                (args.delta, "--delta"),
+                (args.stdout, "--stdout"),
+                (args.verify_reorgs, "--verify-reorgs"),
+                (database_flag.is_some(), database_flag.unwrap_or_default()),
+            ];
+            if let Some((_, flag)) = conflicts.iter().find(|(conflict, _)| *conflict) {
+                let message = format!("s3 output dir cannot be used with {}", flag);
+                return Err(ParseError::ParseError(message))
+            }
+            Some(RemoteStore::new(&args.output_dir)?)
+        }
+        false => None,
+    };
+    let database = args.db.clone().map(Database::new).transpose()?;
+    let postgres = args.postgres.clone().map(PostgresSink::new);
 
     let output = FileOutput {
         output_dir,
         subdirs,
+        hive: args.hive,
+        chain_id,
+        block_timestamps: Default::default(),
         parquet_statistics: !args.no_stats,
         overwrite: args.overwrite,
+        verify_reorgs: args.verify_reorgs,
+        recollect_stale: args.recollect_stale,
+        checkpoint_size: args.checkpoint_size,
         prefix: file_prefix,
         format,
         suffix: label.clone(),
         parquet_compression,
+        ipc_compression,
+        stdout: args.stdout,
+        delta: args.delta,
+        database,
+        postgres,
+        remote,
         row_group_size,
     };
 
     Ok(output)
 }
 
+/// local directory of output files, which is the staging directory of remote output dirs
+pub(crate) fn parse_local_output_dir(args: &Args) -> Result<PathBuf, ParseError> {
+    match (RemoteStore::is_remote(&args.output_dir), &args.staging_dir) {
+        (true, Some(staging_dir)) => Ok(PathBuf::from(staging_dir)),
+        (true, None) => RemoteStore::default_staging_dir(&args.output_dir),
+        (false, _) => Ok(PathBuf::from(&args.output_dir)),
+    }
+}
+
 pub(crate) fn parse_subdirs(args: &Args) -> Vec<SubDir> {
+    // hive layouts default to partitioning by chain, datatype, and block bucket, and delta
+    // tables default to one table directory per datatype
+    let default_subdirs = match (args.hive, args.delta) {
+        (true, _) => vec!["chain_id", "datatype", "block_bucket"],
+        (false, true) => vec!["datatype"],
+        (false, false) => vec![],
+    };
+    let default_subdirs: Vec<String> = default_subdirs.into_iter().map(String::from).collect();
+    let subdirs_args = match args.subdirs.is_empty() {
+        true => &default_subdirs,
+        false => &args.subdirs,
+    };
+
     let mut subdirs = Vec::new();
-    for arg in args.subdirs.iter() {
+    for arg in subdirs_args.iter() {
         if arg == "datatype" {
             subdirs.push(SubDir::Datatype)
         } else if arg == "network" {
             subdirs.push(SubDir::Network)
+        } else if arg == "chain_id" {
+            subdirs.push(SubDir::ChainId)
+        } else if arg == "block_bucket" {
+            subdirs.push(SubDir::BlockBucket(args.bucket_size))
+        } else if arg == "date" {
+            subdirs.push(SubDir::Date)
+        } else if arg == "month" {
+            subdirs.push(SubDir::Month)
         } else {
             subdirs.push(SubDir::Custom(arg.clone()))
         }
@@ -89,13 +212,60 @@ pub(crate) fn parse_network_name(args: &Args, chain_id: u64) -> String {
 }
 
 pub(crate) fn parse_output_format(args: &Args) -> Result<FileFormat, ParseError> {
-    match (args.csv, args.json) {
-        (true, true) => {
-            Err(ParseError::ParseError("choose one of parquet, csv, or json".to_string()))
+    let compression = parse_text_compression(&args.text_compression)?;
+    let format = match (args.csv, args.json, args.ndjson, args.arrow) {
+        (false, false, false, false) if args.stdout => FileFormat::NdJson(compression),
+        (false, false, false, false) => FileFormat::Parquet,
+        (true, false, false, false) => FileFormat::Csv(compression),
+        (false, true, false, false) => FileFormat::Json(compression),
+        (false, false, true, false) => FileFormat::NdJson(compression),
+        (false, false, false, true) => FileFormat::Arrow,
+        _ => {
+            return Err(ParseError::ParseError(
+                "choose one of parquet, csv, json, ndjson, or arrow".to_string(),
+            ))
         }
-        (true, _) => Ok(FileFormat::Csv),
-        (_, true) => Ok(FileFormat::Json),
-        (false, false) => Ok(FileFormat::Parquet),
+    };
+    if compression != TextCompression::Uncompressed &&
+        format.text_compression() == TextCompression::Uncompressed
+    {
+        return Err(ParseError::ParseError(
+            "--text-compression requires one of --csv, --json, or --ndjson".to_string(),
+        ))
+    }
+    if args.stdout && !format.supports_stdout() {
+        return Err(ParseError::ParseError(
+            "--stdout requires one of --csv, --ndjson, or --arrow, without --text-compression"
+                .to_string(),
+        ))
+    }
+    Ok(format)
+}
+
+fn parse_text_compression(input: &[String]) -> Result<TextCompression, ParseError> {
+    let level = |default: i32, range: std::ops::RangeInclusive<i32>| match input.get(1) {
+        Some(level) => level
+            .parse::<i32>()
+            .ok()
+            .filter(|level| range.contains(level))
+            .ok_or_else(|| ParseError::ParseError("Invalid compression level".to_string())),
+        None => Ok(default),
+    };
+    match input.first().map(|algorithm| algorithm.as_str()) {
+        Some("uncompressed") => Ok(TextCompression::Uncompressed),
+        Some("gzip") => Ok(TextCompression::Gzip(level(6, 0..=9)? as u32)),
+        Some("zstd") => Ok(TextCompression::Zstd(level(3, 1..=22)?)),
+        Some("bzip2") => Ok(TextCompression::Bzip2(level(6, 1..=9)? as u32)),
+        _ => Err(ParseError::ParseError("Invalid text compression algorithm".to_string())),
+    }
+}
+
+fn parse_ipc_compression(input: &str) -> Result<Option<IpcCompression>, ParseError> {
+    match input {
+        "uncompressed" => Ok(None),
+        "lz4" => Ok(Some(IpcCompression::LZ4)),
+        "zstd" => Ok(Some(IpcCompression::ZSTD)),
+        _ => Err(ParseError::ParseError("Invalid ipc compression algorithm".to_string())),
     }
 }
 
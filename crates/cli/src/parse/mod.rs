@@ -1,7 +1,7 @@
 mod args;
 mod blocks;
 mod execution;
-mod file_output;
+pub(crate) mod file_output;
 mod parse_utils;
 mod partitions;
 mod query;
@@ -142,12 +142,14 @@ fn parse_call_datas(
     let call_datas = match (call_datas, function, inputs) {
         (None, None, None) => return Ok(None),
         (Some(call_data), None, None) => hex_strings_to_binary(call_data)?,
-        (None, Some(function), None) => hex_strings_to_binary(function)?,
+        (None, Some(function), None) => {
+            function.iter().map(parse_selector).collect::<Result<Vec<_>, _>>()?
+        }
         (None, Some(function), Some(inputs)) => {
             let mut call_datas = Vec::new();
             for f in function.iter() {
                 for i in inputs.iter() {
-                    let mut call_data = hex_string_to_binary(f)?.clone();
+                    let mut call_data = parse_selector(f)?;
                     call_data.extend(hex_string_to_binary(i)?);
                     call_datas.push(call_data);
                 }
@@ -174,6 +176,14 @@ fn parse_call_datas(
     Ok(Some(vec![CallDataChunk::Values(call_datas)]))
 }
 
+/// parse selector of function, given as hex or as a function signature
+fn parse_selector(function: &String) -> Result<Vec<u8>, ParseError> {
+    match function.contains('(') {
+        true => Ok(cryo_freeze::parse_function(function)?.short_signature().to_vec()),
+        false => hex_string_to_binary(function),
+    }
+}
+
 pub(crate) fn parse_transaction_chunks(
     input: &Option<Vec<String>>,
     default_column: &str,
@@ -1,7 +1,8 @@
 use std::collections::HashMap;
 
 use cryo_freeze::{
-    ColumnEncoding, Datatype, FileFormat, LogDecoder, MultiDatatype, ParseError, Table,
+    AbiDecoder, ColumnEncoding, Datatype, FunctionDecoder, LogDecoder, MultiDatatype, ParseError,
+    Table,
 };
 
 use super::file_output;
@@ -34,7 +35,7 @@ pub(crate) fn parse_schemas(
     let sort = parse_sort_columns(&args.sort, &datatypes)?;
     let u256_types = parse_u256_types(args)?;
     let output_format = file_output::parse_output_format(args)?;
-    let binary_column_format = match args.hex | (output_format != FileFormat::Parquet) {
+    let binary_column_format = match args.hex | !output_format.supports_binary() {
         true => ColumnEncoding::Hex,
         false => ColumnEncoding::Binary,
     };
@@ -48,7 +49,7 @@ pub(crate) fn parse_schemas(
     };
 
     // create schemas
-    let schemas: Result<HashMap<Datatype, Table>, ParseError> = datatypes
+    let mut schemas: Result<HashMap<Datatype, Table>, ParseError> = datatypes
         .iter()
         .map(|datatype| {
             datatype
@@ -71,6 +72,21 @@ pub(crate) fn parse_schemas(
         })
         .collect();
 
+    // abi events are decoded from the raw topics and data of logs
+    if let (Ok(schemas), Some(abi)) = (&mut schemas, &args.abi) {
+        add_abi_decoder(abi, args, schemas)?
+    };
+
+    // calls are decoded from their raw input and output data
+    if let (Ok(schemas), Some(signatures)) = (&mut schemas, &args.function_signature) {
+        add_function_decoder(signatures, schemas)?
+    };
+
+    // eth_calls outputs are decoded by the return types of function signatures
+    if let (Ok(schemas), Some(functions)) = (&mut schemas, &args.function) {
+        add_eth_calls_decoder(functions, schemas)?
+    };
+
     // make sure all included columns ended up in at least one schema
     if let (Ok(schemas), Some(include_columns)) = (&schemas, &args.include_columns) {
         ensure_included_columns(include_columns, schemas)?
@@ -84,6 +100,87 @@ pub(crate) fn parse_schemas(
     Ok((datatypes, schemas?))
 }
 
+fn add_abi_decoder(
+    abi: &std::path::Path,
+    args: &Args,
+    schemas: &mut HashMap<Datatype, Table>,
+) -> Result<(), ParseError> {
+    let conflicts = [
+        (args.event_signature.is_some(), "--event-signature"),
+        (args.stdout, "--stdout"),
+        (args.delta, "--delta"),
+        (args.db.is_some(), "--db"),
+        (args.postgres.is_some(), "--postgres"),
+    ];
+    if let Some((_, flag)) = conflicts.iter().find(|(conflict, _)| *conflict) {
+        return Err(ParseError::ParseError(format!("--abi cannot be used with {}", flag)))
+    }
+    let logs = schemas
+        .get_mut(&Datatype::Logs)
+        .ok_or(ParseError::ParseError("--abi requires the logs datatype".to_string()))?;
+    let abi_decoder = AbiDecoder::from_path(abi)?;
+    let mut required_columns = vec!["topic0", "topic1", "topic2", "topic3", "data"];
+    if abi_decoder.events.values().any(|event| event.addresses.is_some()) {
+        required_columns.push("address");
+    }
+    for column in required_columns {
+        if !logs.has_column(column) {
+            let message = format!("--abi requires the {} column of logs", column);
+            return Err(ParseError::ParseError(message))
+        }
+    }
+    logs.abi_decoder = Some(abi_decoder);
+    Ok(())
+}
+
+fn add_function_decoder(
+    signatures: &[String],
+    schemas: &mut HashMap<Datatype, Table>,
+) -> Result<(), ParseError> {
+    let decoder = FunctionDecoder::new(signatures)?;
+    let call_datatypes =
+        [Datatype::Transactions, Datatype::Traces, Datatype::GethCalls, Datatype::TraceCalls];
+    let mut n_decoded = 0;
+    for (datatype, table) in schemas.iter_mut() {
+        if !call_datatypes.contains(datatype) {
+            continue
+        }
+        if !table.has_column("input") && !table.has_column("action_input") {
+            let message =
+                format!("--function-signature requires the input column of {}", datatype.name());
+            return Err(ParseError::ParseError(message))
+        }
+        table.function_decoder = Some(decoder.clone());
+        n_decoded += 1;
+    }
+    if n_decoded == 0 {
+        return Err(ParseError::ParseError(
+            "--function-signature requires transactions, traces, geth_calls, or trace_calls"
+                .to_string(),
+        ))
+    }
+    Ok(())
+}
+
+fn add_eth_calls_decoder(
+    functions: &[String],
+    schemas: &mut HashMap<Datatype, Table>,
+) -> Result<(), ParseError> {
+    let signatures: Vec<String> = functions.iter().filter(|f| f.contains('(')).cloned().collect();
+    let table = match schemas.get_mut(&Datatype::EthCalls) {
+        Some(table) if !signatures.is_empty() => table,
+        _ => return Ok(()),
+    };
+    for column in ["call_data", "output_data"] {
+        if !table.has_column(column) {
+            let message = format!("decoding eth_calls requires the {} column", column);
+            return Err(ParseError::ParseError(message))
+        }
+    }
+    table.function_decoder = Some(FunctionDecoder::new(&signatures)?);
+    Ok(())
+}
+
 fn parse_u256_types(args: &Args) -> Result<Vec<U256Type>, ParseError> {
     args.u256_types.as_ref().map_or(
         Ok(vec![U256Type::Binary, U256Type::String, U256Type::F64]),
@@ -99,8 +196,10 @@ fn parse_u256_types(args: &Args) -> Result<Vec<U256Type>, ParseError> {
                         "f64" | "float64" | "float" => Ok(U256Type::F64),
                         "u32" | "uint32" => Ok(U256Type::U32),
                         "u64" | "uint64" => Ok(U256Type::U64),
-                        "decimal128" | "d128" => Ok(U256Type::Decimal128),
-                        _ => Err(ParseError::ParseError(format!("invalid u256 type: {}", raw))),
+                        "decimal128" | "d128" => Ok(U256Type::Decimal128(0)),
+                        "decimal256" | "d256" => Ok(U256Type::Decimal256),
+                        _ => parse_decimal128(lower_case.as_str())
+                            .ok_or(ParseError::ParseError(format!("invalid u256 type: {}", raw))),
                     }
                 })
                 .collect()
@@ -108,6 +207,18 @@ fn parse_u256_types(args: &Args) -> Result<Vec<U256Type>, ParseError> {
     )
 }
 
+/// parse decimal128 with a scale, such as `d128:18`
+fn parse_decimal128(raw: &str) -> Option<U256Type> {
+    let (name, scale) = raw.split_once(':')?;
+    let scale = scale.parse::<u32>().ok()?;
+    match name {
+        "decimal128" | "d128" if scale as usize <= cryo_freeze::DECIMAL128_PRECISION => {
+            Some(U256Type::Decimal128(scale))
+        }
+        _ => None,
+    }
+}
+
 fn ensure_included_columns(
     include_columns: &[String],
     schemas: &cryo_freeze::Schemas,
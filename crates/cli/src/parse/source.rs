@@ -1,49 +1,61 @@
 use std::env;
 
 use crate::args::Args;
-use cryo_freeze::{sources::ProviderWrapper, ParseError, Source, SourceLabels};
+use cryo_freeze::{
+    sources::ProviderWrapper, AdaptiveRateLimiter, ParseError, RateLimiter, RecordingClient,
+    ReplayClient, RpcCache, RpcEndpoint, RpcEndpoints, Source, SourceLabels,
+};
 use ethers::prelude::*;
-use governor::{Quota, RateLimiter};
+use governor::Quota;
 use polars::prelude::*;
 use std::num::NonZeroU32;
 
 pub(crate) async fn parse_source(args: &Args) -> Result<Source, ParseError> {
     // parse network info
-    let rpc_url = parse_rpc_url(args)?;
-    let (provider, chain_id): (ProviderWrapper, u64) = if rpc_url.starts_with("http") {
-        let provider = Provider::<RetryClient<Http>>::new_client(
-            &rpc_url,
-            args.max_retries,
-            args.initial_backoff,
-        )
-        .map_err(|_e| ParseError::ParseError("could not connect to provider".to_string()))?;
-        let chain_id = provider.get_chainid().await.map_err(ParseError::ProviderError)?.as_u64();
-        (provider.into(), chain_id)
-    } else if rpc_url.starts_with("ws") {
-        let provider = Provider::<Ws>::connect(&rpc_url).await.map_err(|_| {
-            ParseError::ParseError("could not instantiate HTTP Provider".to_string())
-        })?;
-        let chain_id = provider.get_chainid().await.map_err(ParseError::ProviderError)?.as_u64();
-        (provider.into(), chain_id)
-    } else if rpc_url.ends_with(".ipc") {
-        let provider: Provider<Ipc> = Provider::connect_ipc(&rpc_url).await.map_err(|_| {
-            ParseError::ParseError("could not instantiate HTTP Provider".to_string())
-        })?;
+    let (rpc_url, provider, chain_id, endpoints) = if let Some(replay_rpc) = &args.replay_rpc {
+        let client = ReplayClient::load(std::path::Path::new(replay_rpc))
+            .map_err(|e| ParseError::ParseError(format!("could not load fixtures: {}", e)))?;
+        let provider = Provider::new(client);
         let chain_id = provider.get_chainid().await.map_err(ParseError::ProviderError)?.as_u64();
-        (provider.into(), chain_id)
+        (replay_rpc.clone(), provider.into(), chain_id, None)
     } else {
-        return Err(ParseError::ParseError(format!("invalid rpc url: {}", rpc_url)));
+        let specs = parse_rpc_endpoints(args)?;
+        let mut endpoints = Vec::new();
+        let mut chain_id = None;
+        for spec in specs.iter() {
+            let (provider, endpoint_chain_id) = parse_provider(args, &spec.url).await?;
+            match chain_id {
+                Some(chain_id) if chain_id != endpoint_chain_id => {
+                    return Err(ParseError::ParseError(format!(
+                        "rpc endpoint {} is on chain {}, expected chain {}",
+                        spec.label, endpoint_chain_id, chain_id
+                    )))
+                }
+                _ => chain_id = Some(endpoint_chain_id),
+            }
+            let rate_limiter = spec.rate_limit.and_then(create_rate_limiter);
+            let weight = spec.weight.or(spec.rate_limit.map(|x| x as f64)).unwrap_or(1.0);
+            endpoints.push(RpcEndpoint::new(spec.label.clone(), provider, weight, rate_limiter));
+        }
+        let chain_id =
+            chain_id.ok_or(ParseError::ParseError("no rpc endpoint provided".to_string()))?;
+        let provider = endpoints[0].provider.clone();
+        let endpoints = RpcEndpoints::new(
+            endpoints,
+            args.rpc_max_lag,
+            std::time::Duration::from_secs(args.rpc_ejection_time),
+        )
+        .map_err(|e| ParseError::ParseError(e.to_string()))?;
+        (specs[0].url.clone(), provider, chain_id, Some(Arc::new(endpoints)))
     };
 
-    let rate_limiter = match args.requests_per_second {
-        Some(rate_limit) => match (NonZeroU32::new(1), NonZeroU32::new(rate_limit)) {
-            (Some(one), Some(value)) => {
-                let quota = Quota::per_second(value).allow_burst(one);
-                Some(RateLimiter::direct(quota))
-            }
-            _ => None,
-        },
-        None => None,
+    // process rate limits, adaptive rate limiting replaces the static rate limit
+    let (rate_limiter, adaptive_rate_limiter) = if args.adaptive_rate {
+        let initial_rate = args.requests_per_second.unwrap_or(10) as f64;
+        let max_rate = args.max_requests_per_second.map(|x| x as f64);
+        (None, Some(Arc::new(AdaptiveRateLimiter::new(initial_rate, max_rate))))
+    } else {
+        (args.requests_per_second.and_then(create_rate_limiter), None)
     };
 
     // process concurrency info
@@ -57,14 +69,32 @@ pub(crate) async fn parse_source(args: &Args) -> Result<Source, ParseError> {
     let semaphore = tokio::sync::Semaphore::new(max_concurrent_requests as usize);
     let semaphore = Arc::new(Some(semaphore));
 
+    // process response cache
+    let cache = if args.cache {
+        let cache_dir = match &args.cache_dir {
+            Some(cache_dir) => std::path::PathBuf::from(cache_dir),
+            None => super::file_output::parse_local_output_dir(args)?.join(".cryo/cache"),
+        };
+        let max_bytes = match args.cache_size {
+            0 => None,
+            cache_size => Some(cache_size * 1_000_000),
+        };
+        Some(Arc::new(RpcCache::new(cache_dir, max_bytes, args.reorg_buffer)))
+    } else {
+        None
+    };
+
     let output = Source {
         chain_id,
         inner_request_size: args.inner_request_size,
         max_concurrent_chunks,
         semaphore,
         rate_limiter: rate_limiter.into(),
+        adaptive_rate_limiter,
         rpc_url,
         provider,
+        cache,
+        endpoints,
         labels: SourceLabels {
             max_concurrent_requests: args.requests_per_second.map(|x| x as u64),
             max_requests_per_second: args.requests_per_second.map(|x| x as u64),
@@ -76,15 +106,127 @@ pub(crate) async fn parse_source(args: &Args) -> Result<Source, ParseError> {
     Ok(output)
 }
 
-pub(crate) fn parse_rpc_url(args: &Args) -> Result<String, ParseError> {
+async fn parse_provider(args: &Args, rpc_url: &str) -> Result<(ProviderWrapper, u64), ParseError> {
+    if args.record_rpc.is_some() && !rpc_url.starts_with("http") {
+        return Err(ParseError::ParseError("--record-rpc requires an http rpc url".to_string()))
+    }
+    let (provider, chain_id): (ProviderWrapper, u64) = if rpc_url.starts_with("http") {
+        match &args.record_rpc {
+            Some(record_rpc) => {
+                let http: Http = rpc_url.parse().map_err(|_e| {
+                    ParseError::ParseError("could not connect to provider".to_string())
+                })?;
+                let client = RetryClient::new(
+                    http,
+                    Box::new(HttpRateLimitRetryPolicy),
+                    args.max_retries,
+                    args.initial_backoff,
+                );
+                let provider = Provider::new(RecordingClient::new(client, record_rpc.into()));
+                let chain_id =
+                    provider.get_chainid().await.map_err(ParseError::ProviderError)?.as_u64();
+                (provider.into(), chain_id)
+            }
+            None => {
+                let provider = Provider::<RetryClient<Http>>::new_client(
+                    rpc_url,
+                    args.max_retries,
+                    args.initial_backoff,
+                )
+                .map_err(|_e| {
+                    ParseError::ParseError("could not connect to provider".to_string())
+                })?;
+                let chain_id =
+                    provider.get_chainid().await.map_err(ParseError::ProviderError)?.as_u64();
+                (provider.into(), chain_id)
+            }
+        }
+    } else if rpc_url.starts_with("ws") {
+        let provider = Provider::<Ws>::connect(rpc_url).await.map_err(|_| {
+            ParseError::ParseError("could not instantiate HTTP Provider".to_string())
+        })?;
+        let chain_id = provider.get_chainid().await.map_err(ParseError::ProviderError)?.as_u64();
+        (provider.into(), chain_id)
+    } else if rpc_url.ends_with(".ipc") {
+        let provider: Provider<Ipc> = Provider::connect_ipc(rpc_url).await.map_err(|_| {
+            ParseError::ParseError("could not instantiate HTTP Provider".to_string())
+        })?;
+        let chain_id = provider.get_chainid().await.map_err(ParseError::ProviderError)?.as_u64();
+        (provider.into(), chain_id)
+    } else {
+        return Err(ParseError::ParseError(format!("invalid rpc url: {}", rpc_url)));
+    };
+    Ok((provider, chain_id))
+}
+
+fn create_rate_limiter(rate_limit: u32) -> Option<RateLimiter> {
+    match (NonZeroU32::new(1), NonZeroU32::new(rate_limit)) {
+        (Some(one), Some(value)) => {
+            let quota = Quota::per_second(value).allow_burst(one);
+            Some(RateLimiter::direct(quota))
+        }
+        _ => None,
+    }
+}
+
+/// rpc endpoint given by --rpc, MESC, or ETH_RPC_URL
+struct RpcEndpointSpec {
+    label: String,
+    url: String,
+    weight: Option<f64>,
+    rate_limit: Option<u32>,
+}
+
+fn parse_rpc_endpoints(args: &Args) -> Result<Vec<RpcEndpointSpec>, ParseError> {
+    let queries: Vec<Option<String>> = match &args.rpc {
+        Some(rpcs) if !rpcs.is_empty() => rpcs.iter().cloned().map(Some).collect(),
+        _ => vec![None],
+    };
+    let n_endpoints = queries.len();
+    let weights = parse_per_endpoint(&args.rpc_weights, n_endpoints, "--rpc-weights")?;
+    let rate_limits = parse_per_endpoint(&args.rpc_rate_limits, n_endpoints, "--rpc-rate-limits")?;
+
+    let mut specs = Vec::new();
+    for (i, query) in queries.iter().enumerate() {
+        let (label, url, mesc_rate_limit) = parse_rpc_url(query.as_deref())?;
+        specs.push(RpcEndpointSpec {
+            label,
+            url,
+            weight: weights[i],
+            rate_limit: rate_limits[i].or(mesc_rate_limit),
+        })
+    }
+    Ok(specs)
+}
+
+fn parse_per_endpoint<T: Copy>(
+    values: &Option<Vec<T>>,
+    n_endpoints: usize,
+    name: &str,
+) -> Result<Vec<Option<T>>, ParseError> {
+    match values {
+        Some(values) if values.len() == n_endpoints => {
+            Ok(values.iter().map(|x| Some(*x)).collect())
+        }
+        Some(_) => {
+            Err(ParseError::ParseError(format!("{} must have one value per rpc endpoint", name)))
+        }
+        None => Ok(vec![None; n_endpoints]),
+    }
+}
+
+/// resolve rpc query into an endpoint label, url, and MESC rate limit
+pub(crate) fn parse_rpc_url(
+    query: Option<&str>,
+) -> Result<(String, String, Option<u32>), ParseError> {
     // get MESC url
-    let mesc_url = if mesc::is_mesc_enabled() {
-        let endpoint = match &args.rpc {
+    let mesc_endpoint = if mesc::is_mesc_enabled() {
+        let endpoint = match query {
             Some(url) => mesc::get_endpoint_by_query(url, Some("cryo")),
             None => mesc::get_default_endpoint(Some("cryo")),
         };
         match endpoint {
-            Ok(endpoint) => endpoint.map(|endpoint| endpoint.url),
+            Ok(endpoint) => endpoint,
             Err(e) => {
                 eprintln!("Could not load MESC data: {}", e);
                 None
@@ -95,21 +237,40 @@ pub(crate) fn parse_rpc_url(args: &Args) -> Result<String, ParseError> {
     };
 
     // use ETH_RPC_URL if no MESC url found
-    let url = if let Some(url) = mesc_url {
-        url
-    } else if let Some(url) = &args.rpc {
-        url.clone()
+    let (label, url, rate_limit) = if let Some(endpoint) = mesc_endpoint {
+        let rate_limit = endpoint
+            .endpoint_metadata
+            .get("rate_limit_rps")
+            .and_then(|value| value.as_f64())
+            .map(|value| value as u32);
+        (Some(endpoint.name), endpoint.url, rate_limit)
+    } else if let Some(url) = query {
+        (None, url.to_string(), None)
     } else if let Ok(url) = env::var("ETH_RPC_URL") {
-        url
+        (None, url, None)
     } else {
         let message = "must provide --rpc or setup MESC or set ETH_RPC_URL";
         return Err(ParseError::ParseError(message.to_string()))
     };
 
     // prepend http or https if need be
-    if !url.starts_with("http") & !url.starts_with("ws") & !url.ends_with(".ipc") {
-        Ok("http://".to_string() + url.as_str())
+    let url = if !url.starts_with("http") & !url.starts_with("ws") & !url.ends_with(".ipc") {
+        "http://".to_string() + url.as_str()
     } else {
-        Ok(url)
+        url
+    };
+    let label = label.unwrap_or_else(|| redact_url(&url));
+    Ok((label, url, rate_limit))
+}
+
+/// reduce url to its scheme and host, since paths and queries often contain api keys
+fn redact_url(url: &str) -> String {
+    match url.split_once("://") {
+        Some((scheme, rest)) => {
+            let host = rest.split(['/', '?', '#']).next().unwrap_or(rest);
+            let host = host.rsplit('@').next().unwrap_or(host);
+            format!("{}://{}", scheme, host)
+        }
+        None => url.to_string(),
     }
 }
@@ -34,7 +34,7 @@ pub(crate) async fn parse_timestamps(
             let chunk_label = path
                 .split("__")
                 .last()
-                .and_then(|s| s.strip_suffix(".parquet").map(|s| s.to_string()));
+                .and_then(|s| s.split_once('.').map(|(label, _)| label.to_string()));
             file_labels.push(chunk_label);
             file_chunks.push(chunk);
         }
@@ -71,13 +71,7 @@ pub(crate) async fn parse_timestamps(
 }
 
 fn read_integer_column(path: &str, column: &str) -> Result<Vec<u64>, ParseError> {
-    let file = std::fs::File::open(path)
-        .map_err(|_e| ParseError::ParseError("could not open file path".to_string()))?;
-
-    let df = ParquetReader::new(file)
-        .with_columns(Some(vec![column.to_string()]))
-        .finish()
-        .map_err(|_e| ParseError::ParseError("could not read data from column".to_string()))?;
+    let df = cryo_freeze::read_column(path, column)?;
 
     let series = df
         .column(column)
@@ -330,8 +324,8 @@ mod tests {
     use ethers::prelude::*;
 
     async fn setup_source() -> Source {
-        let rpc_url = match crate::parse::source::parse_rpc_url(&Args::default()) {
-            Ok(url) => url,
+        let rpc_url = match crate::parse::source::parse_rpc_url(None) {
+            Ok((_label, url, _rate_limit)) => url,
             Err(_) => std::process::exit(0),
         };
         let max_retry = 5;
@@ -355,6 +349,9 @@ mod tests {
             inner_request_size: 1,
             max_concurrent_chunks: None,
             rpc_url: "".to_string(),
+            adaptive_rate_limiter: None,
+            cache: None,
+            endpoints: None,
             labels: SourceLabels::default(),
         }
     }
@@ -2,8 +2,11 @@ use crate::{args, parse, remember};
 use clap_cryo::Parser;
 use color_print::cstr;
 use colored::Colorize;
-use cryo_freeze::{err, CollectError, ExecutionEnv, FreezeSummary};
-use std::{sync::Arc, time::SystemTime};
+use cryo_freeze::{err, CollectError, CompactTarget, ExecutionEnv, FollowOptions, FreezeSummary};
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 
 /// run cli
 pub async fn run(args: args::Args) -> Result<Option<FreezeSummary>, CollectError> {
@@ -11,8 +14,14 @@ pub async fn run(args: args::Args) -> Result<Option<FreezeSummary>, CollectError
     if args.datatype.first() == Some(&"help".to_string()) {
         return handle_help_subcommands(args).await
     }
+    if args.datatype.first() == Some(&"validate".to_string()) {
+        return handle_validate_subcommand(args)
+    }
+    if args.datatype.first() == Some(&"compact".to_string()) {
+        return handle_compact_subcommand(args)
+    }
 
-    let cryo_dir: std::path::PathBuf = args.output_dir.clone().into();
+    let cryo_dir = parse::file_output::parse_local_output_dir(&args)?;
     let cryo_dir = cryo_dir.join(".cryo");
 
     // remember previous command
@@ -51,7 +60,15 @@ pub async fn run(args: args::Args) -> Result<Option<FreezeSummary>, CollectError
     let source = Arc::new(source);
     let env = ExecutionEnv { t_start_parse, ..env };
     let env = env.set_start_time();
-    cryo_freeze::freeze(&query, &source, &sink, &env).await
+    if args.follow {
+        let options = FollowOptions {
+            chunk_size: args.chunk_size,
+            poll_interval: Duration::from_secs(args.poll_interval),
+        };
+        cryo_freeze::freeze_follow(&query, &source, &sink, &env, &options).await
+    } else {
+        cryo_freeze::freeze(&query, &source, &sink, &env).await
+    }
 }
 
 async fn handle_help_subcommands(args: args::Args) -> Result<Option<FreezeSummary>, CollectError> {
@@ -64,6 +81,7 @@ async fn handle_help_subcommands(args: args::Args) -> Result<Option<FreezeSummar
 - can use ranges                     <white><bold>--blocks 12M:13M 15M:16M</bold></white>
 - can use a parquet file             <white><bold>--blocks ./path/to/file.parquet[:COLUMN_NAME]</bold></white>
 - can use multiple parquet files     <white><bold>--blocks ./path/to/files/*.parquet[:COLUMN_NAME]</bold></white>
+- can use arrow ipc files            <white><bold>--blocks ./path/to/file.arrow[:COLUMN_NAME]</bold></white>
 - numbers can contain { _ . K M B }  <white><bold>5_000 5K 15M 15.5M</bold></white>
 - omitting range end means latest    <white><bold>15.5M:</bold></white> == <white><bold>15.5M:latest</bold></white>
 - omitting range start means 0       <white><bold>:700</bold></white> == <white><bold>0:700</bold></white>
@@ -76,7 +94,8 @@ async fn handle_help_subcommands(args: args::Args) -> Result<Option<FreezeSummar
 - can use transaction hashes         <white><bold>--txs TX_HASH1 TX_HASH2 TX_HASH3</bold></white>
 - can use a parquet file             <white><bold>--txs ./path/to/file.parquet[:COLUMN_NAME]</bold></white>
                                      (default column name is <white><bold>transaction_hash</bold></white>)
-- can use multiple parquet files     <white><bold>--txs ./path/to/ethereum__logs*.parquet</bold></white>"#
+- can use multiple parquet files     <white><bold>--txs ./path/to/ethereum__logs*.parquet</bold></white>
+- can use arrow ipc files            <white><bold>--txs ./path/to/file.arrow[:COLUMN_NAME]</bold></white>"#
         );
         println!("{}", content);
     } else if args.datatype.len() == 2 && args.datatype.contains(&"datasets".to_string()) {
@@ -98,3 +117,57 @@ async fn handle_help_subcommands(args: args::Args) -> Result<Option<FreezeSummar
     }
     Ok(None)
 }
+
+fn handle_validate_subcommand(args: args::Args) -> Result<Option<FreezeSummary>, CollectError> {
+    let args = args::Args { datatype: args.datatype[1..].to_vec(), ..args };
+    let (datatypes, schemas) = parse::schemas::parse_schemas(&args)?;
+
+    // files of all networks are validated unless a network name is given
+    let file_prefix = args.network_name.clone().unwrap_or_default();
+    let sink = parse::file_output::parse_file_output_with_prefix(&args, file_prefix, None)?;
+
+    let report = cryo_freeze::validate(&datatypes, &schemas, &sink)?;
+    if !args.no_verbose {
+        cryo_freeze::print_validation_report(&report);
+    }
+    if !args.no_report {
+        let path = cryo_freeze::write_validation_report(&report, &sink, args.report_dir.clone())?;
+        if !args.no_verbose {
+            println!();
+            println!("validation report written to {}", path.to_string_lossy());
+        }
+    }
+
+    if report.is_valid() {
+        Ok(None)
+    } else {
+        Err(err(format!("validation found {} issues", report.n_issues()).as_str()))
+    }
+}
+
+fn handle_compact_subcommand(args: args::Args) -> Result<Option<FreezeSummary>, CollectError> {
+    let args = args::Args { datatype: args.datatype[1..].to_vec(), ..args };
+    let (datatypes, schemas) = parse::schemas::parse_schemas(&args)?;
+    let file_prefix = args.network_name.clone().unwrap_or_default();
+    let sink = parse::file_output::parse_file_output_with_prefix(&args, file_prefix, None)?;
+
+    let target = match (args.target_blocks, args.target_rows, args.target_bytes) {
+        (Some(n), None, None) => CompactTarget::Blocks(n),
+        (None, Some(n), None) => CompactTarget::Rows(n),
+        (None, None, Some(n)) => CompactTarget::Bytes(n),
+        (None, None, None) => CompactTarget::Bytes(128 * 1024 * 1024),
+        _ => return Err(err("specify only one of --target-blocks, --target-rows, --target-bytes")),
+    };
+    if target == CompactTarget::Blocks(0) ||
+        target == CompactTarget::Rows(0) ||
+        target == CompactTarget::Bytes(0)
+    {
+        return Err(err("compaction target must be greater than 0"))
+    }
+
+    let report = cryo_freeze::compact(&datatypes, &schemas, &sink, target, args.dry)?;
+    if !args.no_verbose {
+        cryo_freeze::print_compaction_report(&report);
+    }
+    Ok(None)
+}
@@ -14,3 +14,272 @@ output formats
 
 command chaining
  */
+
+use cryo_cli::{parse_args, parse_str, run};
+use polars::prelude::*;
+use std::path::PathBuf;
+
+fn fixture_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)
+}
+
+/// collect datatype by replaying the recorded fixtures of a block
+///
+/// `datatype` can be followed by extra arguments, e.g. `"balances --address 0x..."`
+async fn replay(datatype: &str, fixture: &str, blocks: &str) -> DataFrame {
+    let name = datatype.split_whitespace().next().unwrap_or(datatype);
+    let output_dir =
+        std::env::temp_dir().join(format!("cryo_replay_{}_{}", name, std::process::id()));
+    let command = format!(
+        "cryo {} -b {} --replay-rpc {} -o {}",
+        datatype,
+        blocks,
+        fixture_path(fixture).display(),
+        output_dir.display()
+    );
+    let args = parse_str(&command).await.unwrap();
+    let (query, source, _sink, _env) = parse_args(&args).await.unwrap();
+    let df = cryo_freeze::collect(Arc::new(query), Arc::new(source)).await.unwrap();
+    let _ = std::fs::remove_dir_all(output_dir);
+    df
+}
+
+// accounts of devnet_block_1.jsonl
+const ALICE: &str = "0x00000000000000000000000000000000000a11ce";
+const TOKEN: &str = "0x00000000000000000000000000000000000070c3";
+const DEPLOYED: &str = "0x000000000000000000000000000000000000c0de";
+const ALICE_SLOT: &str = "0x0000000000000000000000000000000000000000000000000000000000a11ce0";
+const JS_TRACER: &str = concat!(
+    "{op:null,step:function(log){this.op=log.op.toString()},",
+    "fault:function(){},result:function(){return{lastOp:this.op}}}"
+);
+
+macro_rules! replay_tests {
+    ($($name:ident: $datatype:expr, $fixture:expr, $blocks:expr => $n_rows:expr,)*) => {
+        $(
+            #[tokio::test]
+            async fn $name() {
+                let df = replay($datatype, $fixture, $blocks).await;
+                assert_eq!(df.height(), $n_rows);
+                let replayed = replay($datatype, $fixture, $blocks).await;
+                assert!(df.equals_missing(&replayed));
+            }
+        )*
+    };
+}
+
+replay_tests! {
+    test_replay_blocks: "blocks", "devnet_block_1.jsonl", "1" => 1,
+    test_replay_transactions: "transactions", "devnet_block_1.jsonl", "1" => 2,
+    test_replay_logs: "logs", "devnet_block_1.jsonl", "1" => 1,
+    test_replay_erc20_transfers: "erc20_transfers", "devnet_block_1.jsonl", "1" => 1,
+    test_replay_traces: "traces", "devnet_block_1.jsonl", "1" => 2,
+    test_replay_native_transfers: "native_transfers", "devnet_block_1.jsonl", "1" => 2,
+    test_replay_contracts: "contracts", "devnet_block_1.jsonl", "1" => 1,
+    test_replay_address_appearances: "address_appearances", "devnet_block_1.jsonl", "1" => 12,
+    test_replay_erc20_approvals: "erc20_approvals", "devnet_block_1.jsonl", "1" => 0,
+    test_replay_erc721_transfers: "erc721_transfers", "devnet_block_1.jsonl", "1" => 0,
+    test_replay_balance_diffs: "balance_diffs", "devnet_block_1.jsonl", "1" => 5,
+    test_replay_code_diffs: "code_diffs", "devnet_block_1.jsonl", "1" => 1,
+    test_replay_nonce_diffs: "nonce_diffs", "devnet_block_1.jsonl", "1" => 3,
+    test_replay_storage_diffs: "storage_diffs", "devnet_block_1.jsonl", "1" => 2,
+    test_replay_vm_traces: "vm_traces", "devnet_block_1.jsonl", "1" => 7,
+    test_replay_balance_reads: "balance_reads", "devnet_block_1.jsonl", "1" => 6,
+    test_replay_code_reads: "code_reads", "devnet_block_1.jsonl", "1" => 1,
+    test_replay_nonce_reads: "nonce_reads", "devnet_block_1.jsonl", "1" => 3,
+    test_replay_storage_reads: "storage_reads", "devnet_block_1.jsonl", "1" => 2,
+    test_replay_geth_calls: "geth_calls", "devnet_block_1.jsonl", "1" => 2,
+    test_replay_geth_opcodes: "geth_opcodes", "devnet_block_1.jsonl", "1" => 7,
+    test_replay_four_byte_counts: "four_byte_counts", "devnet_block_1.jsonl", "1" => 1,
+    test_replay_geth_balance_diffs: "geth_balance_diffs", "devnet_block_1.jsonl", "1" => 6,
+    test_replay_geth_code_diffs: "geth_code_diffs", "devnet_block_1.jsonl", "1" => 6,
+    test_replay_geth_nonce_diffs: "geth_nonce_diffs", "devnet_block_1.jsonl", "1" => 6,
+    test_replay_geth_storage_diffs: "geth_storage_diffs", "devnet_block_1.jsonl", "1" => 2,
+    test_replay_javascript_traces: &format!("javascript_traces --js-tracer {}", JS_TRACER), "devnet_block_1.jsonl", "1" => 2,
+    test_replay_balances: &format!("balances --address {}", ALICE), "devnet_block_1.jsonl", "1" => 1,
+    test_replay_codes: &format!("codes --address {}", DEPLOYED), "devnet_block_1.jsonl", "1" => 1,
+    test_replay_nonces: &format!("nonces --address {}", ALICE), "devnet_block_1.jsonl", "1" => 1,
+    test_replay_slots: &format!("slots --contract {} --slot {}", TOKEN, ALICE_SLOT), "devnet_block_1.jsonl", "1" => 1,
+    test_replay_erc20_balances: &format!("erc20_balances --contract {} --address {}", TOKEN, ALICE), "devnet_block_1.jsonl", "1" => 1,
+    test_replay_erc20_metadata: &format!("erc20_metadata --contract {}", TOKEN), "devnet_block_1.jsonl", "1" => 1,
+    test_replay_erc20_supplies: &format!("erc20_supplies --contract {}", TOKEN), "devnet_block_1.jsonl", "1" => 1,
+    test_replay_erc721_metadata: &format!("erc721_metadata --contract {}", TOKEN), "devnet_block_1.jsonl", "1" => 1,
+    test_replay_eth_calls: &format!("eth_calls --contract {} --call-data 0x18160ddd", TOKEN), "devnet_block_1.jsonl", "1" => 1,
+    test_replay_trace_calls: &format!("trace_calls --contract {} --call-data 0x18160ddd", TOKEN), "devnet_block_1.jsonl", "1" => 1,
+    test_replay_reverted_transactions: &format!("transactions {}", REVERT_COLUMNS), "devnet_block_3.jsonl", "3" => 3,
+    test_replay_reverted_traces: &format!("traces {}", REVERT_COLUMNS), "devnet_block_3.jsonl", "3" => 3,
+    test_replay_reverted_geth_calls: &format!("geth_calls {}", REVERT_COLUMNS), "devnet_block_3.jsonl", "3" => 3,
+}
+
+// failed transactions of devnet_block_3.jsonl, reverting with an error string, a panic, and a
+// custom error
+const REVERT_COLUMNS: &str = "--include-columns revert_reason panic_code error_selector";
+
+#[tokio::test]
+async fn test_replay_revert_columns() {
+    for datatype in ["transactions", "traces", "geth_calls"] {
+        let command = format!("{} {}", datatype, REVERT_COLUMNS);
+        let df = replay(&command, "devnet_block_3.jsonl", "3").await;
+        let reasons: Vec<_> =
+            df.column("revert_reason").unwrap().str().unwrap().into_iter().collect();
+        assert_eq!(reasons, vec![Some("ERC20: transfer amount exceeds balance"), None, None]);
+        let codes: Vec<_> = df.column("panic_code").unwrap().u64().unwrap().into_iter().collect();
+        assert_eq!(codes, vec![None, Some(0x11), None]);
+        let selectors: Vec<_> =
+            df.column("error_selector").unwrap().binary().unwrap().into_iter().collect();
+        assert_eq!(selectors, vec![None, None, Some([0xcf, 0x47, 0x91, 0x81].as_slice())]);
+    }
+}
+
+// event of devnet_block_1.jsonl with array and tuple params
+const SWAP_EVENT: &str = "Swap(string indexed pool, uint256[] amounts, (address,uint8) side)";
+const SWAP_TOPIC: &str = "0x67b15ec7971e6851866251c56ff5799ba99efdb33e592ea69b92d78c596e5650";
+
+#[tokio::test]
+async fn test_replay_nested_event_columns() {
+    let command = format!(
+        "cryo logs -b 1 --topic0 {} --u256-types string --replay-rpc {}",
+        SWAP_TOPIC,
+        fixture_path("devnet_block_1.jsonl").display(),
+    );
+    let mut args = parse_str(&command).await.unwrap();
+    args.event_signature = Some(SWAP_EVENT.to_string());
+    let (query, source, _sink, _env) = parse_args(&args).await.unwrap();
+    let df = cryo_freeze::collect(Arc::new(query), Arc::new(source)).await.unwrap();
+    assert_eq!(df.height(), 1);
+
+    // indexed strings are stored as hashes
+    assert_eq!(df.column("event__pool").unwrap().dtype(), &DataType::Binary);
+
+    let amounts = df.column("event__amounts_string").unwrap();
+    assert_eq!(amounts.dtype(), &DataType::List(Box::new(DataType::String)));
+    let amounts = amounts.list().unwrap().get_as_series(0).unwrap();
+    let amounts: Vec<_> = amounts.str().unwrap().into_no_null_iter().collect();
+    assert_eq!(amounts, vec!["1000000000000000000", "2000000000000000000"]);
+
+    let side = df.column("event__side").unwrap();
+    let fields = side.struct_().unwrap().fields().to_vec();
+    assert_eq!(fields[0].dtype(), &DataType::Binary);
+    assert_eq!(fields[1].u64().unwrap().get(0), Some(1));
+}
+
+/// freeze datatype by replaying fixtures, returning the summary and the rows of output files
+async fn replay_freeze(
+    command: &str,
+    fixture: &str,
+    output_dir: &std::path::Path,
+) -> (cryo_freeze::FreezeSummary, usize) {
+    let command = format!(
+        "{} --replay-rpc {} -o {} --no-report",
+        command,
+        fixture_path(fixture).display(),
+        output_dir.display()
+    );
+    let args = parse_str(&command).await.unwrap();
+    let (query, source, sink, env) = parse_args(&args).await.unwrap();
+    let summary = cryo_freeze::freeze(&query, &source, &sink, &env).await.unwrap().unwrap();
+    let mut n_rows = 0;
+    for entry in std::fs::read_dir(output_dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().and_then(|ext| ext.to_str()) == Some("parquet") {
+            let file = std::fs::File::open(path).unwrap();
+            n_rows += ParquetReader::new(file).finish().unwrap().height();
+        }
+    }
+    (summary, n_rows)
+}
+
+#[tokio::test]
+async fn test_checkpoints_resume_from_parts() {
+    let output_dir =
+        std::env::temp_dir().join(format!("cryo_checkpoints_resume_{}", std::process::id()));
+    let command = "cryo blocks -b 1:3 --checkpoint-size 1";
+
+    // block 2 is missing from the first fixture, so only the part of block 1 is saved
+    let (summary, n_rows) = replay_freeze(command, "devnet_block_1.jsonl", &output_dir).await;
+    assert_eq!(summary.errored.len(), 1);
+    assert_eq!(n_rows, 0);
+
+    // block 1 is missing from the second fixture, so it can only come from the saved part
+    let (summary, n_rows) = replay_freeze(command, "devnet_block_2.jsonl", &output_dir).await;
+    assert!(summary.errored.is_empty());
+    assert_eq!(n_rows, 2);
+    assert!(!output_dir.join(".cryo/checkpoints").read_dir().unwrap().any(|_| true));
+
+    let _ = std::fs::remove_dir_all(output_dir);
+}
+
+#[tokio::test]
+async fn test_checkpoints_skip_parts_of_other_schemas() {
+    let output_dir =
+        std::env::temp_dir().join(format!("cryo_checkpoints_schema_{}", std::process::id()));
+    let command = "cryo blocks -b 1:3 --checkpoint-size 1";
+    let (summary, _) = replay_freeze(command, "devnet_block_1.jsonl", &output_dir).await;
+    assert_eq!(summary.errored.len(), 1);
+
+    // the saved part of block 1 has other columns, so block 1 must be collected again
+    let command = "cryo blocks -b 1:3 --checkpoint-size 1 --exclude-columns gas_used";
+    let (summary, n_rows) = replay_freeze(command, "devnet_block_2.jsonl", &output_dir).await;
+    assert_eq!(summary.errored.len(), 1);
+    assert_eq!(n_rows, 0);
+
+    let _ = std::fs::remove_dir_all(output_dir);
+}
+
+#[tokio::test]
+async fn test_compact_replaces_chunks() {
+    let output_dir = std::env::temp_dir().join(format!("cryo_compact_{}", std::process::id()));
+    let command = "cryo blocks --chunk-size 1";
+    replay_freeze(&format!("{} -b 1", command), "devnet_block_1.jsonl", &output_dir).await;
+    replay_freeze(&format!("{} -b 2", command), "devnet_block_2.jsonl", &output_dir).await;
+
+    let compact =
+        format!("cryo compact blocks -o {} --target-blocks 10 --no-verbose", output_dir.display());
+    run(parse_str(&compact).await.unwrap()).await.unwrap();
+    let mut files: Vec<_> = std::fs::read_dir(&output_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .filter(|name| name.ends_with(".parquet"))
+        .collect();
+    files.sort();
+    assert_eq!(files, vec!["network_1337__blocks__00000001_to_00000002.parquet"]);
+
+    let manifest = std::fs::read_to_string(output_dir.join(".cryo/manifest.jsonl")).unwrap();
+    let entry: serde_json::Value = manifest
+        .lines()
+        .find(|line| line.contains(&files[0]))
+        .map(serde_json::from_str)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        entry["replaces"],
+        serde_json::json!([
+            "network_1337__blocks__00000001_to_00000001.parquet",
+            "network_1337__blocks__00000002_to_00000002.parquet",
+        ])
+    );
+
+    // block 2 is missing from the first fixture, so it can only be skipped
+    let command = format!("{} -b 1:3", command);
+    let (summary, n_rows) = replay_freeze(&command, "devnet_block_1.jsonl", &output_dir).await;
+    assert!(summary.errored.is_empty());
+    assert!(summary.completed.is_empty());
+    assert_eq!(summary.skipped.len(), 2);
+    assert_eq!(n_rows, 2);
+
+    let _ = std::fs::remove_dir_all(output_dir);
+}
+
+#[tokio::test]
+async fn test_retry_errors_kinds() {
+    let command = format!(
+        "cryo blocks -b 1 --replay-rpc {} --retry-errors",
+        fixture_path("devnet_block_1.jsonl").display()
+    );
+    let args = parse_str(&format!("{} provider too_many_requests", command)).await.unwrap();
+    assert!(parse_args(&args).await.is_ok());
+
+    let args = parse_str(&format!("{} provider timeout", command)).await.unwrap();
+    let error = parse_args(&args).await.err().unwrap().to_string();
+    assert!(error.contains("unknown --retry-errors kind: timeout"));
+}
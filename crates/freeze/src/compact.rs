@@ -0,0 +1,464 @@
+use crate::{
+    dataframes::{self, SortableDataFrame},
+    err, manifests,
+    reorgs::{self, ChunkBlockHashes},
+    validate::{find_files, read_file, LabelBlockRange, LabeledFile},
+    CollectError, Datatype, FileOutput, Manifest, ManifestEntry, Table, CRYO_VERSION,
+};
+use polars::prelude::*;
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+/// size that adjacent chunk files are merged up to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompactTarget {
+    /// number of blocks spanned by each merged file
+    Blocks(u64),
+    /// number of rows in each merged file
+    Rows(u64),
+    /// number of bytes of each merged file
+    Bytes(u64),
+}
+
+/// result of compacting the files of an output directory
+#[derive(Clone, Debug, Serialize)]
+pub struct CompactionReport {
+    /// version of cryo used to compact
+    pub cryo_version: String,
+    /// output directory that was compacted
+    pub output_dir: PathBuf,
+    /// target size of merged files
+    pub target: CompactTarget,
+    /// whether merges were only planned, without writing files
+    pub dry: bool,
+    /// merged files
+    pub merges: Vec<CompactedFile>,
+}
+
+/// file created by merging adjacent chunk files
+#[derive(Clone, Debug, Serialize)]
+pub struct CompactedFile {
+    /// name of datatype
+    pub datatype: String,
+    /// path of merged file
+    pub path: PathBuf,
+    /// paths of chunk files that were merged
+    pub replaces: Vec<PathBuf>,
+    /// first block of merged file
+    pub first_block: u64,
+    /// last block of merged file
+    pub last_block: u64,
+    /// number of rows of merged file
+    pub n_rows: u64,
+}
+
+/// chunk file along with its block range and size
+struct ChunkFile {
+    path: PathBuf,
+    label: String,
+    range: LabelBlockRange,
+    n_rows: Option<u64>,
+    n_bytes: u64,
+}
+
+/// merge adjacent chunk files of datatypes into files of the target size
+///
+/// merged files are re-sorted by the schema's sort columns, written atomically, and read back
+/// before the chunk files they replace are deleted. the manifest records which chunk files each
+/// merged file replaces, so later runs skip the merged chunks
+pub fn compact(
+    datatypes: &[Datatype],
+    schemas: &HashMap<Datatype, Table>,
+    sink: &FileOutput,
+    target: CompactTarget,
+    dry: bool,
+) -> Result<CompactionReport, CollectError> {
+    if !sink.writes_files() || sink.remote.is_some() {
+        return Err(err("compaction requires a local output directory"))
+    }
+    let mut manifest = manifests::read_manifest(sink)?;
+    let replaced = replaced_paths(sink, &manifest);
+    let mut merges = Vec::new();
+    let mut removed = HashSet::new();
+    for datatype in datatypes.iter() {
+        let table = schemas
+            .get(datatype)
+            .ok_or_else(|| err(format!("schema not provided for {}", datatype.name()).as_str()))?;
+        for (_network, files) in find_files(sink, *datatype)?.into_iter() {
+            for mut group in group_files(sink, files, &manifest).into_values() {
+                group.sort_by_key(|file| (file.range.start, file.range.end));
+                let group = remove_compacted_leftovers(sink, group, &replaced, dry, &mut removed)?;
+                for batch in plan_batches(group, target)?.into_iter() {
+                    let merged = merge_batch(sink, *datatype, table, &batch, &manifest, dry)?;
+                    if let Some(entry) = merged.1 {
+                        manifest.insert(entry.path.clone(), entry);
+                    }
+                    if !dry {
+                        for file in batch.iter() {
+                            remove_chunk_file(sink, &file.path)?;
+                            removed.insert(sink.relative_path(&file.path));
+                        }
+                    }
+                    merges.push(merged.0);
+                }
+            }
+        }
+    }
+    if !removed.is_empty() {
+        manifests::remove_manifest_entries(sink, &removed)?;
+    }
+    Ok(CompactionReport {
+        cryo_version: CRYO_VERSION.to_string(),
+        output_dir: sink.output_dir.clone(),
+        target,
+        dry,
+        merges,
+    })
+}
+
+/// group files that differ only in block range, so that only files in the same directory with the
+/// same other partition dimensions are merged
+fn group_files(
+    sink: &FileOutput,
+    files: Vec<LabeledFile>,
+    manifest: &Manifest,
+) -> BTreeMap<(PathBuf, String), Vec<ChunkFile>> {
+    let mut groups: BTreeMap<(PathBuf, String), Vec<ChunkFile>> = BTreeMap::new();
+    for file in files.into_iter() {
+        let range = match LabelBlockRange::parse(&file.label) {
+            Some(range) => range,
+            None => continue,
+        };
+        let dir = file.path.parent().map(|dir| dir.to_path_buf()).unwrap_or_default();
+        let n_bytes = std::fs::metadata(&file.path).map(|metadata| metadata.len()).unwrap_or(0);
+        let n_rows = manifest_entry(sink, manifest, &file.path).map(|entry| entry.n_rows);
+        let key = (dir, range.group.clone());
+        let file = ChunkFile { path: file.path, label: file.label, range, n_rows, n_bytes };
+        groups.entry(key).or_default().push(file);
+    }
+    groups
+}
+
+/// entry of file itself, ignoring entries of files that it was compacted into
+fn manifest_entry<'a>(
+    sink: &FileOutput,
+    manifest: &'a Manifest,
+    path: &Path,
+) -> Option<&'a ManifestEntry> {
+    let relative = sink.relative_path(path);
+    manifest.get(&relative).filter(|entry| entry.path == relative)
+}
+
+/// paths of chunk files replaced by existing merged files
+fn replaced_paths(sink: &FileOutput, manifest: &Manifest) -> HashSet<PathBuf> {
+    manifest
+        .iter()
+        .filter(|(path, entry)| **path == entry.path && sink.output_dir.join(path).exists())
+        .flat_map(|(_, entry)| entry.replaces.iter().cloned())
+        .collect()
+}
+
+/// delete chunk files left behind by an interrupted compaction
+fn remove_compacted_leftovers(
+    sink: &FileOutput,
+    group: Vec<ChunkFile>,
+    replaced: &HashSet<PathBuf>,
+    dry: bool,
+    removed: &mut HashSet<PathBuf>,
+) -> Result<Vec<ChunkFile>, CollectError> {
+    let mut kept = Vec::new();
+    for file in group.into_iter() {
+        let relative = sink.relative_path(&file.path);
+        if replaced.contains(&relative) {
+            if !dry {
+                remove_chunk_file(sink, &file.path)?;
+            }
+            removed.insert(relative);
+        } else {
+            kept.push(file);
+        }
+    }
+    Ok(kept)
+}
+
+/// split sorted files into batches of adjacent files up to the target size
+///
+/// batches of a single file are left as they are
+fn plan_batches(
+    files: Vec<ChunkFile>,
+    target: CompactTarget,
+) -> Result<Vec<Vec<ChunkFile>>, CollectError> {
+    let mut batches = Vec::new();
+    let mut batch: Vec<ChunkFile> = Vec::new();
+    for mut file in files.into_iter() {
+        if let (CompactTarget::Rows(_), None) = (target, file.n_rows) {
+            file.n_rows = Some(read_file(&file.path)?.height() as u64);
+        }
+        let fits = match (batch.first(), batch.last()) {
+            (Some(first), Some(last)) if file.range.start == last.range.end + 1 => match target {
+                CompactTarget::Blocks(n_blocks) => file.range.end - first.range.start < n_blocks,
+                CompactTarget::Rows(n_rows) => {
+                    let batch_rows: u64 = batch.iter().filter_map(|file| file.n_rows).sum();
+                    batch_rows + file.n_rows.unwrap_or(0) <= n_rows
+                }
+                CompactTarget::Bytes(n_bytes) => {
+                    let batch_bytes: u64 = batch.iter().map(|file| file.n_bytes).sum();
+                    batch_bytes + file.n_bytes <= n_bytes
+                }
+            },
+            _ => false,
+        };
+        if !fits {
+            let previous = std::mem::take(&mut batch);
+            if previous.len() > 1 {
+                batches.push(previous);
+            }
+        }
+        batch.push(file);
+    }
+    if batch.len() > 1 {
+        batches.push(batch);
+    }
+    Ok(batches)
+}
+
+/// whether a merged file read back holds the same columns and values as the merged rows
+///
+/// text files do not keep dtypes, so columns read back are cast to the dtypes they were written as
+fn same_rows(written: &DataFrame, df: &DataFrame) -> bool {
+    if written.get_column_names() != df.get_column_names() {
+        return false
+    }
+    let columns: Result<Vec<Series>, PolarsError> = written
+        .get_columns()
+        .iter()
+        .zip(df.get_columns())
+        .map(|(written, column)| written.cast(column.dtype()))
+        .collect();
+    match columns.and_then(DataFrame::new) {
+        Ok(written) => written.equals_missing(df),
+        Err(_) => false,
+    }
+}
+
+/// merge batch into a single file, returning the merged file and its manifest entry
+fn merge_batch(
+    sink: &FileOutput,
+    datatype: Datatype,
+    table: &Table,
+    batch: &[ChunkFile],
+    manifest: &Manifest,
+    dry: bool,
+) -> Result<(CompactedFile, Option<ManifestEntry>), CollectError> {
+    let (first, last) = match (batch.first(), batch.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return Err(err("no files to compact")),
+    };
+    let (first_block, last_block) = (first.range.start, last.range.end);
+    let label = first.range.label(first_block, last_block);
+    let path = merged_path(sink, &first.path, &first.label, &label)?;
+    if path.exists() && !batch.iter().any(|file| file.path == path) {
+        let message = format!("cannot compact into existing file {}", path.to_string_lossy());
+        return Err(err(&message))
+    }
+
+    let mut replaces = Vec::new();
+    for file in batch.iter() {
+        replaces.push(sink.relative_path(&file.path));
+        if let Some(entry) = manifest_entry(sink, manifest, &file.path) {
+            replaces.extend(entry.replaces.clone());
+        }
+    }
+    let mut merged = CompactedFile {
+        datatype: datatype.name(),
+        path: path.clone(),
+        replaces,
+        first_block,
+        last_block,
+        n_rows: batch.iter().filter_map(|file| file.n_rows).sum(),
+    };
+    if dry {
+        return Ok((merged, None))
+    }
+
+    // merge and sort rows, skipping empty files whose column dtypes may not be resolved
+    let mut dfs = Vec::new();
+    for file in batch.iter() {
+        dfs.push(read_file(&file.path)?);
+    }
+    let n_rows: usize = dfs.iter().map(|df| df.height()).sum();
+    let (mut non_empty, empty): (Vec<_>, Vec<_>) = dfs.into_iter().partition(|df| df.height() > 0);
+    let mut df = match non_empty.is_empty() {
+        true => empty.into_iter().next().ok_or_else(|| err("no files to compact"))?,
+        false => non_empty.remove(0),
+    };
+    for other in non_empty.iter() {
+        df.vstack_mut(other).map_err(|e| {
+            err(&format!("cannot compact files with different columns into {}: {}", label, e))
+        })?;
+    }
+    df.align_chunks();
+    let names = df.get_column_names();
+    let sortable = match &table.sort_columns {
+        Some(sort_columns) => sort_columns.iter().all(|column| names.contains(&column.as_str())),
+        None => false,
+    };
+    let mut df = match sortable {
+        true => Ok::<DataFrame, CollectError>(df).sort_by_schema(table)?,
+        false => df,
+    };
+
+    // write merged file atomically and read it back before anything is deleted
+    dataframes::df_to_file(&mut df, &path, sink)
+        .map_err(|_| err(&format!("could not write {}", path.to_string_lossy())))?;
+    let written = read_file(&path)?;
+    if written.height() != n_rows || !same_rows(&written, &df) {
+        let _ = std::fs::remove_file(&path);
+        return Err(err(&format!("verification of {} failed", path.to_string_lossy())))
+    }
+    merged.n_rows = n_rows as u64;
+
+    if let (Some(first_hashes), Some(last_hashes)) = (
+        reorgs::read_chunk_block_hashes(sink, &first.path),
+        reorgs::read_chunk_block_hashes(sink, &last.path),
+    ) {
+        let hashes = ChunkBlockHashes {
+            first_block: first_hashes.first_block,
+            first_block_hash: first_hashes.first_block_hash,
+            first_parent_hash: first_hashes.first_parent_hash,
+            last_block: last_hashes.last_block,
+            last_block_hash: last_hashes.last_block_hash,
+            last_parent_hash: last_hashes.last_parent_hash,
+        };
+        reorgs::write_chunk_block_hashes(sink, &path, &hashes)?;
+    }
+
+    // merged files keep the schema fingerprint of their chunks when the chunks agree on it
+    let entries: Vec<&ManifestEntry> =
+        batch.iter().filter_map(|file| manifest_entry(sink, manifest, &file.path)).collect();
+    let (schema_fingerprint, cryo_version) = match entries.first() {
+        Some(entry)
+            if entries.len() == batch.len() &&
+                entries
+                    .iter()
+                    .all(|other| other.schema_fingerprint == entry.schema_fingerprint) =>
+        {
+            (entry.schema_fingerprint.clone(), entry.cryo_version.clone())
+        }
+        _ => (manifests::schema_fingerprint(table), CRYO_VERSION.to_string()),
+    };
+    let contents = std::fs::read(&path).map_err(|_| err("could not read written file"))?;
+    let entry = ManifestEntry {
+        path: sink.relative_path(&path),
+        datatype: datatype.name(),
+        partition: label,
+        schema_fingerprint,
+        cryo_version,
+        n_rows: n_rows as u64,
+        first_block: Some(first_block),
+        last_block: Some(last_block),
+        n_bytes: contents.len() as u64,
+        checksum: prefix_hex::encode(ethers::utils::keccak256(&contents).to_vec()),
+        replaces: merged.replaces.clone(),
+    };
+    manifests::append_manifest_entry(sink, &entry)?;
+    Ok((merged, Some(entry)))
+}
+
+/// path of merged file, named after the first chunk file with the merged block range
+fn merged_path(
+    sink: &FileOutput,
+    first_path: &Path,
+    first_label: &str,
+    label: &str,
+) -> Result<PathBuf, CollectError> {
+    let suffix = format!("{}.{}", first_label, sink.format.as_str());
+    let filename = first_path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+    let prefix = filename
+        .strip_suffix(suffix.as_str())
+        .ok_or_else(|| err(&format!("could not parse file name {}", filename)))?;
+    let filename = format!("{}{}.{}", prefix, label, sink.format.as_str());
+    Ok(first_path.with_file_name(filename))
+}
+
+/// delete chunk file along with its recorded block hashes
+fn remove_chunk_file(sink: &FileOutput, path: &Path) -> Result<(), CollectError> {
+    std::fs::remove_file(path)
+        .map_err(|_| err(&format!("could not remove {}", path.to_string_lossy())))?;
+    let _ = std::fs::remove_file(reorgs::get_block_hashes_path(sink, path));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(start: u64, end: u64, n_rows: u64) -> ChunkFile {
+        let label = format!("{:08}_to_{:08}", start, end);
+        ChunkFile {
+            path: PathBuf::from(format!("{}.parquet", label)),
+            range: LabelBlockRange::parse(&label).unwrap(),
+            label,
+            n_rows: Some(n_rows),
+            n_bytes: n_rows * 10,
+        }
+    }
+
+    fn batch_ranges(batches: Vec<Vec<ChunkFile>>) -> Vec<(u64, u64)> {
+        batches
+            .iter()
+            .map(|batch| (batch[0].range.start, batch[batch.len() - 1].range.end))
+            .collect()
+    }
+
+    #[test]
+    fn test_plan_batches() {
+        let files = || {
+            vec![
+                chunk(0, 99, 10),
+                chunk(100, 199, 10),
+                chunk(200, 299, 10),
+                chunk(400, 499, 10),
+                chunk(500, 599, 10),
+            ]
+        };
+
+        // gaps between files end a batch
+        let batches = plan_batches(files(), CompactTarget::Blocks(1000)).unwrap();
+        assert_eq!(batch_ranges(batches), vec![(0, 299), (400, 599)]);
+
+        let batches = plan_batches(files(), CompactTarget::Blocks(200)).unwrap();
+        assert_eq!(batch_ranges(batches), vec![(0, 199), (400, 599)]);
+
+        let batches = plan_batches(files(), CompactTarget::Rows(30)).unwrap();
+        assert_eq!(batch_ranges(batches), vec![(0, 299), (400, 599)]);
+
+        let batches = plan_batches(files(), CompactTarget::Bytes(200)).unwrap();
+        assert_eq!(batch_ranges(batches), vec![(0, 199), (400, 599)]);
+    }
+
+    #[test]
+    fn test_merged_label() {
+        let range = LabelBlockRange::parse("00000100_to_00000199__0xaaaa_to_0xaaaa").unwrap();
+        assert_eq!(range.label(100, 999), "00000100_to_00000999__0xaaaa_to_0xaaaa");
+    }
+
+    #[test]
+    fn test_same_rows() {
+        let df = DataFrame::new(vec![Series::new("block_number", vec![1u64, 2])]).unwrap();
+
+        // csv files read back integers as signed
+        let written = DataFrame::new(vec![Series::new("block_number", vec![1i64, 2])]).unwrap();
+        assert!(same_rows(&written, &df));
+
+        let written = DataFrame::new(vec![Series::new("block_number", vec![1u64, 3])]).unwrap();
+        assert!(!same_rows(&written, &df));
+
+        let written = DataFrame::new(vec![Series::new("timestamp", vec![1u64, 2])]).unwrap();
+        assert!(!same_rows(&written, &df));
+    }
+}
@@ -32,6 +32,10 @@ pub struct Blocks {
 
 #[async_trait::async_trait]
 impl Dataset for Blocks {
+    fn primary_key() -> Option<Vec<&'static str>> {
+        Some(vec!["block_number"])
+    }
+
     fn default_columns() -> Option<Vec<&'static str>> {
         Some(vec![
             "block_number",
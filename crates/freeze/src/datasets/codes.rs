@@ -19,6 +19,10 @@ impl Dataset for Codes {
         Some(vec!["block_number", "address"])
     }
 
+    fn primary_key() -> Option<Vec<&'static str>> {
+        Some(vec!["block_number", "address"])
+    }
+
     fn required_parameters() -> Vec<Dim> {
         vec![Dim::Address]
     }
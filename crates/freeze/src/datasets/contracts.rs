@@ -30,6 +30,10 @@ impl Dataset for Contracts {
     fn default_sort() -> Option<Vec<&'static str>> {
         Some(vec!["block_number", "create_index"])
     }
+
+    fn primary_key() -> Option<Vec<&'static str>> {
+        Some(vec!["block_number", "create_index"])
+    }
 }
 
 #[async_trait::async_trait]
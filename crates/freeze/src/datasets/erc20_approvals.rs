@@ -21,6 +21,10 @@ pub struct Erc20Approvals {
 
 #[async_trait::async_trait]
 impl Dataset for Erc20Approvals {
+    fn primary_key() -> Option<Vec<&'static str>> {
+        Some(vec!["block_number", "log_index"])
+    }
+
     fn default_columns() -> Option<Vec<&'static str>> {
         Some(vec![
             "block_number",
@@ -54,7 +58,7 @@ impl CollectByBlock for Erc20Approvals {
     type Response = Vec<Log>;
 
     async fn extract(request: Params, source: Arc<Source>, _: Arc<Query>) -> R<Self::Response> {
-        let mut topics = [Some(ValueOrArray::Value(Some(*EVENT_ERC20_TRANSFER))), None, None, None];
+        let mut topics = [Some(ValueOrArray::Value(Some(*EVENT_ERC20_APPROVAL))), None, None, None];
         if let Some(from_address) = &request.from_address {
             let mut v = vec![0u8; 12];
             v.append(&mut from_address.to_owned());
@@ -21,6 +21,10 @@ pub struct Erc721Transfers {
 
 #[async_trait::async_trait]
 impl Dataset for Erc721Transfers {
+    fn primary_key() -> Option<Vec<&'static str>> {
+        Some(vec!["block_number", "log_index"])
+    }
+
     fn default_columns() -> Option<Vec<&'static str>> {
         Some(vec![
             "block_number",
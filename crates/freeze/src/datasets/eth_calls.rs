@@ -13,6 +13,7 @@ pub struct EthCalls {
     call_data_hash: Vec<Vec<u8>>,
     output_data: Vec<Option<Vec<u8>>>,
     output_data_hash: Vec<Option<Vec<u8>>>,
+    revert_reason: Vec<Option<String>>,
     chain_id: Vec<u64>,
 }
 
@@ -39,7 +40,7 @@ impl Dataset for EthCalls {
     }
 }
 
-type EthCallsResponse = (u32, Vec<u8>, Vec<u8>, Option<Vec<u8>>);
+type EthCallsResponse = (u32, Vec<u8>, Vec<u8>, Option<Vec<u8>>, Option<Vec<u8>>);
 
 #[async_trait::async_trait]
 impl CollectByBlock for EthCalls {
@@ -52,8 +53,11 @@ impl CollectByBlock for EthCalls {
             ..Default::default()
         };
         let number = request.block_number()?;
-        let output = source.call(transaction, number.into()).await.ok().map(|x| x.to_vec());
-        Ok((number as u32, request.contract()?, request.call_data()?, output))
+        let (output, revert) = match source.call(transaction, number.into()).await {
+            Ok(output) => (Some(output.to_vec()), None),
+            Err(e) => (None, revert_data(&e)),
+        };
+        Ok((number as u32, request.contract()?, request.call_data()?, output, revert))
     }
 
     fn transform(response: Self::Response, columns: &mut Self, query: &Arc<Query>) -> R<()> {
@@ -68,7 +72,7 @@ impl CollectByTransaction for EthCalls {
 }
 
 fn process_eth_call(response: EthCallsResponse, columns: &mut EthCalls, schema: &Table) {
-    let (block_number, contract_address, call_data, output_data) = response;
+    let (block_number, contract_address, call_data, output_data, revert) = response;
     columns.n_rows += 1;
     store!(schema, columns, block_number, block_number);
     store!(schema, columns, contract_address, contract_address);
@@ -81,4 +85,5 @@ fn process_eth_call(response: EthCallsResponse, columns: &mut EthCalls, schema:
         output_data_hash,
         output_data.map(|data| ethers_core::utils::keccak256(data).into())
     );
+    store!(schema, columns, revert_reason, revert.and_then(|data| decode_revert_reason(&data)));
 }
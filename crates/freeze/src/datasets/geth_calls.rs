@@ -20,11 +20,39 @@ pub struct GethCalls {
     transaction_hash: Vec<Option<Vec<u8>>>,
     transaction_index: Vec<u32>,
     trace_address: Vec<String>,
+    revert_data: Vec<Option<Vec<u8>>>,
+    revert_reason: Vec<Option<String>>,
+    panic_code: Vec<Option<u64>>,
+    panic_reason: Vec<Option<String>>,
+    error_selector: Vec<Option<Vec<u8>>>,
     chain_id: Vec<u64>,
 }
 
 #[async_trait::async_trait]
-impl Dataset for GethCalls {}
+impl Dataset for GethCalls {
+    fn primary_key() -> Option<Vec<&'static str>> {
+        Some(vec!["block_number", "transaction_index", "trace_address"])
+    }
+
+    fn default_columns() -> Option<Vec<&'static str>> {
+        Some(vec![
+            "typ",
+            "from_address",
+            "to_address",
+            "value",
+            "gas",
+            "gas_used",
+            "input",
+            "output",
+            "error",
+            "block_number",
+            "transaction_hash",
+            "transaction_index",
+            "trace_address",
+            "chain_id",
+        ])
+    }
+}
 
 #[async_trait::async_trait]
 impl CollectByBlock for GethCalls {
@@ -89,7 +117,20 @@ fn process_trace(
     store!(schema, columns, gas, trace.gas);
     store!(schema, columns, gas_used, trace.gas_used);
     store!(schema, columns, input, trace.input.0.to_vec());
-    store!(schema, columns, output, trace.output.map(|x| x.0.to_vec()));
+    let output = trace.output.map(|x| x.0.to_vec());
+
+    // revert data is the output of failed calls
+    let revert_data = match &trace.error {
+        Some(_) => output.clone().filter(|output| !output.is_empty()),
+        None => None,
+    };
+    let revert = revert_data.as_deref().map(DecodedRevert::decode).unwrap_or_default();
+    store!(schema, columns, revert_data, revert_data);
+    store!(schema, columns, revert_reason, revert.reason);
+    store!(schema, columns, panic_code, revert.panic_code);
+    store!(schema, columns, panic_reason, revert.panic_reason);
+    store!(schema, columns, error_selector, revert.error_selector);
+    store!(schema, columns, output, output);
     store!(schema, columns, error, trace.error);
     store!(schema, columns, block_number, *block_number);
     store!(schema, columns, transaction_hash, tx.clone());
@@ -25,6 +25,10 @@ pub struct Logs {
 
 #[async_trait::async_trait]
 impl Dataset for Logs {
+    fn primary_key() -> Option<Vec<&'static str>> {
+        Some(vec!["block_number", "log_index"])
+    }
+
     fn aliases() -> Vec<&'static str> {
         vec!["events"]
     }
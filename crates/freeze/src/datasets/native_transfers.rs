@@ -20,6 +20,10 @@ pub struct NativeTransfers {
 
 #[async_trait::async_trait]
 impl Dataset for NativeTransfers {
+    fn primary_key() -> Option<Vec<&'static str>> {
+        Some(vec!["block_number", "transfer_index"])
+    }
+
     fn optional_parameters() -> Vec<Dim> {
         vec![Dim::FromAddress, Dim::ToAddress]
     }
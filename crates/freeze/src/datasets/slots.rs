@@ -20,6 +20,10 @@ impl Dataset for Slots {
         Some(vec!["block_number", "address", "slot"])
     }
 
+    fn primary_key() -> Option<Vec<&'static str>> {
+        Some(vec!["block_number", "address", "slot"])
+    }
+
     fn required_parameters() -> Vec<Dim> {
         vec![Dim::Address, Dim::Slot]
     }
@@ -27,11 +27,42 @@ pub struct Traces {
     block_number: Vec<u32>,
     block_hash: Vec<Vec<u8>>,
     error: Vec<Option<String>>,
+    revert_data: Vec<Option<Vec<u8>>>,
+    revert_reason: Vec<Option<String>>,
+    panic_code: Vec<Option<u64>>,
+    panic_reason: Vec<Option<String>>,
+    error_selector: Vec<Option<Vec<u8>>>,
     chain_id: Vec<u64>,
 }
 
 #[async_trait::async_trait]
 impl Dataset for Traces {
+    fn default_columns() -> Option<Vec<&'static str>> {
+        Some(vec![
+            "action_from",
+            "action_to",
+            "action_value",
+            "action_gas",
+            "action_input",
+            "action_call_type",
+            "action_init",
+            "action_reward_type",
+            "action_type",
+            "result_gas_used",
+            "result_output",
+            "result_code",
+            "result_address",
+            "trace_address",
+            "subtraces",
+            "transaction_index",
+            "transaction_hash",
+            "block_number",
+            "block_hash",
+            "error",
+            "chain_id",
+        ])
+    }
+
     fn optional_parameters() -> Vec<Dim> {
         vec![Dim::FromAddress, Dim::ToAddress]
     }
@@ -130,6 +161,20 @@ pub(crate) fn process_traces(traces: &[Trace], columns: &mut Traces, schemas: &S
         store!(schema, columns, block_number, trace.block_number as u32);
         store!(schema, columns, block_hash, trace.block_hash.as_bytes().to_vec());
         store!(schema, columns, error, trace.error.clone());
+
+        // revert data is the output of failed calls
+        let revert_data = match (&trace.error, &trace.result) {
+            (Some(_), Some(Res::Call(result))) if !result.output.is_empty() => {
+                Some(result.output.to_vec())
+            }
+            _ => None,
+        };
+        let revert = revert_data.as_deref().map(DecodedRevert::decode).unwrap_or_default();
+        store!(schema, columns, revert_data, revert_data);
+        store!(schema, columns, revert_reason, revert.reason);
+        store!(schema, columns, panic_code, revert.panic_code);
+        store!(schema, columns, panic_reason, revert.panic_reason);
+        store!(schema, columns, error_selector, revert.error_selector);
     }
     Ok(())
 }
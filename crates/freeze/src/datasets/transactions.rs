@@ -32,10 +32,19 @@ pub struct Transactions {
     r: Vec<Vec<u8>>,
     s: Vec<Vec<u8>>,
     v: Vec<u64>,
+    revert_data: Vec<Option<Vec<u8>>>,
+    revert_reason: Vec<Option<String>>,
+    panic_code: Vec<Option<u64>>,
+    panic_reason: Vec<Option<String>>,
+    error_selector: Vec<Option<Vec<u8>>>,
 }
 
 #[async_trait::async_trait]
 impl Dataset for Transactions {
+    fn primary_key() -> Option<Vec<&'static str>> {
+        Some(vec!["block_number", "transaction_index"])
+    }
+
     fn aliases() -> Vec<&'static str> {
         vec!["txs"]
     }
@@ -74,7 +83,7 @@ pub type TransactionAndReceipt = (Transaction, Option<TransactionReceipt>);
 
 #[async_trait::async_trait]
 impl CollectByBlock for Transactions {
-    type Response = (Block<Transaction>, Vec<TransactionAndReceipt>, bool);
+    type Response = (Block<Transaction>, Vec<TransactionAndReceipt>, Vec<Option<Vec<u8>>>, bool);
 
     async fn extract(request: Params, source: Arc<Source>, query: Arc<Query>) -> R<Self::Response> {
         let block = source
@@ -104,30 +113,37 @@ impl CollectByBlock for Transactions {
         // 2. collect receipts if necessary
         // if transactions are filtered fetch by set of transaction hashes, else fetch all receipts
         // in block
-        let receipts: Vec<Option<_>> =
-            if schema.has_column("gas_used") | schema.has_column("success") {
-                // receipts required
-                let receipts = if request.from_address.is_some() || request.to_address.is_some() {
-                    source.get_tx_receipts(&transactions).await?
-                } else {
-                    source.get_tx_receipts_in_block(&block).await?
-                };
-                receipts.into_iter().map(Some).collect()
+        let receipts: Vec<Option<_>> = if schema.has_column("gas_used") |
+            schema.has_column("success") |
+            has_revert_columns(schema)
+        {
+            // receipts required
+            let receipts = if request.from_address.is_some() || request.to_address.is_some() {
+                source.get_tx_receipts(&transactions).await?
             } else {
-                vec![None; block.transactions.len()]
+                source.get_tx_receipts_in_block(&block).await?
             };
+            receipts.into_iter().map(Some).collect()
+        } else {
+            vec![None; block.transactions.len()]
+        };
 
-        let transactions_with_receips = transactions.into_iter().zip(receipts).collect();
-        Ok((block, transactions_with_receips, query.exclude_failed))
+        let transactions_with_receips: Vec<_> = transactions.into_iter().zip(receipts).collect();
+
+        // 3. collect revert data of failed transactions if necessary
+        let revert_data = get_revert_data(&transactions_with_receips, &source, schema).await?;
+        Ok((block, transactions_with_receips, revert_data, query.exclude_failed))
     }
 
     fn transform(response: Self::Response, columns: &mut Self, query: &Arc<Query>) -> R<()> {
         let schema = query.schemas.get_schema(&Datatype::Transactions)?;
-        let (block, transactions_with_receipts, exclude_failed) = response;
-        for (tx, receipt) in transactions_with_receipts.into_iter() {
+        let (block, transactions_with_receipts, revert_data, exclude_failed) = response;
+        for ((tx, receipt), revert_data) in transactions_with_receipts.into_iter().zip(revert_data)
+        {
             process_transaction(
                 tx,
                 receipt,
+                revert_data,
                 columns,
                 schema,
                 exclude_failed,
@@ -140,7 +156,7 @@ impl CollectByBlock for Transactions {
 
 #[async_trait::async_trait]
 impl CollectByTransaction for Transactions {
-    type Response = (TransactionAndReceipt, bool, u32);
+    type Response = (TransactionAndReceipt, Option<Vec<u8>>, bool, u32);
 
     async fn extract(request: Params, source: Arc<Source>, query: Arc<Query>) -> R<Self::Response> {
         let tx_hash = request.ethers_transaction_hash()?;
@@ -149,7 +165,7 @@ impl CollectByTransaction for Transactions {
             .get_transaction(tx_hash)
             .await?
             .ok_or(CollectError::CollectError("transaction not found".to_string()))?;
-        let receipt = if schema.has_column("gas_used") {
+        let receipt = if schema.has_column("gas_used") | has_revert_columns(schema) {
             source.get_transaction_receipt(tx_hash).await?
         } else {
             None
@@ -166,20 +182,62 @@ impl CollectByTransaction for Transactions {
 
         let timestamp = block.timestamp.as_u32();
 
-        Ok(((transaction, receipt), query.exclude_failed, timestamp))
+        let transaction_and_receipt = (transaction, receipt);
+        let revert_data =
+            get_revert_data(std::slice::from_ref(&transaction_and_receipt), &source, schema)
+                .await?
+                .pop()
+                .flatten();
+
+        Ok((transaction_and_receipt, revert_data, query.exclude_failed, timestamp))
     }
 
     fn transform(response: Self::Response, columns: &mut Self, query: &Arc<Query>) -> R<()> {
         let schema = query.schemas.get_schema(&Datatype::Transactions)?;
-        let ((transaction, receipt), exclude_failed, timestamp) = response;
-        process_transaction(transaction, receipt, columns, schema, exclude_failed, timestamp)?;
+        let ((transaction, receipt), revert_data, exclude_failed, timestamp) = response;
+        process_transaction(
+            transaction,
+            receipt,
+            revert_data,
+            columns,
+            schema,
+            exclude_failed,
+            timestamp,
+        )?;
         Ok(())
     }
 }
 
+fn has_revert_columns(schema: &Table) -> bool {
+    REVERT_COLUMNS.iter().any(|column| schema.has_column(column))
+}
+
+/// get revert data of failed transactions from the output of their call traces
+///
+/// the traces of all failed transactions are requested concurrently
+async fn get_revert_data(
+    transactions: &[TransactionAndReceipt],
+    source: &Source,
+    schema: &Table,
+) -> R<Vec<Option<Vec<u8>>>> {
+    let include_revert = has_revert_columns(schema);
+    futures::future::try_join_all(transactions.iter().map(|(tx, receipt)| async move {
+        let failed = receipt.as_ref().and_then(|r| r.status).map(|status| status.as_u64() == 0);
+        if !include_revert || failed != Some(true) {
+            return Ok(None)
+        }
+        let (_, _, calls) =
+            source.geth_debug_trace_transaction_calls(tx.hash.as_bytes().to_vec(), false).await?;
+        let output = calls.into_iter().next().and_then(|call| call.output);
+        Ok(output.map(|output| output.to_vec()).filter(|data| !data.is_empty()))
+    }))
+    .await
+}
+
 pub(crate) fn process_transaction(
     tx: Transaction,
     receipt: Option<TransactionReceipt>,
+    revert_data: Option<Vec<u8>>,
     columns: &mut Transactions,
     schema: &Table,
     exclude_failed: bool,
@@ -234,6 +292,13 @@ pub(crate) fn process_transaction(
     store!(schema, columns, r, tx.r.to_vec_u8());
     store!(schema, columns, s, tx.s.to_vec_u8());
 
+    let revert = revert_data.as_deref().map(DecodedRevert::decode).unwrap_or_default();
+    store!(schema, columns, revert_data, revert_data);
+    store!(schema, columns, revert_reason, revert.reason);
+    store!(schema, columns, panic_code, revert.panic_code);
+    store!(schema, columns, panic_reason, revert.panic_reason);
+    store!(schema, columns, error_selector, revert.error_selector);
+
     Ok(())
 }
 
@@ -0,0 +1,256 @@
+use crate::{
+    err, freeze, sources::ProviderWrapper, summaries::summary_println, BlockChunk, ChunkData,
+    CollectError, Dim, ExecutionEnv, FileOutput, FreezeSummary, Partition, Query, Source,
+};
+use ethers::providers::Middleware;
+use futures::StreamExt;
+use std::time::Duration;
+
+/// options for following the chain tip
+#[derive(Clone, Debug)]
+pub struct FollowOptions {
+    /// number of blocks per chunk
+    pub chunk_size: u64,
+    /// how often to poll for new blocks when not subscribed to new heads
+    pub poll_interval: Duration,
+}
+
+/// collect data and output as files, then keep collecting new chunks as the chain advances
+///
+/// new chunks are only emitted once they are complete and older than the query's reorg buffer
+pub async fn freeze_follow(
+    query: &Query,
+    source: &Source,
+    sink: &FileOutput,
+    env: &ExecutionEnv,
+    options: &FollowOptions,
+) -> Result<Option<FreezeSummary>, CollectError> {
+    if query.partitioned_by != vec![Dim::BlockNumber] {
+        return Err(err("follow mode requires partitioning by block"))
+    }
+    if options.chunk_size == 0 {
+        return Err(err("follow mode requires a non-zero chunk size"))
+    }
+
+    // collect the initially requested partitions
+    let summary = freeze(query, source, sink, env).await?;
+    if env.dry {
+        return Ok(summary)
+    }
+
+    let mut follower = Follower::new(query, sink, env, options)?;
+    if env.verbose >= 1 {
+        summary_println!();
+        summary_println!("following chain tip from block {}", follower.cursor.next_block);
+    }
+
+    match &source.provider {
+        ProviderWrapper::WsClient(provider) => {
+            let mut stream =
+                provider.subscribe_blocks().await.map_err(CollectError::ProviderError)?;
+            while let Some(block) = stream.next().await {
+                if let Some(number) = block.number {
+                    follower.advance(source, number.as_u64()).await?;
+                }
+            }
+            Err(err("new heads subscription closed"))
+        }
+        ProviderWrapper::IpcClient(provider) => {
+            let mut stream =
+                provider.subscribe_blocks().await.map_err(CollectError::ProviderError)?;
+            while let Some(block) = stream.next().await {
+                if let Some(number) = block.number {
+                    follower.advance(source, number.as_u64()).await?;
+                }
+            }
+            Err(err("new heads subscription closed"))
+        }
+        _ => loop {
+            tokio::time::sleep(options.poll_interval).await;
+            match source.get_block_number().await {
+                Ok(latest_block) => follower.advance(source, latest_block.as_u64()).await?,
+                Err(e) => eprintln!("could not get latest block number: {}", e),
+            }
+        },
+    }
+}
+
+struct Follower {
+    query: Query,
+    sink: FileOutput,
+    env: ExecutionEnv,
+    template: Partition,
+    cursor: FollowCursor,
+    verbose: u32,
+}
+
+impl Follower {
+    fn new(
+        query: &Query,
+        sink: &FileOutput,
+        env: &ExecutionEnv,
+        options: &FollowOptions,
+    ) -> Result<Follower, CollectError> {
+        let template = query
+            .partitions
+            .first()
+            .cloned()
+            .ok_or_else(|| err("follow mode requires at least one block chunk"))?;
+        Ok(Follower {
+            query: query.clone(),
+            sink: sink.clone(),
+            // subsequent rounds print a single line per round instead of full summaries
+            env: ExecutionEnv { bar: None, verbose: 0, ..env.clone() },
+            template: Partition { label: None, block_numbers: None, ..template },
+            cursor: FollowCursor::new(query, options)?,
+            verbose: env.verbose,
+        })
+    }
+
+    /// collect all complete chunks that are at least reorg_buffer blocks behind the tip
+    async fn advance(&mut self, source: &Source, latest_block: u64) -> Result<(), CollectError> {
+        let chunks = self.cursor.ready_chunks(latest_block);
+        let last_block = match chunks.last() {
+            Some((_, end)) => *end,
+            None => return Ok(()),
+        };
+        let partitions = chunks
+            .into_iter()
+            .map(|(start, end)| Partition {
+                block_numbers: Some(vec![BlockChunk::Range(start, end)]),
+                ..self.template.clone()
+            })
+            .collect();
+
+        let query = Query { partitions, ..self.query.clone() };
+        let env = self.env.clone().set_start_time();
+        if let Some(summary) = freeze(&query, source, &self.sink, &env).await? {
+            if self.verbose >= 1 {
+                summary_println!(
+                    "followed to block {}: {} chunks collected, {} skipped, {} errored",
+                    last_block,
+                    summary.completed.len(),
+                    summary.skipped.len(),
+                    summary.errored.len(),
+                );
+            }
+            for (_partition, error) in summary.errored.iter() {
+                eprintln!("error collecting chunk: {}", error);
+            }
+
+            // errored chunks are retried next round, completed chunks will be skipped
+            if summary.errored.is_empty() {
+                self.cursor.next_block = last_block + 1;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// position of follow mode along the chain
+#[derive(Debug, PartialEq)]
+struct FollowCursor {
+    next_block: u64,
+    chunk_size: u64,
+    reorg_buffer: u64,
+}
+
+impl FollowCursor {
+    fn new(query: &Query, options: &FollowOptions) -> Result<FollowCursor, CollectError> {
+        let last_block = query
+            .partitions
+            .iter()
+            .filter_map(|partition| partition.block_numbers.as_ref())
+            .flatten()
+            .filter_map(|chunk| chunk.max_value())
+            .max()
+            .ok_or_else(|| err("follow mode requires at least one block chunk"))?;
+
+        // aligned chunks start at the boundary of the chunk holding the next block, so a partial
+        // last chunk is collected again as a whole aligned chunk instead of skipping its rest
+        let next_block = if query.labels.align {
+            (last_block + 1) / options.chunk_size * options.chunk_size
+        } else {
+            last_block + 1
+        };
+
+        Ok(FollowCursor {
+            next_block,
+            chunk_size: options.chunk_size,
+            reorg_buffer: query.labels.reorg_buffer,
+        })
+    }
+
+    /// ranges of complete chunks after next_block that are at least reorg_buffer blocks behind
+    /// the tip
+    fn ready_chunks(&self, latest_block: u64) -> Vec<(u64, u64)> {
+        let max_allowed = match latest_block.checked_sub(self.reorg_buffer) {
+            Some(max_allowed) => max_allowed,
+            None => return Vec::new(),
+        };
+        let mut chunks = Vec::new();
+        let mut start = self.next_block;
+        while start + self.chunk_size - 1 <= max_allowed {
+            let end = start + self.chunk_size - 1;
+            chunks.push((start, end));
+            start = end + 1;
+        }
+        chunks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{QueryLabels, TimeDimension};
+    use std::collections::HashMap;
+
+    fn query(chunks: Vec<BlockChunk>, align: bool, reorg_buffer: u64) -> Query {
+        Query {
+            datatypes: Vec::new(),
+            schemas: HashMap::new(),
+            time_dimension: TimeDimension::Blocks,
+            partitions: chunks
+                .into_iter()
+                .map(|chunk| Partition { block_numbers: Some(vec![chunk]), ..Default::default() })
+                .collect(),
+            partitioned_by: vec![Dim::BlockNumber],
+            exclude_failed: false,
+            js_tracer: None,
+            labels: QueryLabels { align, reorg_buffer },
+        }
+    }
+
+    fn options(chunk_size: u64) -> FollowOptions {
+        FollowOptions { chunk_size, poll_interval: Duration::from_secs(1) }
+    }
+
+    #[test]
+    fn test_follow_cursor_start() {
+        let chunks = vec![BlockChunk::Range(1000, 1099), BlockChunk::Range(1100, 1149)];
+        let cursor = FollowCursor::new(&query(chunks.clone(), false, 0), &options(100)).unwrap();
+        assert_eq!(cursor.next_block, 1150);
+
+        // aligned cursors round down to the boundary instead of skipping uncollected blocks
+        let cursor = FollowCursor::new(&query(chunks, true, 0), &options(100)).unwrap();
+        assert_eq!(cursor.next_block, 1100);
+        let chunks = vec![BlockChunk::Range(1000, 1099)];
+        let cursor = FollowCursor::new(&query(chunks, true, 0), &options(100)).unwrap();
+        assert_eq!(cursor.next_block, 1100);
+
+        assert!(FollowCursor::new(&query(Vec::new(), false, 0), &options(100)).is_err());
+    }
+
+    #[test]
+    fn test_follow_cursor_ready_chunks() {
+        let chunks = vec![BlockChunk::Range(0, 99)];
+        let cursor = FollowCursor::new(&query(chunks, false, 10), &options(100)).unwrap();
+
+        // chunks are only ready once complete and reorg_buffer blocks behind the tip
+        assert_eq!(cursor.ready_chunks(5), vec![]);
+        assert_eq!(cursor.ready_chunks(208), vec![]);
+        assert_eq!(cursor.ready_chunks(209), vec![(100, 199)]);
+        assert_eq!(cursor.ready_chunks(315), vec![(100, 199), (200, 299)]);
+    }
+}
@@ -1,12 +1,18 @@
 use crate::{
-    collect_partition, dataframes, err, reports, summaries, CollectError, Datatype, ExecutionEnv,
-    FileOutput, FreezeSummary, MetaDatatype, Partition, Query, Source,
+    collect_partition, dataframes, err, manifests, reorgs, reports, summaries,
+    types::{
+        checkpoints,
+        delta::{self, DeltaFile},
+    },
+    ChunkAttempt, ChunkBlockHashes, CollectError, Database, Datatype, ExecutionEnv, FileOutput,
+    FreezeSummary, LoadedChunks, MetaDatatype, Partition, PostgresSink, Query, Source, Table,
 };
 use chrono::{DateTime, Local};
 use futures::{stream::FuturesUnordered, StreamExt};
+use polars::prelude::DataFrame;
 use std::{
     collections::{HashMap, HashSet},
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::Arc,
 };
 use tokio::sync::Semaphore;
@@ -32,8 +38,42 @@ pub async fn freeze(
     // check validity of query
     query.is_valid()?;
 
+    // keep stdout free for data when streaming rows to stdout
+    summaries::print_to_stderr(sink.stdout);
+
+    // fetch block timestamps needed by date subdirectories
+    let sink = &sink.with_block_timestamps(query, source).await?;
+
+    // check existing chunks for reorgs
+    let reorged = if sink.verify_reorgs && !sink.overwrite {
+        reorgs::find_reorged_chunks(query, source, sink).await?
+    } else {
+        Vec::new()
+    };
+    let reorged_paths: HashSet<PathBuf> = reorged.iter().map(|chunk| chunk.path.clone()).collect();
+
+    // read chunks already loaded into postgres
+    let loaded_chunks = match &sink.postgres {
+        Some(postgres) => Some(postgres.loaded_chunks().await?),
+        None => None,
+    };
+
+    // list files already uploaded to object storage, restoring their manifest if needed
+    let remote_sizes = match &sink.remote {
+        Some(remote) => {
+            manifests::download_manifest(sink, remote).await?;
+            Some(remote.list_sizes().await?)
+        }
+        None => None,
+    };
+
     // get partitions
-    let (payloads, skipping) = get_payloads(query, source, sink, env)?;
+    let existing = ExistingOutputs {
+        reorged_paths: &reorged_paths,
+        loaded_chunks: loaded_chunks.as_ref(),
+        remote_sizes: remote_sizes.as_ref(),
+    };
+    let (payloads, skipping) = get_payloads(query, source, sink, env, &existing)?;
 
     // print summary
     if env.verbose >= 1 {
@@ -47,7 +87,16 @@ pub async fn freeze(
 
     // check if empty
     if payloads.is_empty() {
-        let results = FreezeSummary { skipped: skipping, ..Default::default() };
+        if sink.delta {
+            commit_skipped_to_delta(query, sink, &skipping)?;
+        }
+        let results = FreezeSummary {
+            skipped: skipping,
+            reorged,
+            endpoints: source.endpoint_usage(),
+            request_rate: source.steady_state_rate(),
+            ..Default::default()
+        };
         if env.verbose >= 1 {
             summaries::print_cryo_conclusion(&results, query, env)
         }
@@ -60,7 +109,23 @@ pub async fn freeze(
     };
 
     // perform collection
-    let results = freeze_partitions(env, payloads, skipping).await;
+    let results = freeze_partitions(query, sink, env, payloads, skipping).await;
+    if let Some(remote) = &sink.remote {
+        // chunks upload the manifest concurrently, so the final manifest is uploaded once more
+        manifests::upload_manifest(sink, remote).await?;
+    }
+    if sink.delta {
+        commit_skipped_to_delta(query, sink, &results.skipped)?;
+    }
+    if sink.stdout {
+        dataframes::finish_stdout().map_err(|_| err("error writing to stdout"))?;
+    }
+    let results = FreezeSummary {
+        reorged,
+        endpoints: source.endpoint_usage(),
+        request_rate: source.steady_state_rate(),
+        ..results
+    };
 
     // create summary
     if env.verbose >= 1 {
@@ -76,11 +141,22 @@ pub async fn freeze(
     Ok(Some(results))
 }
 
+/// outputs of previous runs, used to decide which chunks to skip
+struct ExistingOutputs<'a> {
+    /// paths of existing files whose chunks were reorged
+    reorged_paths: &'a HashSet<PathBuf>,
+    /// chunks already loaded into postgres
+    loaded_chunks: Option<&'a LoadedChunks>,
+    /// sizes of files already uploaded to object storage
+    remote_sizes: Option<&'a HashMap<PathBuf, u64>>,
+}
+
 fn get_payloads(
     query: &Query,
     source: &Source,
     sink: &FileOutput,
     env: &ExecutionEnv,
+    existing: &ExistingOutputs<'_>,
 ) -> Result<(Vec<PartitionPayload>, Vec<Partition>), CollectError> {
     let semaphore = source
         .max_concurrent_chunks
@@ -90,10 +166,48 @@ fn get_payloads(
     let mut payloads = Vec::new();
     let mut skipping = Vec::new();
     let mut all_paths = HashSet::new();
+    let manifest = manifests::read_manifest(sink)?;
     for datatype in query.datatypes.clone().into_iter() {
         for partition in query.partitions.clone().into_iter() {
             let paths = sink.get_paths(query, &partition, Some(vec![datatype.clone()]))?;
-            if !sink.overwrite && paths.values().all(|path| path.exists()) {
+            let is_file_complete = |path: &Path, table: Option<&Table>| {
+                match existing.remote_sizes {
+                    Some(sizes) => {
+                        manifests::is_remote_file_complete(sink, &manifest, path, sizes, table)
+                    }
+                    None => manifests::is_file_complete(sink, &manifest, path, table),
+                }
+            };
+            let is_complete = |(datatype, path): (&Datatype, &PathBuf)| {
+                let table = query.schemas.get(datatype);
+                // logs are only complete once the table of every decoded event is written
+                let decoded_complete = match table {
+                    Some(logs_table @ Table { abi_decoder: Some(abi_decoder), .. }) => {
+                        abi_decoder.events.values().all(|event| {
+                            let event_path =
+                                sink.get_decoded_path(path, *datatype, &event.table_name);
+                            let event_table = abi_decoder.event_table(event, logs_table);
+                            is_file_complete(&event_path, Some(&event_table))
+                        })
+                    }
+                    _ => true,
+                };
+                is_file_complete(path, table) &&
+                    decoded_complete &&
+                    !existing.reorged_paths.contains(path)
+            };
+            let complete = if sink.database.is_some() || sink.postgres.is_some() {
+                is_database_chunk_complete(
+                    query,
+                    sink,
+                    &partition,
+                    &datatype,
+                    existing.loaded_chunks,
+                )?
+            } else {
+                sink.writes_files() && paths.iter().all(is_complete)
+            };
+            if !sink.overwrite && complete {
                 skipping.push(partition);
                 continue
             }
@@ -124,48 +238,167 @@ fn get_payloads(
     Ok((payloads, skipping))
 }
 
+/// whether every datatype of chunk is already recorded in the database sink
+fn is_database_chunk_complete(
+    query: &Query,
+    sink: &FileOutput,
+    partition: &Partition,
+    meta_datatype: &MetaDatatype,
+    loaded_chunks: Option<&LoadedChunks>,
+) -> Result<bool, CollectError> {
+    let chunk = database_chunk(sink, query, partition)?;
+    for datatype in meta_datatype.datatypes().into_iter() {
+        let table_name = Database::table_name(datatype, &sink.suffix);
+        let table = query.schemas.get(&datatype);
+        let complete = match (&sink.database, loaded_chunks) {
+            (Some(database), _) => {
+                database.is_chunk_complete(&table_name, &chunk, table, sink.recollect_stale)?
+            }
+            (None, Some(loaded)) => PostgresSink::is_chunk_complete(
+                loaded,
+                &table_name,
+                &chunk,
+                table,
+                sink.recollect_stale,
+            ),
+            (None, None) => false,
+        };
+        if !complete {
+            return Ok(false)
+        }
+    }
+    Ok(true)
+}
+
+/// label of chunk rows in database sink
+fn database_chunk(
+    sink: &FileOutput,
+    query: &Query,
+    partition: &Partition,
+) -> Result<String, CollectError> {
+    Ok(format!("{}__{}", sink.prefix, partition.label(&query.partitioned_by)?))
+}
+
+/// record existing files of skipped partitions that delta tables do not contain yet
+///
+/// this registers files written by earlier runs that were interrupted before committing
+fn commit_skipped_to_delta(
+    query: &Query,
+    sink: &FileOutput,
+    skipped: &[Partition],
+) -> Result<(), CollectError> {
+    let mut delta_files = Vec::new();
+    for partition in skipped.iter() {
+        for (datatype, path) in sink.get_paths(query, partition, None)? {
+            if path.exists() {
+                let partition = partition.clone();
+                delta_files.push(DeltaFile { datatype, partition, path, rewritten: false });
+            }
+        }
+    }
+    delta::commit_files(sink, query, &delta_files)
+}
+
+/// number of completed files recorded in each delta table commit
+const DELTA_BATCH_SIZE: usize = 100;
+
 async fn freeze_partitions(
+    query: &Query,
+    sink: &FileOutput,
     env: &ExecutionEnv,
     payloads: Vec<PartitionPayload>,
     skipped: Vec<Partition>,
 ) -> FreezeSummary {
+    let mut rate_ticker = None;
     if let Some(bar) = &env.bar {
         bar.set_length(payloads.len() as u64);
         if let Some(payload) = &payloads.first() {
-            let (_, _, _, _, _, _, env, _) = payload;
+            let (_, _, _, _, source, _, env, _) = payload;
             let dt_start: DateTime<Local> = env.t_start.into();
-            bar.set_message(format!("started at {}", dt_start.format("%Y-%m-%d %H:%M:%S%.3f")));
+            let message = format!("started at {}", dt_start.format("%Y-%m-%d %H:%M:%S%.3f"));
+            bar.set_message(message.clone());
+
+            // show current rate of adaptive rate limiting
+            if let Some(limiter) = source.adaptive_rate_limiter.clone() {
+                let bar = bar.clone();
+                rate_ticker = Some(tokio::spawn(async move {
+                    loop {
+                        bar.set_message(format!("{}, {:.1} requests/s", message, limiter.rate()));
+                        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                    }
+                }));
+            }
         }
     }
 
     // spawn task for each partition
     let mut futures = FuturesUnordered::new();
     for payload in payloads.into_iter() {
-        futures.push(tokio::spawn(
-            async move { (payload.0.clone(), freeze_partition(payload).await) },
-        ));
+        futures.push(tokio::spawn(async move {
+            let (partition, paths) = (payload.0.clone(), payload.2.clone());
+            (partition, paths, freeze_partition_with_retries(payload).await)
+        }));
     }
 
     // aggregate results
     let mut completed = Vec::new();
     let mut errored = Vec::new();
+    let mut attempts = Vec::new();
     let mut n_rows = 0;
+    let mut delta_files = Vec::new();
     while let Some(result) = futures.next().await {
         match result {
-            Ok((partition, Ok(chunk_n_rows))) => {
+            Ok((partition, paths, (Ok(chunk_n_rows), chunk_attempts))) => {
                 n_rows += chunk_n_rows;
-                completed.push(partition)
+                if sink.delta {
+                    delta_files.extend(paths.into_iter().map(|(datatype, path)| DeltaFile {
+                        datatype,
+                        partition: partition.clone(),
+                        path,
+                        rewritten: true,
+                    }));
+                }
+                completed.push(partition);
+                attempts.extend(chunk_attempts)
+            }
+            Ok((partition, _, (Err(e), chunk_attempts))) => {
+                errored.push((Some(partition), e));
+                attempts.extend(chunk_attempts)
             }
-            Ok((partition, Err(e))) => errored.push((Some(partition), e)),
             Err(e) => errored.push((None, err(format!("error joining chunks: {:?}", e).as_str()))),
         }
+
+        // record completed files in delta tables in batches
+        if delta_files.len() >= DELTA_BATCH_SIZE || (futures.is_empty() && !delta_files.is_empty())
+        {
+            if let Err(e) = delta::commit_files(sink, query, &delta_files) {
+                errored.push((None, e))
+            }
+            delta_files.clear();
+        }
     }
 
+    if let Some(rate_ticker) = rate_ticker {
+        rate_ticker.abort();
+    }
     if let Some(bar) = &env.bar {
         bar.finish_and_clear();
     }
 
-    FreezeSummary { completed, errored, skipped, n_rows }
+    FreezeSummary { completed, errored, skipped, n_rows, attempts, ..Default::default() }
+}
+
+/// freeze partition, retrying failed attempts according to the retry policy
+///
+/// attempts are only returned for partitions that failed at least once
+async fn freeze_partition_with_retries(
+    payload: PartitionPayload,
+) -> (Result<u64, CollectError>, Vec<ChunkAttempt>) {
+    let (partition, datatype, _, query, _, _, env, _) = &payload;
+    let datatype_name =
+        datatype.datatypes().iter().map(|dt| dt.name()).collect::<Vec<_>>().join(",");
+    let chunk = partition.label(&query.partitioned_by).unwrap_or_default();
+    env.retry_policy.run(&datatype_name, &chunk, || freeze_partition(payload.clone())).await
 }
 
 async fn freeze_partition(payload: PartitionPayload) -> Result<u64, CollectError> {
@@ -177,18 +410,101 @@ async fn freeze_partition(payload: PartitionPayload) -> Result<u64, CollectError
         None => None,
     };
 
+    // record boundary block hashes before collecting, so later reorgs can be detected
+    let block_hashes = match sink.verify_reorgs && sink.writes_files() {
+        false => None,
+        true => reorgs::get_chunk_block_hashes(&partition, &source).await?,
+    };
+
     // collect data
-    let dfs = collect_partition(datatype, partition, query, source).await?;
+    let partition_label = partition.label(&query.partitioned_by)?;
+    let dfs = match sink.checkpoint_size {
+        Some(checkpoint_size) => {
+            checkpoints::collect_partition_with_checkpoints(
+                datatype,
+                partition.clone(),
+                query.clone(),
+                source,
+                &sink,
+                &paths,
+                checkpoint_size,
+            )
+            .await?
+        }
+        None => collect_partition(datatype, partition.clone(), query.clone(), source).await?,
+    };
 
     // write dataframes to disk
     let mut n_rows = 0;
     for (datatype, mut df) in dfs {
         n_rows += df.height() as u64;
+        if sink.stdout {
+            dataframes::df_to_stdout(&mut df, &sink)
+                .map_err(|_| CollectError::CollectError("error writing to stdout".to_string()))?;
+            continue
+        }
+        if sink.database.is_some() || sink.postgres.is_some() {
+            let table = query
+                .schemas
+                .get(&datatype)
+                .ok_or_else(|| err(&format!("schema not provided for {}", datatype.name())))?;
+            let table_name = Database::table_name(datatype, &sink.suffix);
+            let chunk = database_chunk(&sink, &query, &partition)?;
+            if let Some(database) = &sink.database {
+                // database writes block on file io and locks, so they run off the async workers
+                let (database, table_name, chunk, table, df) =
+                    (database.clone(), table_name.clone(), chunk.clone(), table.clone(), df.clone());
+                tokio::task::spawn_blocking(move || {
+                    database.write_chunk(&table_name, &chunk, datatype, &table, &df)
+                })
+                .await
+                .map_err(CollectError::TaskFailed)??;
+            }
+            if let Some(postgres) = &sink.postgres {
+                postgres.write_chunk(&table_name, &chunk, table, &df).await?;
+            }
+            continue
+        }
         let path = paths.get(&datatype).ok_or_else(|| {
             CollectError::CollectError("could not get path for datatype".to_string())
         })?;
-        let result = dataframes::df_to_file(&mut df, path, &sink);
-        result.map_err(|_| CollectError::CollectError("error writing file".to_string()))?
+        let table = query.schemas.get(&datatype);
+
+        // decoded event tables are written before the logs file, which marks the chunk complete
+        if let Some(logs_table @ Table { abi_decoder: Some(abi_decoder), .. }) = table {
+            for (event, mut event_df) in abi_decoder.decode_logs(&df, logs_table)? {
+                let event_path = sink.get_decoded_path(path, datatype, &event.table_name);
+                let event_table = abi_decoder.event_table(event, logs_table);
+                write_file(
+                    &mut event_df,
+                    &event_path,
+                    event.table_name.clone(),
+                    Some(&event_table),
+                    &partition_label,
+                    &partition,
+                    &sink,
+                    &block_hashes,
+                )
+                .await?;
+            }
+        }
+
+        write_file(
+            &mut df,
+            path,
+            datatype.name(),
+            table,
+            &partition_label,
+            &partition,
+            &sink,
+            &block_hashes,
+        )
+        .await?;
+    }
+
+    // checkpoints are no longer needed once all files are written
+    if sink.checkpoint_size.is_some() {
+        checkpoints::remove_checkpoints(&sink, &paths);
     }
 
     // update progress bar
@@ -198,3 +514,48 @@ async fn freeze_partition(payload: PartitionPayload) -> Result<u64, CollectError
 
     Ok(n_rows)
 }
+
+/// write output file of table, then record it in the manifest and upload it
+#[allow(clippy::too_many_arguments)]
+async fn write_file(
+    df: &mut DataFrame,
+    path: &Path,
+    table_name: String,
+    table: Option<&Table>,
+    partition_label: &str,
+    partition: &Partition,
+    sink: &FileOutput,
+    block_hashes: &Option<ChunkBlockHashes>,
+) -> Result<(), CollectError> {
+    if sink.delta {
+        delta::cast_unsigned(df)?;
+    }
+    let result = dataframes::df_to_file(df, path, sink);
+    result.map_err(|_| CollectError::CollectError("error writing file".to_string()))?;
+    if let Some(block_hashes) = block_hashes {
+        reorgs::write_chunk_block_hashes(sink, path, block_hashes)?;
+    }
+    if let Some(table) = table {
+        let entry = manifests::create_manifest_entry(
+            sink,
+            path,
+            table_name,
+            partition_label.to_string(),
+            partition,
+            table,
+            df.height() as u64,
+        )?;
+        manifests::append_manifest_entry(sink, &entry)?;
+    }
+
+    // uploaded files are removed from the staging directory, the manifest is uploaded after the
+    // file so that it never lists a file that was not uploaded
+    if let Some(remote) = &sink.remote {
+        remote.upload(path, &sink.relative_path(path)).await?;
+        std::fs::remove_file(path).map_err(|_| err("could not remove staged file"))?;
+        if table.is_some() {
+            manifests::upload_manifest(sink, remote).await?;
+        }
+    }
+    Ok(())
+}
@@ -8,13 +8,22 @@
 ))]
 
 mod collect;
+mod compact;
 mod datasets;
+mod follow;
 mod freeze;
 mod multi_datasets;
 mod types;
+mod validate;
 
 pub use collect::collect;
+pub use compact::{compact, CompactTarget, CompactedFile, CompactionReport};
 pub use datasets::*;
+pub use follow::{freeze_follow, FollowOptions};
 pub use freeze::freeze;
 pub use multi_datasets::*;
 pub use types::*;
+pub use validate::{
+    validate, write_validation_report, DatasetValidation, ValidationIssue, ValidationIssueKind,
+    ValidationReport,
+};
@@ -1,7 +1,7 @@
 use crate::*;
 use ethers::prelude::*;
 use polars::prelude::*;
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
 /// state diffs from geth debug traces
 pub struct GethStateDiffs(
@@ -118,7 +118,7 @@ pub(crate) fn process_geth_diffs(
             .pre
             .keys()
             .chain(trace.post.keys())
-            .collect::<std::collections::HashSet<_>>()
+            .collect::<BTreeSet<_>>()
             .into_iter()
             .collect();
         for address in addresses.into_iter() {
@@ -241,7 +241,7 @@ fn add_storages(
         .clone()
         .into_keys()
         .chain(post.clone().into_keys())
-        .collect::<HashSet<_>>()
+        .collect::<BTreeSet<_>>()
         .into_iter()
         .collect();
     let blank = H256::zero();
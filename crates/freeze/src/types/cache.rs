@@ -0,0 +1,184 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::SystemTime,
+};
+
+/// on-disk cache of rpc responses, keyed by method, params, and chain_id
+///
+/// entries are only written for blocks at least `reorg_buffer` blocks behind the chain tip, so
+/// cached responses are treated as final and never expire, they are only removed by eviction
+#[derive(Debug)]
+pub struct RpcCache {
+    /// directory of cache entries
+    pub dir: PathBuf,
+    /// maximum total size of cache entries in bytes
+    pub max_bytes: Option<u64>,
+    /// number of blocks behind the chain tip before responses are cached
+    pub reorg_buffer: u64,
+    latest_block: AtomicU64,
+    n_bytes: AtomicU64,
+    evicting: AtomicBool,
+}
+
+impl RpcCache {
+    /// open cache in directory, entries are created on first insert
+    pub fn new(dir: PathBuf, max_bytes: Option<u64>, reorg_buffer: u64) -> RpcCache {
+        let n_bytes = list_entries(&dir).iter().map(|(_, _, n_bytes)| n_bytes).sum();
+        RpcCache {
+            dir,
+            max_bytes,
+            reorg_buffer,
+            latest_block: AtomicU64::new(0),
+            n_bytes: AtomicU64::new(n_bytes),
+            evicting: AtomicBool::new(false),
+        }
+    }
+
+    /// compute key of a request
+    pub fn key<P: Serialize>(method: &str, params: &P, chain_id: u64) -> Option<String> {
+        let params = serde_json::to_string(params).ok()?;
+        let description = format!("{}:{}:{}", chain_id, method, params);
+        Some(prefix_hex::encode(ethers::utils::keccak256(description.as_bytes()).to_vec()))
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        let key = key.trim_start_matches("0x");
+        self.dir.join(&key[..2]).join(format!("{}.json", key))
+    }
+
+    /// load cached response
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let path = self.entry_path(key);
+        let contents = std::fs::read(&path).ok()?;
+        let value = serde_json::from_slice(&contents).ok()?;
+
+        // bump modification time so that eviction removes least recently used entries first
+        if let Ok(file) = std::fs::File::options().write(true).open(&path) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+        Some(value)
+    }
+
+    /// store response in cache, null responses are not stored
+    pub fn insert<T: Serialize>(&self, key: &str, value: &T) {
+        let contents = match serde_json::to_vec(value) {
+            Ok(contents) if contents != b"null" => contents,
+            _ => return,
+        };
+        let path = self.entry_path(key);
+        if let Some(parent) = path.parent() {
+            if std::fs::create_dir_all(parent).is_err() {
+                return
+            }
+        }
+
+        // entries are written to a unique temporary file and then renamed into place, so that
+        // concurrent runs sharing the cache never observe partially written entries
+        let tmp_path =
+            path.with_extension(format!("{}.{}.tmp", std::process::id(), rand::random::<u64>()));
+        if std::fs::write(&tmp_path, &contents).is_err() {
+            let _ = std::fs::remove_file(&tmp_path);
+            return
+        }
+        if std::fs::rename(&tmp_path, &path).is_err() {
+            let _ = std::fs::remove_file(&tmp_path);
+            return
+        }
+
+        let n_bytes =
+            self.n_bytes.fetch_add(contents.len() as u64, Ordering::SeqCst) + contents.len() as u64;
+        if let Some(max_bytes) = self.max_bytes {
+            if n_bytes > max_bytes {
+                self.evict(max_bytes)
+            }
+        }
+    }
+
+    /// whether block is far enough behind the known chain tip to be cached
+    pub fn is_finalized(&self, block_number: u64) -> bool {
+        let latest_block = self.latest_block.load(Ordering::SeqCst);
+        latest_block > 0 && block_number.saturating_add(self.reorg_buffer) <= latest_block
+    }
+
+    /// record latest known block of chain
+    pub fn set_latest_block(&self, latest_block: u64) {
+        self.latest_block.fetch_max(latest_block, Ordering::SeqCst);
+    }
+
+    /// remove least recently used entries until cache is below 90% of max size
+    fn evict(&self, max_bytes: u64) {
+        if self.evicting.swap(true, Ordering::SeqCst) {
+            return
+        }
+        let mut entries = list_entries(&self.dir);
+        entries.sort_by_key(|(_, modified, _)| *modified);
+        let target = max_bytes / 10 * 9;
+        let mut n_bytes: u64 = entries.iter().map(|(_, _, n_bytes)| n_bytes).sum();
+        for (path, _, entry_bytes) in entries.into_iter() {
+            if n_bytes <= target {
+                break
+            }
+            // entries may already have been removed by a concurrent run
+            let _ = std::fs::remove_file(path);
+            n_bytes = n_bytes.saturating_sub(entry_bytes);
+        }
+        self.n_bytes.store(n_bytes, Ordering::SeqCst);
+        self.evicting.store(false, Ordering::SeqCst);
+    }
+}
+
+fn list_entries(dir: &Path) -> Vec<(PathBuf, SystemTime, u64)> {
+    let mut entries = Vec::new();
+    let subdirs = match std::fs::read_dir(dir) {
+        Ok(subdirs) => subdirs,
+        Err(_) => return entries,
+    };
+    for subdir in subdirs.flatten() {
+        let files = match std::fs::read_dir(subdir.path()) {
+            Ok(files) => files,
+            Err(_) => continue,
+        };
+        for file in files.flatten() {
+            let path = file.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue
+            }
+            if let Ok(metadata) = file.metadata() {
+                let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                entries.push((path, modified, metadata.len()))
+            }
+        }
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_round_trip_and_eviction() {
+        let dir = std::env::temp_dir().join(format!("cryo_cache_test_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let cache = RpcCache::new(dir.clone(), Some(100), 0);
+
+        let key = RpcCache::key("eth_getBlockByNumber", &(1u64, false), 1).unwrap();
+        assert_ne!(key, RpcCache::key("eth_getBlockByNumber", &(1u64, false), 10).unwrap());
+        cache.insert(&key, &vec![1u64, 2, 3]);
+        assert_eq!(cache.get::<Vec<u64>>(&key), Some(vec![1, 2, 3]));
+
+        cache.insert(&key, &Option::<u64>::None);
+        assert_eq!(cache.get::<Vec<u64>>(&key), Some(vec![1, 2, 3]));
+
+        for i in 0..20u64 {
+            let key = RpcCache::key("eth_getBlockByNumber", &(i, true), 1).unwrap();
+            cache.insert(&key, &"x".repeat(10));
+        }
+        let n_bytes: u64 = list_entries(&dir).iter().map(|(_, _, n_bytes)| n_bytes).sum();
+        assert!(n_bytes <= 100);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
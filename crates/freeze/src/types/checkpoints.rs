@@ -0,0 +1,140 @@
+use crate::{
+    collect_partition, dataframes, dataframes::SortableDataFrame, err, manifests, BlockChunk,
+    ChunkData, CollectError, Datatype, FileOutput, MetaDatatype, Partition, Query, Source,
+    Subchunk,
+};
+use polars::prelude::*;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+/// directory where checkpointed parts of an output file are stored
+pub(crate) fn get_checkpoint_dir(sink: &FileOutput, path: &Path) -> PathBuf {
+    let relative = sink.relative_path(path);
+    sink.output_dir.join(".cryo/checkpoints").join(relative.with_extension(""))
+}
+
+/// collect partition as a series of checkpointed sub-chunks
+///
+/// each completed sub-chunk is saved as a parquet part, so an interrupted partition only
+/// re-collects the sub-chunks that were not yet saved. parts are stored under the schema
+/// fingerprint of their table, so parts saved with a different schema are not reused
+pub(crate) async fn collect_partition_with_checkpoints(
+    datatype: MetaDatatype,
+    partition: Partition,
+    query: Arc<Query>,
+    source: Arc<Source>,
+    sink: &FileOutput,
+    paths: &HashMap<Datatype, PathBuf>,
+    checkpoint_size: u64,
+) -> Result<HashMap<Datatype, DataFrame>, CollectError> {
+    let block_numbers = match &partition.block_numbers {
+        Some(block_numbers) => block_numbers.clone(),
+        None => return collect_partition(datatype, partition, query, source).await,
+    };
+
+    let mut parts: HashMap<Datatype, Vec<DataFrame>> = HashMap::new();
+    for sub_chunk in block_numbers.subchunk_by_size(&checkpoint_size) {
+        let part_paths = get_part_paths(sink, paths, &query, &sub_chunk)?;
+        let dfs = if part_paths.values().all(|path| path.exists()) {
+            read_parts(&part_paths)?
+        } else {
+            let sub_partition =
+                Partition { block_numbers: Some(vec![sub_chunk]), ..partition.clone() };
+            let dfs =
+                collect_partition(datatype.clone(), sub_partition, query.clone(), source.clone())
+                    .await?;
+            write_parts(dfs, &part_paths, sink)?
+        };
+        for (datatype, df) in dfs.into_iter() {
+            parts.entry(datatype).or_default().push(df);
+        }
+    }
+
+    let mut dfs = HashMap::new();
+    for (datatype, part_dfs) in parts.into_iter() {
+        let table = query
+            .schemas
+            .get(&datatype)
+            .ok_or_else(|| err(format!("schema not provided for {}", datatype.name()).as_str()))?;
+        let df = concat_parts(part_dfs).sort_by_schema(table)?;
+        dfs.insert(datatype, df);
+    }
+    Ok(dfs)
+}
+
+/// remove checkpointed parts of output files once they have been written
+pub(crate) fn remove_checkpoints(sink: &FileOutput, paths: &HashMap<Datatype, PathBuf>) {
+    for path in paths.values() {
+        let _ = std::fs::remove_dir_all(get_checkpoint_dir(sink, path));
+    }
+}
+
+fn get_part_paths(
+    sink: &FileOutput,
+    paths: &HashMap<Datatype, PathBuf>,
+    query: &Query,
+    sub_chunk: &BlockChunk,
+) -> Result<HashMap<Datatype, PathBuf>, CollectError> {
+    let stub = sub_chunk.stub().map_err(|_| err("could not create checkpoint label"))?;
+    let mut part_paths = HashMap::new();
+    for (datatype, path) in paths.iter() {
+        let table = query
+            .schemas
+            .get(datatype)
+            .ok_or_else(|| err(format!("schema not provided for {}", datatype.name()).as_str()))?;
+        let fingerprint = manifests::schema_fingerprint(table);
+        let part_path = get_checkpoint_dir(sink, path)
+            .join(fingerprint.trim_start_matches("0x"))
+            .join(format!("{}.parquet", stub));
+        part_paths.insert(*datatype, part_path);
+    }
+    Ok(part_paths)
+}
+
+fn read_parts(
+    part_paths: &HashMap<Datatype, PathBuf>,
+) -> Result<HashMap<Datatype, DataFrame>, CollectError> {
+    let mut dfs = HashMap::new();
+    for (datatype, path) in part_paths.iter() {
+        let file = std::fs::File::open(path).map_err(|_| err("could not open checkpoint"))?;
+        let df = dataframes::read_parquet(file).map_err(CollectError::PolarsError)?;
+        dfs.insert(*datatype, df);
+    }
+    Ok(dfs)
+}
+
+fn write_parts(
+    dfs: HashMap<Datatype, DataFrame>,
+    part_paths: &HashMap<Datatype, PathBuf>,
+    sink: &FileOutput,
+) -> Result<HashMap<Datatype, DataFrame>, CollectError> {
+    let mut written = HashMap::new();
+    for (datatype, mut df) in dfs.into_iter() {
+        let path =
+            part_paths.get(&datatype).ok_or_else(|| err("could not get path for checkpoint"))?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|_| err("could not create checkpoint directory"))?;
+        }
+        dataframes::df_to_file(&mut df, path, sink).map_err(|_| err("error writing checkpoint"))?;
+        written.insert(datatype, df);
+    }
+    Ok(written)
+}
+
+fn concat_parts(part_dfs: Vec<DataFrame>) -> Result<DataFrame, CollectError> {
+    // empty parts are skipped because their column dtypes may not be fully resolved
+    let (mut non_empty, empty): (Vec<_>, Vec<_>) =
+        part_dfs.into_iter().partition(|df| df.height() > 0);
+    if non_empty.is_empty() {
+        return empty.into_iter().next().ok_or_else(|| err("no checkpoint parts collected"))
+    }
+    let mut df = non_empty.remove(0);
+    for other in non_empty.iter() {
+        df.vstack_mut(other).map_err(CollectError::PolarsError)?;
+    }
+    df.align_chunks();
+    Ok(df)
+}
@@ -81,6 +81,11 @@ pub trait Dataset: Sync + Send {
         None
     }
 
+    /// columns that uniquely identify each row of dataset
+    fn primary_key() -> Option<Vec<&'static str>> {
+        None
+    }
+
     /// default columns extracted for Dataset
     fn default_columns() -> Option<Vec<&'static str>> {
         None
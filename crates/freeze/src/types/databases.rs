@@ -0,0 +1,527 @@
+use crate::{
+    err, manifests::schema_fingerprint, CollectError, ColumnType, Datatype, ParseError, Table,
+    CRYO_VERSION, DECIMAL128_PRECISION,
+};
+use polars::prelude::*;
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// kind of embedded database
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DatabaseKind {
+    /// SQLite database
+    Sqlite,
+    /// DuckDB database
+    DuckDb,
+}
+
+/// embedded database file that datatypes are written into, one table per datatype
+///
+/// each chunk is recorded in a `_cryo_chunks` table, and rows carry the label of their chunk in a
+/// `_cryo_chunk` column, so that re-collected chunks replace their previous rows
+#[derive(Clone)]
+pub struct Database {
+    /// kind of database
+    pub kind: DatabaseKind,
+    /// path of database file
+    pub path: PathBuf,
+    connection: Arc<Mutex<Option<Connection>>>,
+}
+
+impl std::fmt::Debug for Database {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Database").field("kind", &self.kind).field("path", &self.path).finish()
+    }
+}
+
+enum Connection {
+    #[cfg(feature = "sqlite")]
+    Sqlite(rusqlite::Connection),
+    #[cfg(feature = "duckdb")]
+    DuckDb(duckdb::Connection),
+}
+
+/// value of a database cell
+#[cfg_attr(not(any(feature = "sqlite", feature = "duckdb")), allow(dead_code))]
+enum SqlValue {
+    Null,
+    Integer(i64),
+    UInteger(u64),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+pub(crate) const CHUNKS_TABLE: &str = "_cryo_chunks";
+pub(crate) const CHUNK_COLUMN: &str = "_cryo_chunk";
+
+impl Database {
+    /// create database sink, with kind of database given by file extension
+    pub fn new(path: PathBuf) -> Result<Database, ParseError> {
+        let kind = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("sqlite") | Some("sqlite3") | Some("db") => DatabaseKind::Sqlite,
+            Some("duckdb") | Some("ddb") => DatabaseKind::DuckDb,
+            _ => {
+                return Err(ParseError::ParseError(
+                    "database path must end in .sqlite, .sqlite3, .db, .duckdb, or .ddb"
+                        .to_string(),
+                ))
+            }
+        };
+        Ok(Database { kind, path, connection: Arc::new(Mutex::new(None)) })
+    }
+
+    /// name of table of datatype
+    pub fn table_name(datatype: Datatype, suffix: &Option<String>) -> String {
+        match suffix {
+            Some(suffix) => format!("{}__{}", datatype.name(), suffix),
+            None => datatype.name(),
+        }
+    }
+
+    /// whether chunk was already written to table
+    ///
+    /// chunks written with a different schema or cryo version are only complete when stale
+    /// chunks are not being re-collected
+    pub(crate) fn is_chunk_complete(
+        &self,
+        table_name: &str,
+        chunk: &str,
+        table: Option<&Table>,
+        recollect_stale: bool,
+    ) -> Result<bool, CollectError> {
+        let sql = format!(
+            "SELECT schema_fingerprint, cryo_version FROM {} WHERE table_name = ? AND chunk = ?",
+            CHUNKS_TABLE
+        );
+        let params =
+            vec![SqlValue::Text(table_name.to_string()), SqlValue::Text(chunk.to_string())];
+        let rows = self.with_connection(|connection| {
+            connection.execute_batch(&chunks_table_sql())?;
+            connection.query_strings(&sql, params)
+        })?;
+        let (fingerprint, version) = match rows.first() {
+            Some(row) => (row[0].clone(), row[1].clone()),
+            None => return Ok(false),
+        };
+        match table {
+            Some(table) if recollect_stale => {
+                Ok(fingerprint == schema_fingerprint(table) && version == CRYO_VERSION)
+            }
+            _ => Ok(true),
+        }
+    }
+
+    /// write rows of chunk to table of datatype, replacing previous rows of chunk
+    pub(crate) fn write_chunk(
+        &self,
+        table_name: &str,
+        chunk: &str,
+        datatype: Datatype,
+        table: &Table,
+        df: &DataFrame,
+    ) -> Result<(), CollectError> {
+        let columns: Vec<String> =
+            df.get_column_names().into_iter().map(|name| name.to_string()).collect();
+        let create_sql = create_table_sql(self.kind, table_name, datatype, table, &columns);
+        let insert_sql = insert_sql(table_name, &columns, datatype);
+        let rows = dataframe_rows(df, chunk)?;
+        self.with_connection(|connection| {
+            connection.execute_batch(&chunks_table_sql())?;
+            connection.execute_batch(&create_sql)?;
+
+            // previous rows are removed before inserting, so that a crash in between leaves the
+            // chunk unrecorded and it is collected again by the next run
+            let chunk_params =
+                || vec![SqlValue::Text(table_name.to_string()), SqlValue::Text(chunk.to_string())];
+            connection.transaction(vec![
+                (
+                    format!("DELETE FROM {} WHERE table_name = ? AND chunk = ?", CHUNKS_TABLE),
+                    vec![chunk_params()],
+                ),
+                (
+                    format!("DELETE FROM {} WHERE {} = ?", quote(table_name), CHUNK_COLUMN),
+                    vec![vec![SqlValue::Text(chunk.to_string())]],
+                ),
+            ])?;
+
+            let mut chunk_record = chunk_params();
+            chunk_record.push(SqlValue::Integer(df.height() as i64));
+            chunk_record.push(SqlValue::Text(schema_fingerprint(table)));
+            chunk_record.push(SqlValue::Text(CRYO_VERSION.to_string()));
+            connection.transaction(vec![
+                (insert_sql.clone(), rows),
+                (
+                    format!("INSERT INTO {} VALUES (?, ?, ?, ?, ?)", CHUNKS_TABLE),
+                    vec![chunk_record],
+                ),
+            ])
+        })
+    }
+
+    fn with_connection<T>(
+        &self,
+        f: impl FnOnce(&mut Connection) -> Result<T, CollectError>,
+    ) -> Result<T, CollectError> {
+        let mut guard = self.connection.lock().map_err(|_| err("database connection poisoned"))?;
+        if guard.is_none() {
+            *guard = Some(Connection::open(self.kind, &self.path)?);
+        }
+        match guard.as_mut() {
+            Some(connection) => f(connection),
+            None => Err(err("could not open database")),
+        }
+    }
+}
+
+#[cfg(any(feature = "sqlite", feature = "duckdb"))]
+fn database_err<E: std::fmt::Display>(e: E) -> CollectError {
+    err(&format!("database error: {}", e))
+}
+
+impl Connection {
+    #[allow(unused_variables)]
+    fn open(kind: DatabaseKind, path: &Path) -> Result<Connection, CollectError> {
+        match kind {
+            #[cfg(feature = "sqlite")]
+            DatabaseKind::Sqlite => {
+                let connection = rusqlite::Connection::open(path).map_err(database_err)?;
+                // chunks of concurrent runs wait for each other instead of failing
+                connection
+                    .busy_timeout(std::time::Duration::from_secs(60))
+                    .map_err(database_err)?;
+                Ok(Connection::Sqlite(connection))
+            }
+            #[cfg(feature = "duckdb")]
+            DatabaseKind::DuckDb => {
+                Ok(Connection::DuckDb(duckdb::Connection::open(path).map_err(database_err)?))
+            }
+            #[allow(unreachable_patterns)]
+            kind => Err(err(&format!(
+                "cryo was built without {} support, rebuild with `--features {}`",
+                kind.feature(),
+                kind.feature()
+            ))),
+        }
+    }
+
+    fn execute_batch(&mut self, sql: &str) -> Result<(), CollectError> {
+        match self {
+            #[cfg(feature = "sqlite")]
+            Connection::Sqlite(connection) => connection.execute_batch(sql).map_err(database_err),
+            #[cfg(feature = "duckdb")]
+            Connection::DuckDb(connection) => connection.execute_batch(sql).map_err(database_err),
+            #[allow(unreachable_patterns)]
+            _ => Err(err(&format!("could not execute {}", sql))),
+        }
+    }
+
+    /// query rows whose columns are all strings
+    fn query_strings(
+        &mut self,
+        sql: &str,
+        params: Vec<SqlValue>,
+    ) -> Result<Vec<Vec<String>>, CollectError> {
+        #[cfg(any(feature = "sqlite", feature = "duckdb"))]
+        macro_rules! query_strings {
+            ($connection:expr, $module:ident, $to_value:ident) => {{
+                let mut statement = $connection.prepare(sql).map_err(database_err)?;
+                let n_columns = statement.column_count();
+                let params = params.into_iter().map($to_value);
+                let mut rows =
+                    statement.query($module::params_from_iter(params)).map_err(database_err)?;
+                let mut output = Vec::new();
+                while let Some(row) = rows.next().map_err(database_err)? {
+                    let values: Result<Vec<String>, _> =
+                        (0..n_columns).map(|i| row.get(i)).collect();
+                    output.push(values.map_err(database_err)?);
+                }
+                Ok(output)
+            }};
+        }
+        match self {
+            #[cfg(feature = "sqlite")]
+            Connection::Sqlite(connection) => query_strings!(connection, rusqlite, to_sqlite_value),
+            #[cfg(feature = "duckdb")]
+            Connection::DuckDb(connection) => query_strings!(connection, duckdb, to_duckdb_value),
+            #[allow(unreachable_patterns)]
+            _ => {
+                let _ = (sql, params);
+                Err(err("database support not enabled"))
+            }
+        }
+    }
+
+    /// execute each statement with each of its parameter rows, in a single transaction
+    fn transaction(
+        &mut self,
+        statements: Vec<(String, Vec<Vec<SqlValue>>)>,
+    ) -> Result<(), CollectError> {
+        #[cfg(any(feature = "sqlite", feature = "duckdb"))]
+        macro_rules! transaction {
+            ($connection:expr, $module:ident, $to_value:ident) => {{
+                let transaction = $connection.transaction().map_err(database_err)?;
+                for (sql, rows) in statements.into_iter() {
+                    let mut statement = transaction.prepare(&sql).map_err(database_err)?;
+                    for row in rows.into_iter() {
+                        let params = row.into_iter().map($to_value);
+                        statement
+                            .execute($module::params_from_iter(params))
+                            .map_err(database_err)?;
+                    }
+                }
+                transaction.commit().map_err(database_err)
+            }};
+        }
+        match self {
+            #[cfg(feature = "sqlite")]
+            Connection::Sqlite(connection) => transaction!(connection, rusqlite, to_sqlite_value),
+            #[cfg(feature = "duckdb")]
+            Connection::DuckDb(connection) => transaction!(connection, duckdb, to_duckdb_value),
+            #[allow(unreachable_patterns)]
+            _ => {
+                let _ = statements;
+                Err(err("database support not enabled"))
+            }
+        }
+    }
+}
+
+impl DatabaseKind {
+    fn feature(&self) -> &'static str {
+        match self {
+            DatabaseKind::Sqlite => "sqlite",
+            DatabaseKind::DuckDb => "duckdb",
+        }
+    }
+}
+
+#[cfg(feature = "sqlite")]
+fn to_sqlite_value(value: SqlValue) -> rusqlite::types::Value {
+    use rusqlite::types::Value;
+    match value {
+        SqlValue::Null => Value::Null,
+        SqlValue::Integer(value) => Value::Integer(value),
+        // sqlite integers are signed 64-bit, so larger values are stored as text
+        SqlValue::UInteger(value) => match i64::try_from(value) {
+            Ok(value) => Value::Integer(value),
+            Err(_) => Value::Text(value.to_string()),
+        },
+        SqlValue::Real(value) => Value::Real(value),
+        SqlValue::Text(value) => Value::Text(value),
+        SqlValue::Blob(value) => Value::Blob(value),
+    }
+}
+
+#[cfg(feature = "duckdb")]
+fn to_duckdb_value(value: SqlValue) -> duckdb::types::Value {
+    use duckdb::types::Value;
+    match value {
+        SqlValue::Null => Value::Null,
+        SqlValue::Integer(value) => Value::BigInt(value),
+        SqlValue::UInteger(value) => Value::UBigInt(value),
+        SqlValue::Real(value) => Value::Double(value),
+        SqlValue::Text(value) => Value::Text(value),
+        SqlValue::Blob(value) => Value::Blob(value),
+    }
+}
+
+pub(crate) fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+pub(crate) fn chunks_table_sql() -> String {
+    format!(
+        "CREATE TABLE IF NOT EXISTS {} (table_name TEXT NOT NULL, chunk TEXT NOT NULL, \
+         n_rows BIGINT, schema_fingerprint TEXT, cryo_version TEXT, \
+         PRIMARY KEY (table_name, chunk))",
+        CHUNKS_TABLE
+    )
+}
+
+/// columns of natural key of datatype, if all of them are written
+fn natural_key(datatype: Datatype, columns: &[String]) -> Option<Vec<&'static str>> {
+    let primary_key = datatype.primary_key()?;
+    match primary_key.iter().all(|column| columns.iter().any(|c| c == column)) {
+        true => Some(primary_key),
+        false => None,
+    }
+}
+
+fn create_table_sql(
+    kind: DatabaseKind,
+    table_name: &str,
+    datatype: Datatype,
+    table: &Table,
+    columns: &[String],
+) -> String {
+    let mut definitions: Vec<String> = columns
+        .iter()
+        .map(|column| format!("{} {}", quote(column), sql_type(kind, column_type(table, column))))
+        .collect();
+    definitions.push(format!("{} TEXT NOT NULL", CHUNK_COLUMN));
+    if let Some(key) = natural_key(datatype, columns) {
+        let key: Vec<String> = key.iter().map(|column| quote(column)).collect();
+        definitions.push(format!("PRIMARY KEY ({})", key.join(", ")));
+    }
+    format!("CREATE TABLE IF NOT EXISTS {} ({})", quote(table_name), definitions.join(", "))
+}
+
+/// rows with the same natural key as an existing row replace it, so inserts are idempotent
+fn insert_sql(table_name: &str, columns: &[String], datatype: Datatype) -> String {
+    let mut names: Vec<String> = columns.iter().map(|column| quote(column)).collect();
+    names.push(CHUNK_COLUMN.to_string());
+    let placeholders = vec!["?"; names.len()].join(", ");
+    let verb = match natural_key(datatype, columns) {
+        Some(_) => "INSERT OR REPLACE",
+        None => "INSERT",
+    };
+    format!("{} INTO {} ({}) VALUES ({})", verb, quote(table_name), names.join(", "), placeholders)
+}
+
+/// type of written column, including the expanded columns of u256 values
+pub(crate) fn column_type(table: &Table, column: &str) -> Option<ColumnType> {
+    if let Some(column_type) = table.column_type(column) {
+        return Some(column_type)
+    }
+    table.u256_types.iter().find_map(|u256_type| {
+        let base = column.strip_suffix(u256_type.suffix().as_str())?;
+        match table.column_type(base) {
+            Some(column_type) if column_type.is_256_bit() => Some(u256_type.to_columntype()),
+            _ => None,
+        }
+    })
+}
+
+/// sql type of column, u256 values are stored in each of their expanded representations
+fn sql_type(kind: DatabaseKind, column_type: Option<ColumnType>) -> String {
+    let sql_type = match (kind, column_type) {
+        (DatabaseKind::Sqlite, Some(ColumnType::Boolean)) => "BOOLEAN",
+        (
+            DatabaseKind::Sqlite,
+            Some(ColumnType::UInt32 | ColumnType::UInt64 | ColumnType::Int32 | ColumnType::Int64),
+        ) => "INTEGER",
+        (DatabaseKind::Sqlite, Some(ColumnType::Float32 | ColumnType::Float64)) => "REAL",
+        (DatabaseKind::Sqlite, Some(ColumnType::Binary)) => "BLOB",
+        (DatabaseKind::Sqlite, _) => "TEXT",
+        (DatabaseKind::DuckDb, Some(ColumnType::Boolean)) => "BOOLEAN",
+        (DatabaseKind::DuckDb, Some(ColumnType::UInt32)) => "UINTEGER",
+        (DatabaseKind::DuckDb, Some(ColumnType::UInt64)) => "UBIGINT",
+        (DatabaseKind::DuckDb, Some(ColumnType::Int32)) => "INTEGER",
+        (DatabaseKind::DuckDb, Some(ColumnType::Int64)) => "BIGINT",
+        (DatabaseKind::DuckDb, Some(ColumnType::Float32)) => "FLOAT",
+        (DatabaseKind::DuckDb, Some(ColumnType::Float64)) => "DOUBLE",
+        (DatabaseKind::DuckDb, Some(ColumnType::Decimal128(scale))) => {
+            return format!("DECIMAL({}, {})", DECIMAL128_PRECISION, scale)
+        }
+        (DatabaseKind::DuckDb, Some(ColumnType::Binary)) => "BLOB",
+        (DatabaseKind::DuckDb, _) => "VARCHAR",
+    };
+    sql_type.to_string()
+}
+
+/// convert rows of dataframe to database values, followed by the chunk label
+fn dataframe_rows(df: &DataFrame, chunk: &str) -> Result<Vec<Vec<SqlValue>>, CollectError> {
+    let mut rows = Vec::with_capacity(df.height());
+    for i in 0..df.height() {
+        let mut row = Vec::with_capacity(df.width() + 1);
+        for series in df.get_columns() {
+            let value = series.get(i).map_err(CollectError::PolarsError)?;
+            row.push(to_sql_value(value));
+        }
+        row.push(SqlValue::Text(chunk.to_string()));
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+fn to_sql_value(value: AnyValue<'_>) -> SqlValue {
+    match value {
+        AnyValue::Null => SqlValue::Null,
+        AnyValue::Boolean(value) => SqlValue::Integer(value as i64),
+        AnyValue::UInt8(value) => SqlValue::Integer(value as i64),
+        AnyValue::UInt16(value) => SqlValue::Integer(value as i64),
+        AnyValue::UInt32(value) => SqlValue::Integer(value as i64),
+        AnyValue::UInt64(value) => SqlValue::UInteger(value),
+        AnyValue::Int8(value) => SqlValue::Integer(value as i64),
+        AnyValue::Int16(value) => SqlValue::Integer(value as i64),
+        AnyValue::Int32(value) => SqlValue::Integer(value as i64),
+        AnyValue::Int64(value) => SqlValue::Integer(value),
+        AnyValue::Float32(value) => SqlValue::Real(value as f64),
+        AnyValue::Float64(value) => SqlValue::Real(value),
+        AnyValue::String(value) => SqlValue::Text(value.to_string()),
+        AnyValue::StringOwned(value) => SqlValue::Text(value.to_string()),
+        AnyValue::Binary(value) => SqlValue::Blob(value.to_vec()),
+        AnyValue::BinaryOwned(value) => SqlValue::Blob(value),
+        value => SqlValue::Text(value.to_string()),
+    }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+    use crate::{ColumnEncoding, U256Type};
+
+    fn blocks_table(columns: &[&str]) -> Table {
+        let columns = Some(columns.iter().map(|column| column.to_string()).collect());
+        Datatype::Blocks
+            .table_schema(
+                &[U256Type::Binary],
+                &ColumnEncoding::Binary,
+                &None,
+                &None,
+                &columns,
+                None,
+                None,
+            )
+            .unwrap()
+    }
+
+    fn count_rows(database: &Database, table_name: &str) -> Vec<Vec<String>> {
+        let sql = format!(
+            "SELECT {}, CAST(COUNT(*) AS TEXT) FROM {} GROUP BY {} ORDER BY {}",
+            CHUNK_COLUMN,
+            quote(table_name),
+            CHUNK_COLUMN,
+            CHUNK_COLUMN
+        );
+        database.with_connection(|connection| connection.query_strings(&sql, vec![])).unwrap()
+    }
+
+    #[test]
+    fn test_write_chunk_twice() {
+        let path =
+            std::env::temp_dir().join(format!("cryo_database_{}.sqlite", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let database = Database::new(path.clone()).unwrap();
+        let table = blocks_table(&["block_number", "block_hash"]);
+        let df = df!(
+            "block_number" => [1u32, 2u32],
+            "block_hash" => [vec![1u8; 32], vec![2u8; 32]],
+        )
+        .unwrap();
+
+        assert!(!database.is_chunk_complete("blocks", "1_to_2", Some(&table), true).unwrap());
+        database.write_chunk("blocks", "1_to_2", Datatype::Blocks, &table, &df).unwrap();
+        database.write_chunk("blocks", "1_to_2", Datatype::Blocks, &table, &df).unwrap();
+        database
+            .write_chunk("blocks", "3_to_3", Datatype::Blocks, &table, &df.head(Some(0)))
+            .unwrap();
+
+        // rewriting a chunk replaces its rows instead of duplicating them
+        let expected = vec![vec!["1_to_2".to_string(), "2".to_string()]];
+        assert_eq!(count_rows(&database, "blocks"), expected);
+        assert!(database.is_chunk_complete("blocks", "1_to_2", Some(&table), true).unwrap());
+        assert!(database.is_chunk_complete("blocks", "3_to_3", Some(&table), true).unwrap());
+        assert!(!database.is_chunk_complete("blocks", "4_to_4", Some(&table), true).unwrap());
+
+        // chunks written with another schema are stale
+        let other_table = blocks_table(&["block_number", "block_hash", "timestamp"]);
+        assert!(!database.is_chunk_complete("blocks", "1_to_2", Some(&other_table), true).unwrap());
+        assert!(database.is_chunk_complete("blocks", "1_to_2", Some(&other_table), false).unwrap());
+
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -87,9 +87,15 @@ macro_rules! with_series_u256 {
                 $all_series.push(Series::new(name, converted));
             }
 
-            // decimal128
-            if $schema.u256_types.contains(&U256Type::Decimal128) {
-                return Err(CollectError::CollectError("DECIMAL128 not implemented".to_string()))
+            // decimal128 and decimal256
+            for u256_type in $schema.u256_types.iter() {
+                if let U256Type::Decimal128(_) | U256Type::Decimal256 = u256_type {
+                    $all_series.push($value.to_u256_series(
+                        $name.to_string(),
+                        u256_type.clone(),
+                        &$schema.binary_type,
+                    )?);
+                }
             }
         }
     };
@@ -168,9 +174,15 @@ macro_rules! with_series_option_u256 {
                 $all_series.push(Series::new(name, converted));
             }
 
-            // decimal128
-            if $schema.u256_types.contains(&U256Type::Decimal128) {
-                return Err(CollectError::CollectError("DECIMAL128 not implemented".to_string()))
+            // decimal128 and decimal256
+            for u256_type in $schema.u256_types.iter() {
+                if let U256Type::Decimal128(_) | U256Type::Decimal256 = u256_type {
+                    $all_series.push($value.to_u256_series(
+                        $name.to_string(),
+                        u256_type.clone(),
+                        &$schema.binary_type,
+                    )?);
+                }
             }
         }
     };
@@ -1,8 +1,22 @@
-use std::path::Path;
+use bzip2::write::BzEncoder;
+use flate2::write::GzEncoder;
+use std::{io::Write, path::Path, sync::Mutex};
 
-use polars::prelude::*;
+use ethers::prelude::*;
+use polars::{
+    export::arrow::{
+        array::PrimitiveArray,
+        datatypes::PhysicalType,
+        io::ipc::write::{StreamWriter, WriteOptions},
+        types::{i256, PrimitiveType},
+    },
+    prelude::*,
+};
+use polars_parquet::write::{self as parquet, FallibleStreamingIterator};
 
-use crate::types::{FileError, FileOutput};
+use crate::types::{
+    FileError, FileFormat, FileOutput, TextCompression, U256Type, DECIMAL256_PRECISION,
+};
 
 /// write polars dataframe to file
 pub(crate) fn df_to_file(
@@ -11,10 +25,21 @@ pub(crate) fn df_to_file(
     file_output: &FileOutput,
 ) -> Result<(), FileError> {
     let tmp_filename = filename.with_extension("_tmp");
-    let result = match filename.extension().and_then(|ex| ex.to_str()) {
+
+    // text files are compressed when their name ends with the extension of the output compression
+    let compression = file_output.format.text_compression();
+    let (compression, uncompressed_filename) = match compression.extension() {
+        Some(extension) if filename.extension().and_then(|ex| ex.to_str()) == Some(extension) => {
+            (compression, filename.with_extension(""))
+        }
+        _ => (TextCompression::Uncompressed, filename.to_path_buf()),
+    };
+    let result = match uncompressed_filename.extension().and_then(|ex| ex.to_str()) {
         Some("parquet") => df_to_parquet(df, &tmp_filename, file_output),
-        Some("csv") => df_to_csv(df, &tmp_filename),
-        Some("json") => df_to_json(df, &tmp_filename),
+        Some("csv") => df_to_csv(df, &tmp_filename, compression),
+        Some("json") => df_to_json(df, &tmp_filename, compression),
+        Some("ndjson") => df_to_ndjson(df, &tmp_filename, compression),
+        Some("arrow") => df_to_arrow(df, &tmp_filename, file_output),
         _ => return Err(FileError::FileWriteError),
     };
     match result {
@@ -30,33 +55,385 @@ fn df_to_parquet(
     file_output: &FileOutput,
 ) -> Result<(), FileError> {
     let file = std::fs::File::create(filename).map_err(|_e| FileError::FileWriteError)?;
-    let result = ParquetWriter::new(file)
-        .with_statistics(file_output.parquet_statistics)
-        .with_compression(file_output.parquet_compression)
-        .with_row_group_size(file_output.row_group_size)
-        .finish(df);
+    let result = if df.get_columns().iter().any(is_decimal256) {
+        let options = parquet::WriteOptions {
+            write_statistics: file_output.parquet_statistics,
+            compression: file_output.parquet_compression.into(),
+            version: parquet::Version::V2,
+            data_pagesize_limit: None,
+        };
+        df_to_decimal256_parquet(df, file, options, file_output.row_group_size)
+    } else {
+        ParquetWriter::new(file)
+            .with_statistics(file_output.parquet_statistics)
+            .with_compression(file_output.parquet_compression)
+            .with_row_group_size(file_output.row_group_size)
+            .finish(df)
+            .map(|_| ())
+    };
     match result {
         Err(_e) => Err(FileError::FileWriteError),
         _ => Ok(()),
     }
 }
 
+/// whether series holds the decimal strings of a decimal256 column
+pub(crate) fn is_decimal256(series: &Series) -> bool {
+    series.dtype() == &DataType::String && series.name().ends_with(&U256Type::Decimal256.suffix())
+}
+
+/// write polars dataframe to parquet file, writing decimal256 columns as decimal(76, 0)
+///
+/// polars cannot hold 256-bit decimals, so these columns are converted to arrow arrays here
+fn df_to_decimal256_parquet(
+    df: &DataFrame,
+    file: std::fs::File,
+    options: parquet::WriteOptions,
+    row_group_size: Option<usize>,
+) -> PolarsResult<()> {
+    let fields: Vec<ArrowField> = df
+        .get_columns()
+        .iter()
+        .map(|series| {
+            if is_decimal256(series) {
+                let data_type = ArrowDataType::Decimal256(DECIMAL256_PRECISION, 0);
+                ArrowField::new(series.name(), data_type, true)
+            } else {
+                series.field().to_arrow()
+            }
+        })
+        .collect();
+    let schema = ArrowSchema::from(fields);
+    let encodings: Vec<Vec<parquet::Encoding>> = schema
+        .fields
+        .iter()
+        .map(|field| parquet::transverse(&field.data_type, parquet_encoding))
+        .collect();
+
+    let parquet_types = parquet::to_parquet_schema(&schema)?.fields().to_vec();
+    let mut writer = parquet::FileWriter::try_new(file, schema, options)?;
+    let row_group_size = row_group_size.unwrap_or(512 * 512).max(1);
+    for offset in (0..df.height()).step_by(row_group_size) {
+        let slice = df.slice(offset as i64, row_group_size);
+        let mut columns = Vec::new();
+        for ((series, type_), encoding) in
+            slice.get_columns().iter().zip(parquet_types.iter()).zip(encodings.iter())
+        {
+            let pages = if is_decimal256(series) {
+                // arrow cannot estimate the size of i256 arrays to split them into pages, so each
+                // row group of a decimal256 column is written as a single page
+                let array = decimal256_array(series)?;
+                let nested = parquet::to_nested(array.as_ref(), type_)?;
+                let leaf = parquet::to_parquet_leaves(type_.clone()).remove(0);
+                let page = parquet::array_to_page(
+                    array.as_ref(),
+                    leaf,
+                    &nested[0],
+                    options,
+                    encoding[0],
+                );
+                vec![parquet::DynIter::new(std::iter::once(page))]
+            } else {
+                parquet::array_to_columns(
+                    series.rechunk().to_arrow(0),
+                    type_.clone(),
+                    options,
+                    encoding,
+                )?
+            };
+            for pages in pages {
+                let pages = pages.map(|page| {
+                    page.map_err(|e| polars_parquet::parquet::error::Error::OutOfSpec(e.to_string()))
+                });
+                let compressed = parquet::Compressor::new(
+                    parquet::DynIter::new(pages),
+                    options.compression,
+                    vec![],
+                )
+                .map_err(|e| polars_err!(ComputeError: "{}", e));
+                columns.push(Ok(parquet::DynStreamingIterator::new(compressed)));
+            }
+        }
+        writer.write(parquet::DynIter::new(columns.into_iter()))?;
+    }
+    writer.end(None)?;
+    Ok(())
+}
+
+/// encodings of parquet columns, matching those used by polars
+fn parquet_encoding(data_type: &ArrowDataType) -> parquet::Encoding {
+    match data_type.to_physical_type() {
+        PhysicalType::Dictionary(_) | PhysicalType::LargeBinary | PhysicalType::LargeUtf8 => {
+            parquet::Encoding::RleDictionary
+        }
+        PhysicalType::Primitive(
+            PrimitiveType::Float16 |
+            PrimitiveType::Float32 |
+            PrimitiveType::Float64 |
+            PrimitiveType::Int256,
+        ) => parquet::Encoding::Plain,
+        PhysicalType::Primitive(_) => parquet::Encoding::RleDictionary,
+        _ => parquet::Encoding::Plain,
+    }
+}
+
+/// convert decimal strings of series into arrow decimal256 array
+fn decimal256_array(series: &Series) -> PolarsResult<Box<dyn polars::export::arrow::array::Array>> {
+    let values = series
+        .str()?
+        .into_iter()
+        .map(|value| value.map(parse_i256).transpose())
+        .collect::<PolarsResult<Vec<Option<i256>>>>()?;
+    let data_type = ArrowDataType::Decimal256(DECIMAL256_PRECISION, 0);
+    Ok(PrimitiveArray::<i256>::from(values).to(data_type).boxed())
+}
+
+fn parse_i256(value: &str) -> PolarsResult<i256> {
+    let value = I256::from_dec_str(value)
+        .map_err(|_| polars_err!(ComputeError: "invalid decimal256 value: {}", value))?;
+    let mut bytes = [0u8; 32];
+    value.into_raw().to_big_endian(&mut bytes);
+    let (hi, lo) = bytes.split_at(16);
+    let hi = i128::from_be_bytes(hi.try_into().unwrap_or_default());
+    let lo = i128::from_be_bytes(lo.try_into().unwrap_or_default());
+    Ok(i256::from_words(hi, lo))
+}
+
 /// write polars dataframe to csv file
-fn df_to_csv(df: &mut DataFrame, filename: &Path) -> Result<(), FileError> {
+fn df_to_csv(
+    df: &mut DataFrame,
+    filename: &Path,
+    compression: TextCompression,
+) -> Result<(), FileError> {
+    write_text_file(filename, compression, |writer| CsvWriter::new(writer).finish(df))
+}
+
+/// write polars dataframe to json file
+fn df_to_json(
+    df: &mut DataFrame,
+    filename: &Path,
+    compression: TextCompression,
+) -> Result<(), FileError> {
+    write_text_file(filename, compression, |writer| {
+        JsonWriter::new(writer).with_json_format(JsonFormat::Json).finish(df)
+    })
+}
+
+/// write polars dataframe to newline-delimited json file
+fn df_to_ndjson(
+    df: &mut DataFrame,
+    filename: &Path,
+    compression: TextCompression,
+) -> Result<(), FileError> {
+    write_text_file(filename, compression, |writer| {
+        JsonWriter::new(writer).with_json_format(JsonFormat::JsonLines).finish(df)
+    })
+}
+
+/// create text file, passing a writer that compresses its contents
+fn write_text_file<F>(
+    filename: &Path,
+    compression: TextCompression,
+    write: F,
+) -> Result<(), FileError>
+where
+    F: FnOnce(&mut dyn Write) -> PolarsResult<()>,
+{
     let file = std::fs::File::create(filename).map_err(|_e| FileError::FileWriteError)?;
-    let result = CsvWriter::new(file).finish(df);
+    let mut file = std::io::BufWriter::new(file);
+    let result = match compression {
+        TextCompression::Uncompressed => {
+            write(&mut file).map_err(|_e| FileError::FileWriteError)?;
+            file.flush()
+        }
+        TextCompression::Gzip(level) => {
+            let mut encoder = GzEncoder::new(&mut file, flate2::Compression::new(level));
+            write(&mut encoder).map_err(|_e| FileError::FileWriteError)?;
+            encoder.finish().and_then(|file| file.flush())
+        }
+        TextCompression::Zstd(level) => {
+            let mut encoder =
+                zstd::Encoder::new(&mut file, level).map_err(|_e| FileError::FileWriteError)?;
+            write(&mut encoder).map_err(|_e| FileError::FileWriteError)?;
+            encoder.finish().and_then(|file| file.flush())
+        }
+        TextCompression::Bzip2(level) => {
+            let mut encoder = BzEncoder::new(&mut file, bzip2::Compression::new(level));
+            write(&mut encoder).map_err(|_e| FileError::FileWriteError)?;
+            encoder.finish().and_then(|file| file.flush())
+        }
+    };
+    result.map_err(|_e| FileError::FileWriteError)
+}
+
+/// write polars dataframe to arrow ipc file
+fn df_to_arrow(
+    df: &mut DataFrame,
+    filename: &Path,
+    file_output: &FileOutput,
+) -> Result<(), FileError> {
+    let file = std::fs::File::create(filename).map_err(|_e| FileError::FileWriteError)?;
+    let result = IpcWriter::new(file).with_compression(file_output.ipc_compression).finish(df);
     match result {
         Err(_e) => Err(FileError::FileWriteError),
         _ => Ok(()),
     }
 }
 
-/// write polars dataframe to json file
-fn df_to_json(df: &mut DataFrame, filename: &Path) -> Result<(), FileError> {
-    let file = std::fs::File::create(filename).map_err(|_e| FileError::FileWriteError)?;
-    let result = JsonWriter::new(file).with_json_format(JsonFormat::Json).finish(df);
+/// state of data streamed to stdout, shared by all chunks of the process
+struct StdoutState {
+    csv_header_written: bool,
+    ipc_writer: Option<StreamWriter<std::io::Stdout>>,
+}
+
+static STDOUT: Mutex<StdoutState> =
+    Mutex::new(StdoutState { csv_header_written: false, ipc_writer: None });
+
+/// write polars dataframe to stdout
+///
+/// each dataframe is written as one contiguous block of rows. csv headers are only written
+/// for the first dataframe, and arrow dataframes are appended to a single ipc stream
+pub(crate) fn df_to_stdout(df: &mut DataFrame, file_output: &FileOutput) -> Result<(), FileError> {
+    let mut state = STDOUT.lock().map_err(|_e| FileError::FileWriteError)?;
+    let result = match file_output.format {
+        FileFormat::Csv(TextCompression::Uncompressed) => {
+            let include_header = !state.csv_header_written;
+            state.csv_header_written = true;
+            CsvWriter::new(std::io::stdout().lock()).include_header(include_header).finish(df)
+        }
+        FileFormat::NdJson(TextCompression::Uncompressed) => {
+            JsonWriter::new(std::io::stdout().lock())
+                .with_json_format(JsonFormat::JsonLines)
+                .finish(df)
+        }
+        FileFormat::Arrow => write_ipc_stream(&mut state, df, file_output),
+        _ => return Err(FileError::FileWriteError),
+    };
     match result {
         Err(_e) => Err(FileError::FileWriteError),
-        _ => Ok(()),
+        _ => std::io::stdout().flush().map_err(|_e| FileError::FileWriteError),
+    }
+}
+
+fn write_ipc_stream(
+    state: &mut StdoutState,
+    df: &mut DataFrame,
+    file_output: &FileOutput,
+) -> PolarsResult<()> {
+    if state.ipc_writer.is_none() {
+        let options = WriteOptions { compression: file_output.ipc_compression.map(|c| c.into()) };
+        let mut writer = StreamWriter::new(std::io::stdout(), options);
+        writer.start(&df.schema().to_arrow(), None)?;
+        state.ipc_writer = Some(writer);
+    }
+    if let Some(writer) = state.ipc_writer.as_mut() {
+        df.align_chunks();
+        for batch in df.iter_chunks() {
+            writer.write(&batch, None)?;
+        }
+    }
+    Ok(())
+}
+
+/// flush data streamed to stdout, ending the arrow ipc stream if one was started
+pub(crate) fn finish_stdout() -> Result<(), FileError> {
+    let mut state = STDOUT.lock().map_err(|_e| FileError::FileWriteError)?;
+    if let Some(mut writer) = state.ipc_writer.take() {
+        writer.finish().map_err(|_e| FileError::FileWriteError)?;
+    }
+    std::io::stdout().flush().map_err(|_e| FileError::FileWriteError)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{dataframes::read_text_file, ColumnEncoding, ToU256Series};
+
+    #[test]
+    fn test_compressed_csv_roundtrip() {
+        let compressions =
+            [TextCompression::Gzip(6), TextCompression::Zstd(3), TextCompression::Bzip2(6)];
+        for compression in compressions.into_iter() {
+            let mut df =
+                DataFrame::new(vec![Series::new("block_number", vec![1u64, 2, 3])]).unwrap();
+            let extension = compression.extension().unwrap();
+            let path = std::env::temp_dir().join(format!(
+                "cryo_test_{}.csv.{}",
+                std::process::id(),
+                extension
+            ));
+            df_to_csv(&mut df, &path, compression).unwrap();
+
+            let (inner_extension, contents) = read_text_file(&path).unwrap().unwrap();
+            assert_eq!(inner_extension.as_deref(), Some("csv"));
+            assert_eq!(String::from_utf8(contents).unwrap(), "block_number\n1\n2\n3\n");
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_decimal256_parquet_roundtrip() {
+        let max = "9".repeat(DECIMAL256_PRECISION);
+        let values = vec![
+            Some(U256::exp10(DECIMAL256_PRECISION) - 1),
+            Some(U256::exp10(DECIMAL256_PRECISION)),
+            None,
+            Some(U256::from(7)),
+        ];
+        let signed =
+            vec![-I256::from_dec_str(&max).unwrap(), I256::MIN, I256::from(-1), I256::zero()];
+        let df = DataFrame::new(vec![
+            values
+                .to_u256_series(
+                    "value".to_string(),
+                    U256Type::Decimal256,
+                    &ColumnEncoding::Binary,
+                )
+                .unwrap(),
+            signed
+                .to_u256_series(
+                    "delta".to_string(),
+                    U256Type::Decimal256,
+                    &ColumnEncoding::Binary,
+                )
+                .unwrap(),
+            Series::new("block_number", vec![1u64, 2, 3, 4]),
+        ])
+        .unwrap();
+        let path =
+            std::env::temp_dir().join(format!("cryo_test_{}_d256.parquet", std::process::id()));
+        let options = parquet::WriteOptions {
+            write_statistics: true,
+            compression: parquet::CompressionOptions::Uncompressed,
+            version: parquet::Version::V2,
+            data_pagesize_limit: None,
+        };
+        let file = std::fs::File::create(&path).unwrap();
+        df_to_decimal256_parquet(&df, file, options, Some(3)).unwrap();
+
+        // the file declares decimal(76, 0) columns
+        let mut file = std::fs::File::open(&path).unwrap();
+        let metadata = polars_parquet::read::read_metadata(&mut file).unwrap();
+        let schema = polars_parquet::read::infer_schema(&metadata).unwrap();
+        assert_eq!(schema.fields[0].data_type, ArrowDataType::Decimal256(DECIMAL256_PRECISION, 0));
+        assert_eq!(metadata.row_groups.len(), 2);
+
+        let read = crate::dataframes::read_parquet(file).unwrap();
+        let expected_signed =
+            [Some(format!("-{}", max)), None, Some("-1".to_string()), Some("0".to_string())];
+        let read_signed: Vec<_> = read
+            .column("delta_d256")
+            .unwrap()
+            .str()
+            .unwrap()
+            .into_iter()
+            .map(|v| v.map(String::from))
+            .collect();
+        assert_eq!(read_signed, expected_signed);
+        let read_values: Vec<_> =
+            read.column("value_d256").unwrap().str().unwrap().into_iter().collect();
+        assert_eq!(read_values, vec![Some(max.as_str()), None, None, Some("7")]);
+        assert!(read.column("block_number").unwrap().equals(df.column("block_number").unwrap()));
+        std::fs::remove_file(path).unwrap();
     }
 }
@@ -1,15 +1,144 @@
 use crate::ParseError;
-use polars::prelude::*;
+use bzip2::read::BzDecoder;
+use flate2::read::MultiGzDecoder;
+use polars::{
+    export::arrow::{
+        array::{Array, PrimitiveArray},
+        types::i256,
+    },
+    prelude::*,
+};
+use polars_parquet::read as parquet;
+use std::{
+    io::{Cursor, Read, Seek},
+    path::Path,
+};
 
-/// read single binary column of parquet file as Vec<u8>
-pub fn read_binary_column(path: &str, column: &str) -> Result<Vec<Vec<u8>>, ParseError> {
-    let file = std::fs::File::open(path)
-        .map_err(|_e| ParseError::ParseError("could not open file path".to_string()))?;
+/// read single column of parquet, arrow ipc, or csv file as dataframe
+///
+/// csv files can be compressed with gzip, zstd, or bzip2
+pub fn read_column(path: &str, column: &str) -> Result<DataFrame, ParseError> {
+    let columns = Some(vec![column.to_string()]);
+    let (extension, contents) = match read_text_file(Path::new(path)) {
+        Ok(Some((extension, contents))) => (extension, Some(contents)),
+        Ok(None) => (file_extension(Path::new(path)), None),
+        Err(_e) => return Err(ParseError::ParseError("could not open file path".to_string())),
+    };
+    let df = match (extension.as_deref(), contents) {
+        (Some("csv"), Some(contents)) => {
+            CsvReader::new(Cursor::new(contents)).with_columns(columns).finish().and_then(
+                |mut df| {
+                    // integers of csv files are inferred as signed
+                    let series = df.column(column)?;
+                    if series.dtype() == &DataType::Int64 {
+                        let series = series.cast(&DataType::UInt64)?;
+                        df.with_column(series)?;
+                    }
+                    Ok(df)
+                },
+            )
+        }
+        (Some("arrow") | Some("ipc") | Some("feather"), None) => {
+            let file = open_file(path)?;
+            IpcReader::new(file).with_columns(columns).memory_mapped(true).finish()
+        }
+        (_, None) => ParquetReader::new(open_file(path)?).with_columns(columns).finish(),
+        (_, Some(_)) => {
+            return Err(ParseError::ParseError("unsupported input file format".to_string()))
+        }
+    };
+    df.map_err(|_e| ParseError::ParseError("could not read data from column".to_string()))
+}
+
+fn open_file(path: &str) -> Result<std::fs::File, ParseError> {
+    std::fs::File::open(path)
+        .map_err(|_e| ParseError::ParseError("could not open file path".to_string()))
+}
+
+fn file_extension(path: &Path) -> Option<String> {
+    path.extension().and_then(|ex| ex.to_str()).map(|ex| ex.to_string())
+}
+
+/// read parquet file as dataframe, reading decimal256 columns as decimal strings
+///
+/// polars cannot hold 256-bit decimals, so files with these columns are read as arrow arrays
+pub(crate) fn read_parquet(mut file: std::fs::File) -> PolarsResult<DataFrame> {
+    let metadata = parquet::read_metadata(&mut file)?;
+    let schema = parquet::infer_schema(&metadata)?;
+    let is_decimal256 =
+        |field: &ArrowField| matches!(field.data_type, ArrowDataType::Decimal256(..));
+    if !schema.fields.iter().any(is_decimal256) {
+        file.rewind()?;
+        return ParquetReader::new(file).finish()
+    }
+
+    let columns = schema
+        .fields
+        .iter()
+        .map(|field| {
+            let dtype = if is_decimal256(field) {
+                DataType::String
+            } else {
+                DataType::from(&field.data_type)
+            };
+            Series::new_empty(&field.name, &dtype)
+        })
+        .collect();
+    let mut df = DataFrame::new(columns)?;
+    let reader =
+        parquet::FileReader::new(file, metadata.row_groups, schema.clone(), None, None, None);
+    for chunk in reader {
+        let columns = chunk?
+            .into_arrays()
+            .into_iter()
+            .zip(schema.fields.iter())
+            .map(|(array, field)| {
+                if is_decimal256(field) {
+                    Ok(decimal256_series(&field.name, array.as_ref()))
+                } else {
+                    Series::try_from((field.name.as_str(), array))
+                }
+            })
+            .collect::<PolarsResult<Vec<_>>>()?;
+        df.vstack_mut(&DataFrame::new(columns)?)?;
+    }
+    Ok(df)
+}
+
+fn decimal256_series(name: &str, array: &dyn Array) -> Series {
+    let values: Vec<Option<String>> = match array.as_any().downcast_ref::<PrimitiveArray<i256>>() {
+        Some(array) => array.iter().map(|value| value.map(|value| value.0.to_string())).collect(),
+        None => vec![None; array.len()],
+    };
+    Series::new(name, values)
+}
 
-    let df = ParquetReader::new(file)
-        .with_columns(Some(vec![column.to_string()]))
-        .finish()
-        .map_err(|_e| ParseError::ParseError("could not read data from column".to_string()))?;
+/// read contents of csv or json file, decompressing files ending in .gz, .zst, or .bz2
+///
+/// returns the extension of the uncompressed file along with its contents, or None for files
+/// that are not text files
+pub(crate) fn read_text_file(path: &Path) -> std::io::Result<Option<(Option<String>, Vec<u8>)>> {
+    let (extension, compressed) = match file_extension(path).as_deref() {
+        Some("gz") | Some("zst") | Some("bz2") => (file_extension(&path.with_extension("")), true),
+        _ => (file_extension(path), false),
+    };
+    if !matches!(extension.as_deref(), Some("csv") | Some("json") | Some("ndjson")) {
+        return Ok(None)
+    }
+    let file = std::fs::File::open(path)?;
+    let mut contents = Vec::new();
+    match (compressed, file_extension(path).as_deref()) {
+        (true, Some("gz")) => MultiGzDecoder::new(file).read_to_end(&mut contents)?,
+        (true, Some("zst")) => zstd::Decoder::new(file)?.read_to_end(&mut contents)?,
+        (true, _) => BzDecoder::new(file).read_to_end(&mut contents)?,
+        (false, _) => std::io::BufReader::new(file).read_to_end(&mut contents)?,
+    };
+    Ok(Some((extension, contents)))
+}
+
+/// read single binary column of parquet or arrow ipc file as Vec<u8>
+pub fn read_binary_column(path: &str, column: &str) -> Result<Vec<Vec<u8>>, ParseError> {
+    let df = read_column(path, column)?;
 
     let series = df
         .column(column)
@@ -17,6 +146,19 @@ pub fn read_binary_column(path: &str, column: &str) -> Result<Vec<Vec<u8>>, Pars
         .unique()
         .map_err(|_e| ParseError::ParseError("could not get column".to_string()))?;
 
+    // binary columns of csv files are hex encoded
+    if let Ok(ca) = series.str() {
+        return ca
+            .into_iter()
+            .map(|value| {
+                let value = value
+                    .ok_or_else(|| ParseError::ParseError("missing value in column".to_string()))?;
+                prefix_hex::decode(value)
+                    .map_err(|_e| ParseError::ParseError("could not decode hex value".to_string()))
+            })
+            .collect()
+    }
+
     let ca = series
         .binary()
         .map_err(|_e| ParseError::ParseError("could not convert to binary column".to_string()))?;
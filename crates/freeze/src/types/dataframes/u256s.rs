@@ -2,6 +2,15 @@ use crate::*;
 use ethers::prelude::*;
 use polars::prelude::*;
 
+/// precision of decimal128 columns, the number of decimal digits that always fit in an i128
+pub const DECIMAL128_PRECISION: usize = 38;
+
+/// precision of decimal256 columns, the number of decimal digits that always fit in an i256
+///
+/// polars decimals are backed by i128, so decimal256 columns are held as decimal strings and
+/// only become decimals when written to parquet
+pub const DECIMAL256_PRECISION: usize = 76;
+
 /// Converts a Vec of U256-like data into a polars Series
 pub trait ToU256Series {
     /// convert a Vec of U256-like data into a polars Series
@@ -53,8 +62,13 @@ impl ToU256Series for Vec<U256> {
                 let converted: Vec<u64> = self.iter().map(|v| v.as_u64()).collect();
                 Ok(Series::new(name, converted))
             }
-            U256Type::Decimal128 => {
-                Err(CollectError::CollectError("DECIMAL128 not implemented".to_string()))
+            U256Type::Decimal128(scale) => {
+                let converted: Vec<Option<i128>> = self.iter().map(u256_to_decimal128).collect();
+                decimal128_series(name, converted, scale)
+            }
+            U256Type::Decimal256 => {
+                let converted: Vec<Option<String>> = self.iter().map(u256_to_decimal256).collect();
+                Ok(Series::new(name, converted))
             }
         }
     }
@@ -108,8 +122,15 @@ impl ToU256Series for Vec<Option<U256>> {
                     self.iter().map(|v| v.map(|x| x.as_u64())).collect();
                 Ok(Series::new(name, converted))
             }
-            U256Type::Decimal128 => {
-                Err(CollectError::CollectError("DECIMAL128 not implemented".to_string()))
+            U256Type::Decimal128(scale) => {
+                let converted: Vec<Option<i128>> =
+                    self.iter().map(|v| v.as_ref().and_then(u256_to_decimal128)).collect();
+                decimal128_series(name, converted, scale)
+            }
+            U256Type::Decimal256 => {
+                let converted: Vec<Option<String>> =
+                    self.iter().map(|v| v.as_ref().and_then(u256_to_decimal256)).collect();
+                Ok(Series::new(name, converted))
             }
         }
     }
@@ -148,15 +169,22 @@ impl ToU256Series for Vec<I256> {
                 Ok(Series::new(name, converted))
             }
             U256Type::U32 => {
-                let converted: Vec<u32> = self.iter().map(|v| v.as_u32()).collect();
+                let converted: Vec<Option<u32>> =
+                    self.iter().map(|v| u32::try_from(*v).ok()).collect();
                 Ok(Series::new(name, converted))
             }
             U256Type::U64 => {
-                let converted: Vec<u64> = self.iter().map(|v| v.as_u64()).collect();
+                let converted: Vec<Option<u64>> =
+                    self.iter().map(|v| u64::try_from(*v).ok()).collect();
                 Ok(Series::new(name, converted))
             }
-            U256Type::Decimal128 => {
-                Err(CollectError::CollectError("DECIMAL128 not implemented".to_string()))
+            U256Type::Decimal128(scale) => {
+                let converted: Vec<Option<i128>> = self.iter().map(i256_to_decimal128).collect();
+                decimal128_series(name, converted, scale)
+            }
+            U256Type::Decimal256 => {
+                let converted: Vec<Option<String>> = self.iter().map(i256_to_decimal256).collect();
+                Ok(Series::new(name, converted))
             }
         }
     }
@@ -202,17 +230,115 @@ impl ToU256Series for Vec<Option<I256>> {
             }
             U256Type::U32 => {
                 let converted: Vec<Option<u32>> =
-                    self.iter().map(|v| v.map(|x| x.as_u32())).collect();
+                    self.iter().map(|v| v.and_then(|x| u32::try_from(x).ok())).collect();
                 Ok(Series::new(name, converted))
             }
             U256Type::U64 => {
                 let converted: Vec<Option<u64>> =
-                    self.iter().map(|v| v.map(|x| x.as_u64())).collect();
+                    self.iter().map(|v| v.and_then(|x| u64::try_from(x).ok())).collect();
                 Ok(Series::new(name, converted))
             }
-            U256Type::Decimal128 => {
-                Err(CollectError::CollectError("DECIMAL128 not implemented".to_string()))
+            U256Type::Decimal128(scale) => {
+                let converted: Vec<Option<i128>> =
+                    self.iter().map(|v| v.as_ref().and_then(i256_to_decimal128)).collect();
+                decimal128_series(name, converted, scale)
+            }
+            U256Type::Decimal256 => {
+                let converted: Vec<Option<String>> =
+                    self.iter().map(|v| v.as_ref().and_then(i256_to_decimal256)).collect();
+                Ok(Series::new(name, converted))
             }
         }
     }
 }
+
+/// unscaled decimal128 value, or None if the value has more digits than fit
+fn u256_to_decimal128(value: &U256) -> Option<i128> {
+    if *value < U256::exp10(DECIMAL128_PRECISION) {
+        Some(value.as_u128() as i128)
+    } else {
+        None
+    }
+}
+
+/// unscaled decimal128 value, or None if the value has more digits than fit
+fn i256_to_decimal128(value: &I256) -> Option<i128> {
+    let magnitude = u256_to_decimal128(&value.unsigned_abs())?;
+    if value.is_negative() {
+        Some(-magnitude)
+    } else {
+        Some(magnitude)
+    }
+}
+
+/// decimal string of value, or None if the value has more digits than fit
+fn u256_to_decimal256(value: &U256) -> Option<String> {
+    if *value < U256::exp10(DECIMAL256_PRECISION) {
+        Some(value.to_string())
+    } else {
+        None
+    }
+}
+
+/// decimal string of value, or None if the value has more digits than fit
+fn i256_to_decimal256(value: &I256) -> Option<String> {
+    u256_to_decimal256(&value.unsigned_abs())?;
+    Some(value.to_string())
+}
+
+/// integers are stored unscaled, so a scale of 18 represents token amounts in whole tokens
+fn decimal128_series(
+    name: &str,
+    values: Vec<Option<i128>>,
+    scale: u32,
+) -> Result<Series, CollectError> {
+    Int128Chunked::from_iter_options(name, values.into_iter())
+        .into_decimal(Some(DECIMAL128_PRECISION), scale as usize)
+        .map(|values| values.into_series())
+        .map_err(|e| {
+            CollectError::CollectError(format!("could not create decimal column {}: {}", name, e))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decimal128_bounds() {
+        let max = U256::exp10(DECIMAL128_PRECISION) - 1;
+        assert_eq!(u256_to_decimal128(&max), Some(10i128.pow(38) - 1));
+        assert_eq!(u256_to_decimal128(&(max + 1)), None);
+        assert_eq!(i256_to_decimal128(&I256::from(-5)), Some(-5));
+        assert_eq!(i256_to_decimal128(&I256::MIN), None);
+    }
+
+    #[test]
+    fn test_i256_decimal128_series() {
+        let values = vec![Some(I256::from(-1500)), None, Some(I256::from(2))];
+        let series = values
+            .to_u256_series("amount".to_string(), U256Type::Decimal128(3), &ColumnEncoding::Binary)
+            .unwrap();
+        assert_eq!(series.name(), "amount_d128");
+        assert_eq!(series.dtype(), &DataType::Decimal(Some(DECIMAL128_PRECISION), Some(3)));
+        assert_eq!(series.null_count(), 1);
+    }
+
+    #[test]
+    fn test_negative_i256_integer_series() {
+        // negative and oversized values do not fit unsigned integers, so they are null
+        let values = vec![I256::from(-1), I256::from(7), I256::from(u64::MAX) + I256::from(1)];
+        let series =
+            values.to_u256_series("amount".to_string(), U256Type::U64, &ColumnEncoding::Binary);
+        let series = series.unwrap();
+        let values: Vec<Option<u64>> = series.u64().unwrap().into_iter().collect();
+        assert_eq!(values, vec![None, Some(7), None]);
+
+        let values = vec![Some(I256::from(-1)), None, Some(I256::from(7))];
+        let series =
+            values.to_u256_series("amount".to_string(), U256Type::U32, &ColumnEncoding::Binary);
+        let series = series.unwrap();
+        let values: Vec<Option<u32>> = series.u32().unwrap().into_iter().collect();
+        assert_eq!(values, vec![None, None, Some(7)]);
+    }
+}
@@ -41,6 +41,13 @@ macro_rules! define_datatypes {
                 }
             }
 
+            /// columns that uniquely identify each row of datatype
+            pub fn primary_key(&self) -> Option<Vec<&'static str>> {
+                match *self {
+                    $(Datatype::$datatype => $datatype::primary_key(),)*
+                }
+            }
+
             /// default columns of datatype
             pub fn default_columns(&self) -> Vec<&'static str> {
                 match *self {
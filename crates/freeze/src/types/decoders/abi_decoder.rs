@@ -0,0 +1,455 @@
+use crate::{err, CollectError, ColumnType, LogDecoder, ParseError, Table};
+use ethers::prelude::*;
+use ethers_core::abi::{Abi, RawLog, Token};
+use indexmap::IndexMap;
+use polars::prelude::*;
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+/// raw columns of logs that are needed to decode them
+const RAW_COLUMNS: [&str; 5] = ["topic0", "topic1", "topic2", "topic3", "data"];
+
+/// topic0 of event and its number of indexed params
+///
+/// events like the ERC20 and ERC721 `Transfer` share a topic0 but not their indexed params
+pub type EventKey = (H256, usize);
+
+/// rows of logs matched to an event and their decoded params, keyed by event
+type DecodedLogs = IndexMap<EventKey, (Vec<IdxSize>, IndexMap<String, Vec<Token>>)>;
+
+/// decoder of logs against the events of contract abis, with one output table per event
+#[derive(Clone, Debug, PartialEq)]
+pub struct AbiDecoder {
+    /// events, keyed by topic0 and number of indexed params
+    pub events: IndexMap<EventKey, AbiEvent>,
+}
+
+/// event of an abi that is decoded into its own table
+#[derive(Clone, Debug, PartialEq)]
+pub struct AbiEvent {
+    /// name of decoded table, such as `logs_transfer`
+    pub table_name: String,
+    /// decoder of event
+    pub decoder: LogDecoder,
+    /// contracts whose logs are decoded, or None to decode logs of any contract
+    pub addresses: Option<HashSet<H160>>,
+}
+
+impl AbiDecoder {
+    /// load events of abi json file, or of a directory of abi files named by contract address
+    ///
+    /// abi files can be plain abis or compiler artifacts with an `abi` field
+    pub fn from_path(path: &Path) -> Result<AbiDecoder, ParseError> {
+        let mut abis = Vec::new();
+        if path.is_dir() {
+            let entries = std::fs::read_dir(path)
+                .map_err(|_| ParseError::ParseError("could not read abi directory".to_string()))?;
+            let mut paths: Vec<_> =
+                entries.filter_map(|entry| entry.ok()).map(|e| e.path()).collect();
+            paths.sort();
+            for path in paths.into_iter() {
+                if path.extension().and_then(|ex| ex.to_str()) != Some("json") {
+                    continue
+                }
+                let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default();
+                let address = stem.parse::<H160>().map_err(|_| {
+                    ParseError::ParseError(format!("abi file name is not an address: {}", stem))
+                })?;
+                abis.push((Some(address), read_abi(&path)?));
+            }
+        } else {
+            abis.push((None, read_abi(path)?));
+        }
+        AbiDecoder::new(abis)
+    }
+
+    /// create decoder from abis, each optionally restricted to the logs of a contract
+    pub fn new(abis: Vec<(Option<H160>, Abi)>) -> Result<AbiDecoder, ParseError> {
+        let mut events: IndexMap<EventKey, (abi::Event, Option<HashSet<H160>>)> = IndexMap::new();
+        for (address, abi) in abis.into_iter() {
+            for event in abi.events().filter(|event| !event.anonymous) {
+                let addresses = address.map(|address| HashSet::from([address]));
+                let n_indexed = event.inputs.iter().filter(|input| input.indexed).count();
+                let key = (event.signature(), n_indexed);
+                match events.get_mut(&key) {
+                    Some((_, existing)) => match (existing.as_mut(), addresses) {
+                        (Some(existing), Some(addresses)) => existing.extend(addresses),
+                        _ => *existing = None,
+                    },
+                    None => {
+                        let mut event = event.clone();
+                        for (i, input) in event.inputs.iter_mut().enumerate() {
+                            if input.name.is_empty() {
+                                input.name = format!("arg{}", i);
+                            }
+                        }
+                        events.insert(key, (event, addresses));
+                    }
+                }
+            }
+        }
+        if events.is_empty() {
+            return Err(ParseError::ParseError("abi has no events to decode".to_string()))
+        }
+
+        // events that share a name get the start of their topic0 appended, and events that also
+        // share a topic0 get their number of indexed params appended
+        let mut name_counts: HashMap<String, usize> = HashMap::new();
+        let mut topic0_counts: HashMap<H256, usize> = HashMap::new();
+        for ((topic0, _), (event, _)) in events.iter() {
+            *name_counts.entry(event_name(event)).or_default() += 1;
+            *topic0_counts.entry(*topic0).or_default() += 1;
+        }
+        let events = events
+            .into_iter()
+            .map(|((topic0, n_indexed), (event, addresses))| {
+                let name = event_name(&event);
+                let prefix = prefix_hex::encode(topic0.as_bytes()[..4].to_vec());
+                let prefix = prefix.trim_start_matches("0x");
+                let table_name = match (name_counts.get(&name), topic0_counts.get(&topic0)) {
+                    (_, Some(count)) if *count > 1 => {
+                        format!("logs_{}_{}_{}_indexed", name, prefix, n_indexed)
+                    }
+                    (Some(count), _) if *count > 1 => format!("logs_{}_{}", name, prefix),
+                    _ => format!("logs_{}", name),
+                };
+                let decoder = LogDecoder { raw: event_signature(&event), event };
+                ((topic0, n_indexed), AbiEvent { table_name, decoder, addresses })
+            })
+            .collect();
+        Ok(AbiDecoder { events })
+    }
+
+    /// schema of decoded table of event, containing the logs columns that identify each log
+    pub fn event_table(&self, event: &AbiEvent, logs_table: &Table) -> Table {
+        let mut columns: IndexMap<String, ColumnType> = IndexMap::new();
+        for column in logs_table.columns() {
+            if !RAW_COLUMNS.contains(&column) && column != "n_data_bytes" {
+                if let Some(column_type) = logs_table.column_type(column) {
+                    columns.insert(column.to_string(), column_type);
+                }
+            }
+        }
+        Table {
+            columns,
+            log_decoder: Some(event.decoder.clone()),
+            abi_decoder: None,
+            function_decoder: None,
+            ..logs_table.clone()
+        }
+    }
+
+    /// decode logs dataframe into a dataframe per event
+    ///
+    /// events without logs get empty dataframes, so that every event has a file for each chunk
+    pub fn decode_logs<'a>(
+        &'a self,
+        logs: &DataFrame,
+        logs_table: &Table,
+    ) -> Result<Vec<(&'a AbiEvent, DataFrame)>, CollectError> {
+        let topics = [
+            binary_values(logs, "topic0")?,
+            binary_values(logs, "topic1")?,
+            binary_values(logs, "topic2")?,
+            binary_values(logs, "topic3")?,
+        ];
+        let data = binary_values(logs, "data")?;
+        let restricted = self.events.values().any(|event| event.addresses.is_some());
+        let addresses = match restricted {
+            true => Some(binary_values(logs, "address")?),
+            false => None,
+        };
+
+        // match each log to its event by topic0 and number of indexed params
+        let mut decoded: DecodedLogs = IndexMap::new();
+        let topic0s: HashSet<H256> = self.events.keys().map(|(topic0, _)| *topic0).collect();
+        let mut n_undecoded = 0;
+        for row in 0..logs.height() {
+            let log_topics: Vec<H256> = topics
+                .iter()
+                .map_while(|topic| topic[row].as_ref())
+                .filter(|topic| topic.len() == 32)
+                .map(|topic| H256::from_slice(topic))
+                .collect();
+            let topic0 = match log_topics.first() {
+                Some(topic0) if topic0s.contains(topic0) => *topic0,
+                _ => continue,
+            };
+            let key = (topic0, log_topics.len() - 1);
+            let event = match self.events.get(&key) {
+                Some(event) => event,
+                None => {
+                    n_undecoded += 1;
+                    continue
+                }
+            };
+            if let (Some(allowed), Some(addresses)) = (&event.addresses, &addresses) {
+                match &addresses[row] {
+                    Some(address) if address.len() == 20 => {
+                        if !allowed.contains(&H160::from_slice(address)) {
+                            continue
+                        }
+                    }
+                    _ => continue,
+                }
+            }
+            let raw_log =
+                RawLog { topics: log_topics, data: data[row].clone().unwrap_or_default() };
+            match event.decoder.event.parse_log(raw_log) {
+                Ok(log) => {
+                    let (rows, params) = decoded.entry(key).or_default();
+                    rows.push(row as IdxSize);
+                    for param in log.params.into_iter() {
+                        params.entry(param.name).or_default().push(param.value);
+                    }
+                }
+                Err(_) => n_undecoded += 1,
+            }
+        }
+        if n_undecoded > 0 {
+            let message = "logs matched the topic0 of an abi event but could not be decoded";
+            eprintln!("warning: {} {}", n_undecoded, message);
+        }
+
+        // build table of each event from identifying logs columns and decoded params
+        let mut dfs = Vec::new();
+        for (key, event) in self.events.iter() {
+            let (rows, params) = decoded.swap_remove(key).unwrap_or_else(|| {
+                let params = event.decoder.field_names().into_iter().map(|name| (name, vec![]));
+                (vec![], params.collect())
+            });
+            let table = self.event_table(event, logs_table);
+            let columns: Vec<&str> =
+                table.columns().into_iter().filter(|c| logs.column(c).is_ok()).collect();
+            let mut df = logs.select(columns)?.take(&IdxCa::from_vec("", rows.clone()))?;
+            for (name, tokens) in params.into_iter() {
+                let series = event.decoder.make_series(
+                    name,
+                    tokens,
+                    rows.len(),
+                    &table.u256_types,
+                    &table.binary_type,
+                )?;
+                df.hstack_mut(&series)?;
+            }
+            dfs.push((event, df));
+        }
+        Ok(dfs)
+    }
+}
+
+pub(crate) fn read_abi(path: &Path) -> Result<Abi, ParseError> {
+    let contents = std::fs::read_to_string(path).map_err(|_| {
+        ParseError::ParseError(format!("could not read abi file {}", path.to_string_lossy()))
+    })?;
+    let invalid =
+        |_| ParseError::ParseError(format!("invalid abi file {}", path.to_string_lossy()));
+    let value: serde_json::Value = serde_json::from_str(&contents).map_err(invalid)?;
+    let value = match value {
+        serde_json::Value::Object(mut artifact) if artifact.contains_key("abi") => {
+            artifact.remove("abi").unwrap_or_default()
+        }
+        value => value,
+    };
+    serde_json::from_value(value).map_err(invalid)
+}
+
+fn event_name(event: &abi::Event) -> String {
+    heck::AsSnakeCase(&event.name).to_string()
+}
+
+/// human readable signature of event, ex: event Transfer(address indexed from, ...)
+fn event_signature(event: &abi::Event) -> String {
+    let inputs: Vec<String> = event
+        .inputs
+        .iter()
+        .map(|input| match input.indexed {
+            true => format!("{} indexed {}", input.kind, input.name),
+            false => format!("{} {}", input.kind, input.name),
+        })
+        .collect();
+    format!("event {}({})", event.name, inputs.join(", "))
+}
+
+/// values of binary column, which is hex encoded in text output formats
+pub(crate) fn binary_values(
+    df: &DataFrame,
+    column: &str,
+) -> Result<Vec<Option<Vec<u8>>>, CollectError> {
+    let series =
+        df.column(column).map_err(|_| err(&format!("decoding requires the {} column", column)))?;
+    match series.dtype() {
+        DataType::Binary => {
+            Ok(series.binary()?.into_iter().map(|v| v.map(|v| v.to_vec())).collect())
+        }
+        DataType::String => series
+            .str()?
+            .into_iter()
+            .map(|v| {
+                v.map(|v| {
+                    prefix_hex::decode::<Vec<u8>>(v)
+                        .map_err(|_| err("invalid hex in binary column"))
+                })
+                .transpose()
+            })
+            .collect(),
+        _ => Err(err(&format!("could not read binary values of {} column", column))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ABI: &str = r#"[
+        {"type": "event", "name": "Transfer", "anonymous": false, "inputs": [
+            {"name": "from", "type": "address", "indexed": true},
+            {"name": "to", "type": "address", "indexed": true},
+            {"name": "value", "type": "uint256", "indexed": false}
+        ]},
+        {"type": "event", "name": "Approval", "anonymous": false, "inputs": [
+            {"name": "owner", "type": "address", "indexed": true},
+            {"name": "", "type": "address", "indexed": true},
+            {"name": "value", "type": "uint256", "indexed": false}
+        ]}
+    ]"#;
+
+    #[test]
+    fn test_events_by_topic0() {
+        let abi: Abi = serde_json::from_str(ABI).unwrap();
+        let decoder = AbiDecoder::new(vec![(None, abi)]).unwrap();
+        let names: Vec<&str> =
+            decoder.events.values().map(|event| event.table_name.as_str()).collect();
+        // abis list their events by name
+        assert_eq!(names, vec!["logs_approval", "logs_transfer"]);
+
+        let transfer: H256 =
+            "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef".parse().unwrap();
+        assert_eq!(decoder.events[&(transfer, 2)].table_name, "logs_transfer");
+
+        let approval = &decoder.events[0];
+        assert_eq!(approval.decoder.field_names(), vec!["owner", "arg1", "value"]);
+    }
+
+    #[test]
+    fn test_decode_logs() {
+        let abi: Abi = serde_json::from_str(ABI).unwrap();
+        let decoder = AbiDecoder::new(vec![(None, abi)]).unwrap();
+        let table = crate::Datatype::Logs
+            .table_schema(
+                &[crate::U256Type::String],
+                &crate::ColumnEncoding::Binary,
+                &None,
+                &None,
+                &None,
+                None,
+                None,
+            )
+            .unwrap();
+
+        let topic = |byte: u8| Some(vec![byte; 32]);
+        let transfer = decoder.events.keys().nth(1).unwrap().0.as_bytes().to_vec();
+        let value = {
+            let mut value = vec![0u8; 32];
+            value[31] = 7;
+            value
+        };
+        let logs = DataFrame::new(vec![
+            Series::new("block_number", vec![1u32, 1]),
+            Series::new("log_index", vec![0u32, 1]),
+            Series::new("topic0", vec![Some(transfer), topic(9)]),
+            Series::new("topic1", vec![topic(1), None]),
+            Series::new("topic2", vec![topic(2), None]),
+            Series::new("topic3", vec![None::<Vec<u8>>, None]),
+            Series::new("data", vec![value, vec![]]),
+        ])
+        .unwrap();
+
+        let dfs = decoder.decode_logs(&logs, &table).unwrap();
+        assert_eq!(dfs.len(), 2);
+        let (event, df) = &dfs[1];
+        assert_eq!(event.table_name, "logs_transfer");
+        assert_eq!(df.height(), 1);
+        assert_eq!(
+            df.get_column_names(),
+            vec!["block_number", "log_index", "event__from", "event__to", "event__value_string"]
+        );
+        let values = df.column("event__value_string").unwrap().str().unwrap().get(0);
+        assert_eq!(values, Some("7"));
+
+        // events without logs have empty tables with typed columns
+        let (event, df) = &dfs[0];
+        assert_eq!(event.table_name, "logs_approval");
+        assert_eq!(df.height(), 0);
+        assert_eq!(
+            df.get_column_names(),
+            vec!["block_number", "log_index", "event__owner", "event__arg1", "event__value_string"]
+        );
+        assert_eq!(df.column("event__value_string").unwrap().dtype(), &DataType::String);
+    }
+
+    #[test]
+    fn test_events_sharing_topic0() {
+        // erc721 transfers index their token id instead of holding a value in data
+        const ERC721_ABI: &str = r#"[
+            {"type": "event", "name": "Transfer", "anonymous": false, "inputs": [
+                {"name": "from", "type": "address", "indexed": true},
+                {"name": "to", "type": "address", "indexed": true},
+                {"name": "tokenId", "type": "uint256", "indexed": true}
+            ]}
+        ]"#;
+        let erc20: H160 = H160::from_low_u64_be(20);
+        let erc721: H160 = H160::from_low_u64_be(721);
+        let decoder = AbiDecoder::new(vec![
+            (Some(erc20), serde_json::from_str(ABI).unwrap()),
+            (Some(erc721), serde_json::from_str(ERC721_ABI).unwrap()),
+        ])
+        .unwrap();
+        let names: Vec<&str> =
+            decoder.events.values().map(|event| event.table_name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "logs_approval",
+                "logs_transfer_ddf252ad_2_indexed",
+                "logs_transfer_ddf252ad_3_indexed",
+            ]
+        );
+
+        let table = crate::Datatype::Logs
+            .table_schema(
+                &[crate::U256Type::String],
+                &crate::ColumnEncoding::Binary,
+                &None,
+                &None,
+                &None,
+                None,
+                None,
+            )
+            .unwrap();
+        let topic = |byte: u8| Some(vec![byte; 32]);
+        let transfer = decoder.events.keys().nth(1).unwrap().0.as_bytes().to_vec();
+        let logs = DataFrame::new(vec![
+            Series::new("block_number", vec![1u32, 1]),
+            Series::new("log_index", vec![0u32, 1]),
+            Series::new("address", vec![erc20.as_bytes().to_vec(), erc721.as_bytes().to_vec()]),
+            Series::new("topic0", vec![Some(transfer.clone()), Some(transfer)]),
+            Series::new("topic1", vec![topic(1), topic(1)]),
+            Series::new("topic2", vec![topic(2), topic(2)]),
+            Series::new("topic3", vec![None, topic(3)]),
+            Series::new("data", vec![vec![0u8; 32], vec![]]),
+        ])
+        .unwrap();
+
+        // each log is decoded by the event with its number of indexed params
+        let dfs = decoder.decode_logs(&logs, &table).unwrap();
+        let heights: Vec<usize> = dfs.iter().map(|(_, df)| df.height()).collect();
+        assert_eq!(heights, vec![0, 1, 1]);
+        let (_, erc721_transfers) = &dfs[2];
+        let log_index = erc721_transfers.column("log_index").unwrap().u32().unwrap().get(0);
+        assert_eq!(log_index, Some(1));
+        assert!(erc721_transfers.column("event__tokenId_string").is_ok());
+    }
+}
@@ -0,0 +1,279 @@
+use super::{abi_decoder, log_decoder};
+use crate::{CollectError, ParseError, Table};
+use ethers::prelude::*;
+use ethers_core::abi::{HumanReadableParser, Param, Token};
+use indexmap::IndexMap;
+use polars::prelude::*;
+use std::path::Path;
+
+/// columns of call inputs, in the order they are looked up
+const INPUT_COLUMNS: [&str; 3] = ["input", "action_input", "call_data"];
+
+/// columns of call outputs, in the order they are looked up
+const OUTPUT_COLUMNS: [&str; 3] = ["output", "result_output", "output_data"];
+
+/// decoder of call inputs and outputs by function signature
+#[derive(Clone, Debug, PartialEq)]
+pub struct FunctionDecoder {
+    /// functions, keyed by 4 byte selector
+    pub functions: IndexMap<Selector, abi::Function>,
+}
+
+impl FunctionDecoder {
+    /// create decoder from function signatures, such as `transfer(address,uint256)`, or from
+    /// abi json files containing the functions to decode
+    pub fn new(signatures: &[String]) -> Result<FunctionDecoder, ParseError> {
+        let mut functions = IndexMap::new();
+        for signature in signatures.iter() {
+            let parsed = if signature.ends_with(".json") {
+                abi_decoder::read_abi(Path::new(signature))?.functions().cloned().collect()
+            } else {
+                vec![parse_function(signature)?]
+            };
+            for mut function in parsed.into_iter() {
+                name_params(&mut function.inputs);
+                name_params(&mut function.outputs);
+                functions.insert(function.short_signature(), function);
+            }
+        }
+        if functions.is_empty() {
+            return Err(ParseError::ParseError("no functions to decode".to_string()))
+        }
+        Ok(FunctionDecoder { functions })
+    }
+
+    /// prefix of decoded columns of function
+    ///
+    /// columns are named `input__<param>` and `output__<param>` when decoding a single function,
+    /// otherwise the function name is included, as in `input__transfer__<param>`
+    fn column_prefix(&self, function: &abi::Function, kind: &str) -> String {
+        if self.functions.len() == 1 {
+            return kind.to_string()
+        }
+        let name = heck::AsSnakeCase(&function.name).to_string();
+        let overloaded = self.functions.values().filter(|f| f.name == function.name).count() > 1;
+        match overloaded {
+            true => {
+                let selector = prefix_hex::encode(function.short_signature().to_vec());
+                format!("{}__{}_{}", kind, name, selector.trim_start_matches("0x"))
+            }
+            false => format!("{}__{}", kind, name),
+        }
+    }
+
+    /// decode input and output columns of calls into columns of function params
+    ///
+    /// rows whose selector does not match a function, or whose data cannot be decoded, are null
+    pub fn decode_calls(
+        &self,
+        mut df: DataFrame,
+        table: &Table,
+    ) -> Result<DataFrame, CollectError> {
+        let input_column = INPUT_COLUMNS.iter().find(|column| df.column(column).is_ok());
+        let input_column = match input_column {
+            Some(input_column) => input_column,
+            None => return Ok(df),
+        };
+        let inputs = abi_decoder::binary_values(&df, input_column)?;
+        let outputs = match OUTPUT_COLUMNS.iter().find(|column| df.column(column).is_ok()) {
+            Some(output_column) => Some(abi_decoder::binary_values(&df, output_column)?),
+            None => None,
+        };
+
+        // decode rows of each function
+        let mut decoded: IndexMap<Selector, DecodedCalls> = self
+            .functions
+            .keys()
+            .map(|selector| (*selector, DecodedCalls::new(df.height())))
+            .collect();
+        for (row, input) in inputs.iter().enumerate() {
+            let input = match input {
+                Some(input) if input.len() >= 4 => input,
+                _ => continue,
+            };
+            let selector: Selector = [input[0], input[1], input[2], input[3]];
+            let (function, calls) =
+                match (self.functions.get(&selector), decoded.get_mut(&selector)) {
+                    (Some(function), Some(calls)) => (function, calls),
+                    _ => continue,
+                };
+            if let Ok(tokens) = function.decode_input(&input[4..]) {
+                calls.indices[row] = Some(calls.inputs.len() as IdxSize);
+                calls.inputs.push(tokens);
+            }
+            let output = outputs.as_ref().and_then(|outputs| outputs[row].as_ref());
+            if let Some(Ok(tokens)) = output.map(|output| function.decode_output(output)) {
+                calls.output_indices[row] = Some(calls.outputs.len() as IdxSize);
+                calls.outputs.push(tokens);
+            }
+        }
+
+        // place decoded params in the rows of their calls
+        for (selector, calls) in decoded.into_iter() {
+            let function = &self.functions[&selector];
+            let prefix = self.column_prefix(function, "input");
+            let input_columns = params_to_series(&prefix, &function.inputs, calls.inputs, table)?;
+            let indices = IdxCa::from_iter_options("", calls.indices.into_iter());
+            for series in input_columns.iter() {
+                df.with_column(series.take(&indices)?)?;
+            }
+            if outputs.is_some() {
+                let prefix = self.column_prefix(function, "output");
+                let output_columns =
+                    params_to_series(&prefix, &function.outputs, calls.outputs, table)?;
+                let indices = IdxCa::from_iter_options("", calls.output_indices.into_iter());
+                for series in output_columns.iter() {
+                    df.with_column(series.take(&indices)?)?;
+                }
+            }
+        }
+        Ok(df)
+    }
+}
+
+/// decoded params of the calls of a function
+struct DecodedCalls {
+    /// position of decoded inputs of each row
+    indices: Vec<Option<IdxSize>>,
+    /// decoded inputs
+    inputs: Vec<Vec<Token>>,
+    /// position of decoded outputs of each row
+    output_indices: Vec<Option<IdxSize>>,
+    /// decoded outputs
+    outputs: Vec<Vec<Token>>,
+}
+
+impl DecodedCalls {
+    fn new(n_rows: usize) -> DecodedCalls {
+        DecodedCalls {
+            indices: vec![None; n_rows],
+            inputs: Vec::new(),
+            output_indices: vec![None; n_rows],
+            outputs: Vec::new(),
+        }
+    }
+}
+
+/// parse function signature, such as `transfer(address,uint256)`
+///
+/// return types can be given as `function f() returns (uint256)` or as `f()(uint256)`
+pub fn parse_function(signature: &str) -> Result<abi::Function, ParseError> {
+    let signature = signature.trim();
+    let raw = signature.strip_prefix("function ").unwrap_or(signature);
+    let raw = match split_returns(raw) {
+        Some((head, returns)) => format!("function {} returns {}", head, returns),
+        None => format!("function {}", raw),
+    };
+    HumanReadableParser::parse_function(&raw)
+        .map_err(|_| ParseError::ParseError(format!("invalid function signature: {}", signature)))
+}
+
+/// split `f(inputs)(outputs)` into `f(inputs)` and `(outputs)`
+fn split_returns(signature: &str) -> Option<(&str, &str)> {
+    let mut depth = 0;
+    for (i, c) in signature.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    let (head, rest) = signature.split_at(i + 1);
+                    return match rest.trim_start().starts_with('(') {
+                        true => Some((head, rest.trim_start())),
+                        false => None,
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// unnamed params are named by their position
+fn name_params(params: &mut [Param]) {
+    for (i, param) in params.iter_mut().enumerate() {
+        if param.name.is_empty() {
+            param.name = format!("arg{}", i);
+        }
+    }
+}
+
+fn params_to_series(
+    prefix: &str,
+    params: &[Param],
+    rows: Vec<Vec<Token>>,
+    table: &Table,
+) -> Result<Vec<Series>, CollectError> {
+    let mut columns: Vec<Vec<Token>> = vec![Vec::with_capacity(rows.len()); params.len()];
+    for tokens in rows.into_iter() {
+        for (column, token) in columns.iter_mut().zip(tokens) {
+            column.push(token)
+        }
+    }
+    let mut series = Vec::new();
+    for (param, tokens) in params.iter().zip(columns) {
+        let name = format!("{}__{}", prefix, param.name);
+        series.extend(log_decoder::tokens_to_series(
+            &name,
+            &param.kind,
+            tokens,
+            &table.u256_types,
+            &table.binary_type,
+        )?);
+    }
+    Ok(series)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_function() {
+        let function = parse_function("getReserves()(uint112,uint112,uint32)").unwrap();
+        assert_eq!(function.name, "getReserves");
+        assert_eq!(function.outputs.len(), 3);
+        assert_eq!(function.short_signature(), [0x09, 0x02, 0xf1, 0xac]);
+
+        let function = parse_function("function balanceOf(address) returns (uint256)").unwrap();
+        assert_eq!(function.inputs.len(), 1);
+        assert_eq!(function.outputs.len(), 1);
+    }
+
+    #[test]
+    fn test_decode_calls() {
+        let signature = "transfer(address to, uint256 amount) returns (bool)".to_string();
+        let decoder = FunctionDecoder::new(&[signature]).unwrap();
+        let table = crate::Datatype::Traces
+            .table_schema(
+                &[crate::U256Type::String],
+                &crate::ColumnEncoding::Binary,
+                &None,
+                &None,
+                &None,
+                None,
+                None,
+            )
+            .unwrap();
+
+        let function = decoder.functions.values().next().unwrap();
+        let call = function
+            .encode_input(&[Token::Address(H160::repeat_byte(1)), Token::Uint(5.into())])
+            .unwrap();
+        let success = ethers_core::abi::encode(&[Token::Bool(true)]);
+        let df = DataFrame::new(vec![
+            Series::new("action_input", vec![Some(call), Some(vec![1, 2, 3, 4]), None]),
+            Series::new("result_output", vec![Some(success), None, None]),
+        ])
+        .unwrap();
+
+        let df = decoder.decode_calls(df, &table).unwrap();
+        let amounts = df.column("input__amount_string").unwrap().str().unwrap();
+        assert_eq!(amounts.into_iter().collect::<Vec<_>>(), vec![Some("5"), None, None]);
+        let to = df.column("input__to").unwrap();
+        assert_eq!(to.null_count(), 2);
+        let outputs = df.column("output__arg0").unwrap().bool().unwrap();
+        assert_eq!(outputs.into_iter().collect::<Vec<_>>(), vec![Some(true), None, None]);
+    }
+}
@@ -1,7 +1,7 @@
-use crate::{err, CollectError, ColumnEncoding, ToU256Series, U256Type};
+use crate::{err, CollectError, ColumnEncoding, ToU256Series, ToVecHex, U256Type};
 use ethers::prelude::*;
 use ethers_core::abi::{AbiEncode, EventParam, HumanReadableParser, ParamType, RawLog, Token};
-use polars::prelude::*;
+use polars::{chunked_array::builder::get_list_builder, prelude::*};
 use std::collections::HashSet;
 
 /// container for log decoding context
@@ -77,7 +77,6 @@ impl LogDecoder {
         let mut hexes: Vec<String> = vec![];
         let mut bools: Vec<bool> = vec![];
         let mut strings: Vec<String> = vec![];
-        // TODO: support array & tuple types
 
         let param = self
             .event
@@ -88,6 +87,21 @@ impl LogDecoder {
             .collect::<Vec<EventParam>>();
         let param = param.first();
 
+        // nested and empty columns are built from the abi type of the param
+        if let Some(kind) = param.map(column_kind) {
+            let nested = matches!(
+                kind,
+                ParamType::Array(_) | ParamType::FixedArray(..) | ParamType::Tuple(_)
+            );
+            if nested || (data.is_empty() && chunk_len == 0) {
+                if data.len() != chunk_len {
+                    return Err(err(&format!("could not parse column {}, mixed type", name)))
+                }
+                let name = format!("event__{}", name);
+                return tokens_to_series(&name, &kind, data, u256_types, column_encoding)
+            }
+        }
+
         for token in data {
             match token {
                 Token::Address(a) => match column_encoding {
@@ -201,3 +215,196 @@ impl LogDecoder {
         }
     }
 }
+
+/// abi type of the decoded column of a param
+///
+/// indexed params of dynamic types are stored in topics as their hash
+fn column_kind(param: &EventParam) -> ParamType {
+    match &param.kind {
+        ParamType::String |
+        ParamType::Bytes |
+        ParamType::Array(_) |
+        ParamType::FixedArray(..) |
+        ParamType::Tuple(_)
+            if param.indexed =>
+        {
+            ParamType::FixedBytes(32)
+        }
+        kind => kind.clone(),
+    }
+}
+
+/// build columns of tokens of an abi type, arrays become List columns and tuples become Struct
+/// columns with fields `field_0`, `field_1`, ...
+///
+/// 256 bit integers create a column (or struct field) for each u256 type
+pub(crate) fn tokens_to_series(
+    name: &str,
+    kind: &ParamType,
+    tokens: Vec<Token>,
+    u256_types: &[U256Type],
+    column_encoding: &ColumnEncoding,
+) -> Result<Vec<Series>, CollectError> {
+    let mismatch = || err(&format!("could not parse column {}, mismatched type", name));
+    match kind {
+        ParamType::Array(inner) | ParamType::FixedArray(inner, _) => {
+            let mut lengths = Vec::with_capacity(tokens.len());
+            let mut values = Vec::new();
+            for token in tokens.into_iter() {
+                match token {
+                    Token::Array(items) | Token::FixedArray(items) => {
+                        lengths.push(items.len());
+                        values.extend(items);
+                    }
+                    _ => return Err(mismatch()),
+                }
+            }
+            tokens_to_series(name, inner, values, u256_types, column_encoding)?
+                .iter()
+                .map(|series| to_list_series(series, &lengths))
+                .collect()
+        }
+        ParamType::Tuple(components) => {
+            let mut fields = vec![Vec::with_capacity(tokens.len()); components.len()];
+            for token in tokens.into_iter() {
+                match token {
+                    Token::Tuple(items) if items.len() == components.len() => {
+                        for (field, item) in fields.iter_mut().zip(items) {
+                            field.push(item)
+                        }
+                    }
+                    _ => return Err(mismatch()),
+                }
+            }
+            let mut columns = Vec::new();
+            for (i, (kind, tokens)) in components.iter().zip(fields).enumerate() {
+                let field_name = format!("field_{}", i);
+                columns.extend(tokens_to_series(
+                    &field_name,
+                    kind,
+                    tokens,
+                    u256_types,
+                    column_encoding,
+                )?);
+            }
+            Ok(vec![StructChunked::new(name, &columns)?.into_series()])
+        }
+        ParamType::Uint(bits) if *bits > 64 => {
+            let values: Option<Vec<U256>> = tokens.into_iter().map(|t| t.into_uint()).collect();
+            let values = values.ok_or_else(mismatch)?;
+            u256_types
+                .iter()
+                .map(|u256_type| {
+                    values.to_u256_series(name.to_string(), u256_type.clone(), column_encoding)
+                })
+                .collect()
+        }
+        ParamType::Int(bits) if *bits > 64 => {
+            let values: Option<Vec<I256>> =
+                tokens.into_iter().map(|t| t.into_int().map(I256::from_raw)).collect();
+            let values = values.ok_or_else(mismatch)?;
+            u256_types
+                .iter()
+                .map(|u256_type| {
+                    values.to_u256_series(name.to_string(), u256_type.clone(), column_encoding)
+                })
+                .collect()
+        }
+        ParamType::Uint(_) => {
+            let values: Option<Vec<u64>> =
+                tokens.into_iter().map(|t| t.into_uint().map(|v| v.as_u64())).collect();
+            Ok(vec![Series::new(name, values.ok_or_else(mismatch)?)])
+        }
+        ParamType::Int(_) => {
+            let values: Option<Vec<i64>> = tokens
+                .into_iter()
+                .map(|t| t.into_int().map(|v| I256::from_raw(v).as_i64()))
+                .collect();
+            Ok(vec![Series::new(name, values.ok_or_else(mismatch)?)])
+        }
+        ParamType::Bool => {
+            let values: Option<Vec<bool>> = tokens.into_iter().map(|t| t.into_bool()).collect();
+            Ok(vec![Series::new(name, values.ok_or_else(mismatch)?)])
+        }
+        ParamType::String => {
+            let values: Option<Vec<String>> = tokens.into_iter().map(|t| t.into_string()).collect();
+            Ok(vec![Series::new(name, values.ok_or_else(mismatch)?)])
+        }
+        ParamType::Address | ParamType::Bytes | ParamType::FixedBytes(_) => {
+            let values: Option<Vec<Vec<u8>>> = tokens
+                .into_iter()
+                .map(|token| match token {
+                    Token::Address(address) => Some(address.as_bytes().to_vec()),
+                    Token::Bytes(bytes) | Token::FixedBytes(bytes) => Some(bytes),
+                    _ => None,
+                })
+                .collect();
+            let values = values.ok_or_else(mismatch)?;
+            match column_encoding {
+                ColumnEncoding::Binary => Ok(vec![Series::new(name, values)]),
+                ColumnEncoding::Hex => Ok(vec![Series::new(name, values.to_vec_hex())]),
+            }
+        }
+    }
+}
+
+/// split values into a List column with a list of the given length for each row
+fn to_list_series(values: &Series, lengths: &[usize]) -> Result<Series, CollectError> {
+    let mut builder = get_list_builder(values.dtype(), values.len(), lengths.len(), values.name())?;
+    let mut offset = 0;
+    for length in lengths.iter() {
+        builder.append_series(&values.slice(offset as i64, *length))?;
+        offset += length;
+    }
+    Ok(builder.finish().into_series())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nested_columns() {
+        let decoder = LogDecoder::new(
+            "event Swap(string indexed pool, uint256[] amounts, (address,uint8) side)".to_string(),
+        )
+        .unwrap();
+        let u256_types = [U256Type::String, U256Type::F64];
+
+        // indexed dynamic params are stored as hashes
+        let pool = vec![Token::FixedBytes(vec![1; 32])];
+        let pool =
+            decoder.make_series("pool".into(), pool, 1, &u256_types, &ColumnEncoding::Binary);
+        assert_eq!(pool.unwrap()[0].dtype(), &DataType::Binary);
+
+        let amounts = vec![
+            Token::Array(vec![Token::Uint(1.into()), Token::Uint(2.into())]),
+            Token::Array(vec![]),
+        ];
+        let amounts = decoder
+            .make_series("amounts".into(), amounts, 2, &u256_types, &ColumnEncoding::Binary)
+            .unwrap();
+        assert_eq!(amounts[0].name(), "event__amounts_string");
+        assert_eq!(amounts[0].dtype(), &DataType::List(Box::new(DataType::String)));
+        assert_eq!(amounts[1].dtype(), &DataType::List(Box::new(DataType::Float64)));
+        let lists = amounts[0].list().unwrap();
+        assert_eq!(lists.get_as_series(0).map(|list| list.len()), Some(2));
+        assert_eq!(lists.get_as_series(1).map(|list| list.len()), Some(0));
+
+        let side = vec![Token::Tuple(vec![Token::Address(H160::zero()), Token::Uint(3.into())]); 2];
+        let side =
+            decoder.make_series("side".into(), side, 2, &u256_types, &ColumnEncoding::Hex).unwrap();
+        let fields = side[0].struct_().unwrap().fields().to_vec();
+        assert_eq!(fields[0].name(), "field_0");
+        assert_eq!(fields[0].dtype(), &DataType::String);
+        assert_eq!(fields[1].dtype(), &DataType::UInt64);
+
+        // nested columns round trip through parquet
+        let mut df = DataFrame::new(amounts.into_iter().chain(side).collect()).unwrap();
+        let mut buffer = Vec::new();
+        ParquetWriter::new(&mut buffer).finish(&mut df).unwrap();
+        let read = ParquetReader::new(std::io::Cursor::new(buffer)).finish().unwrap();
+        assert_eq!(read.shape(), df.shape());
+        assert_eq!(read.dtypes(), df.dtypes());
+    }
+}
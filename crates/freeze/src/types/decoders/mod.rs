@@ -1,3 +1,15 @@
 /// log decoder
 pub mod log_decoder;
 pub use log_decoder::*;
+
+/// abi decoder
+pub mod abi_decoder;
+pub use abi_decoder::*;
+
+/// function decoder
+pub mod function_decoder;
+pub use function_decoder::*;
+
+/// revert decoder
+pub mod revert_decoder;
+pub use revert_decoder::*;
@@ -0,0 +1,114 @@
+use crate::CollectError;
+use ethers::{prelude::U256, providers::RpcError};
+use ethers_core::abi::{decode, ParamType, Token};
+
+/// selector of `Error(string)` reverts
+pub const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+
+/// selector of `Panic(uint256)` reverts
+pub const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+/// columns of decoded revert data
+pub const REVERT_COLUMNS: [&str; 5] =
+    ["revert_data", "revert_reason", "panic_code", "panic_reason", "error_selector"];
+
+/// decoded revert data of a failed call
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DecodedRevert {
+    /// message of `Error(string)` reverts
+    pub reason: Option<String>,
+    /// code of `Panic(uint256)` reverts
+    pub panic_code: Option<u64>,
+    /// meaning of panic code
+    pub panic_reason: Option<String>,
+    /// selector of custom error reverts
+    pub error_selector: Option<Vec<u8>>,
+}
+
+impl DecodedRevert {
+    /// decode revert data, which is empty for reverts without a payload
+    pub fn decode(data: &[u8]) -> DecodedRevert {
+        if data.len() < 4 {
+            return DecodedRevert::default()
+        }
+        let (selector, payload) = data.split_at(4);
+        if selector == ERROR_SELECTOR {
+            let reason = match decode(&[ParamType::String], payload).ok().and_then(|mut t| t.pop())
+            {
+                Some(Token::String(message)) => Some(message),
+                _ => None,
+            };
+            DecodedRevert { reason, ..Default::default() }
+        } else if selector == PANIC_SELECTOR {
+            let code = match decode(&[ParamType::Uint(256)], payload).ok().and_then(|mut t| t.pop())
+            {
+                Some(Token::Uint(code)) if code <= U256::from(u64::MAX) => Some(code.as_u64()),
+                _ => None,
+            };
+            let panic_reason = code.and_then(panic_reason).map(String::from);
+            DecodedRevert { panic_code: code, panic_reason, ..Default::default() }
+        } else {
+            DecodedRevert { error_selector: Some(selector.to_vec()), ..Default::default() }
+        }
+    }
+}
+
+/// meaning of solidity panic code
+pub fn panic_reason(code: u64) -> Option<&'static str> {
+    match code {
+        0x00 => Some("generic compiler panic"),
+        0x01 => Some("assertion failed"),
+        0x11 => Some("arithmetic overflow or underflow"),
+        0x12 => Some("division or modulo by zero"),
+        0x21 => Some("invalid enum value"),
+        0x22 => Some("invalid storage byte array encoding"),
+        0x31 => Some("pop on empty array"),
+        0x32 => Some("array index out of bounds"),
+        0x41 => Some("out of memory"),
+        0x51 => Some("call to uninitialized internal function"),
+        _ => None,
+    }
+}
+
+/// decode reason of revert data, from `Error(string)` messages or `Panic(uint256)` codes
+pub fn decode_revert_reason(data: &[u8]) -> Option<String> {
+    let revert = DecodedRevert::decode(data);
+    match (revert.reason, revert.panic_code, revert.panic_reason) {
+        (Some(reason), _, _) => Some(reason),
+        (None, Some(code), Some(panic_reason)) => {
+            Some(format!("panic {:#04x}: {}", code, panic_reason))
+        }
+        (None, Some(code), None) => Some(format!("panic {:#04x}", code)),
+        (None, None, _) => None,
+    }
+}
+
+/// revert data returned in the error of a reverted call
+pub(crate) fn revert_data(error: &CollectError) -> Option<Vec<u8>> {
+    match error {
+        CollectError::ProviderError(e) => Some(e.as_error_response()?.as_revert_data()?.to_vec()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_revert_reason() {
+        let mut data = ERROR_SELECTOR.to_vec();
+        data.extend(ethers_core::abi::encode(&[Token::String("not owner".to_string())]));
+        assert_eq!(decode_revert_reason(&data), Some("not owner".to_string()));
+
+        let mut data = PANIC_SELECTOR.to_vec();
+        data.extend(ethers_core::abi::encode(&[Token::Uint(U256::from(0x11))]));
+        let reason = "panic 0x11: arithmetic overflow or underflow".to_string();
+        assert_eq!(decode_revert_reason(&data), Some(reason));
+        assert_eq!(DecodedRevert::decode(&data).panic_code, Some(0x11));
+
+        let custom = [0xde, 0xad, 0xbe, 0xef, 0x00];
+        assert_eq!(decode_revert_reason(&custom), None);
+        assert_eq!(DecodedRevert::decode(&custom).error_selector, Some(custom[..4].to_vec()));
+    }
+}
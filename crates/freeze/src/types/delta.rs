@@ -0,0 +1,329 @@
+use crate::{
+    err, CollectError, ColumnType, Datatype, FileOutput, Partition, Query, SubDir, Table,
+    DECIMAL128_PRECISION, DECIMAL256_PRECISION,
+};
+use polars::prelude::*;
+use serde_json::{json, Value};
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// number of decimal digits of the largest u64
+const U64_PRECISION: usize = 20;
+
+/// number of attempts to commit when concurrent writers claim the same table version
+const MAX_COMMIT_ATTEMPTS: usize = 50;
+
+/// output file to record in the delta table of its dataset
+#[derive(Clone, Debug)]
+pub(crate) struct DeltaFile {
+    /// datatype of file
+    pub datatype: Datatype,
+    /// partition of file
+    pub partition: Partition,
+    /// path of file
+    pub path: PathBuf,
+    /// whether file was just written, replacing any previous file at the same path
+    pub rewritten: bool,
+}
+
+/// files of a delta table as of its latest version
+struct Snapshot {
+    version: Option<u64>,
+    active_paths: HashSet<String>,
+}
+
+/// record output files in the delta tables of their datasets, with one commit per table
+///
+/// each commit adds the given files, removing previous files at the same paths. files that were
+/// not rewritten are only added if the table does not already contain them
+pub(crate) fn commit_files(
+    sink: &FileOutput,
+    query: &Query,
+    files: &[DeltaFile],
+) -> Result<(), CollectError> {
+    let mut by_datatype: BTreeMap<String, (Datatype, Vec<&DeltaFile>)> = BTreeMap::new();
+    for file in files.iter() {
+        by_datatype.entry(file.datatype.name()).or_insert((file.datatype, Vec::new())).1.push(file);
+    }
+    for (datatype, files) in by_datatype.into_values() {
+        let table = query
+            .schemas
+            .get(&datatype)
+            .ok_or_else(|| err(&format!("schema not provided for {}", datatype.name())))?;
+        commit_table_files(sink, datatype, table, &files)?;
+    }
+    Ok(())
+}
+
+fn commit_table_files(
+    sink: &FileOutput,
+    datatype: Datatype,
+    table: &Table,
+    files: &[&DeltaFile],
+) -> Result<(), CollectError> {
+    let root = sink.get_table_dir(datatype)?;
+    let log_dir = root.join("_delta_log");
+    std::fs::create_dir_all(&log_dir).map_err(|_| err("could not create delta log directory"))?;
+    let partition_columns = partition_columns(sink, table);
+
+    // commits are claimed by atomically linking a complete log file to the next version, so
+    // when a concurrent writer claims the version first the commit is rebuilt on top of it
+    for _ in 0..MAX_COMMIT_ATTEMPTS {
+        let snapshot = read_snapshot(&log_dir)?;
+        let now = timestamp_ms();
+        let mut actions = vec![json!({
+            "commitInfo": {
+                "timestamp": now,
+                "operation": "WRITE",
+                "operationParameters": {"mode": "Append"},
+                "engineInfo": format!("cryo {}", crate::CRYO_VERSION),
+            }
+        })];
+        if snapshot.version.is_none() {
+            actions.push(json!({"protocol": {"minReaderVersion": 1, "minWriterVersion": 2}}));
+            actions.push(metadata_action(table, &partition_columns, now));
+        }
+
+        let mut n_added = 0;
+        for file in files.iter() {
+            let path = relative_path(&root, &file.path)?;
+            let is_active = snapshot.active_paths.contains(&path);
+            if is_active && !file.rewritten {
+                continue
+            }
+            if is_active {
+                actions.push(json!({
+                    "remove": {"path": path, "deletionTimestamp": now, "dataChange": true}
+                }));
+            }
+            let metadata = std::fs::metadata(&file.path)
+                .map_err(|_| err(&format!("could not read {}", file.path.to_string_lossy())))?;
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map(|modified| modified.as_millis() as u64)
+                .unwrap_or(now);
+            let mut partition_values = serde_json::Map::new();
+            for (column, subdir) in partition_columns.iter() {
+                let value = sink.subdir_value(subdir, datatype, Some(&file.partition))?;
+                partition_values.insert(column.clone(), Value::String(value));
+            }
+            actions.push(json!({
+                "add": {
+                    "path": path,
+                    "partitionValues": partition_values,
+                    "size": metadata.len(),
+                    "modificationTime": modified,
+                    "dataChange": true,
+                }
+            }));
+            n_added += 1;
+        }
+        if n_added == 0 && snapshot.version.is_some() {
+            return Ok(())
+        }
+
+        let version = snapshot.version.map(|version| version + 1).unwrap_or(0);
+        if write_commit(&log_dir, version, &actions)? {
+            return Ok(())
+        }
+    }
+    Err(err("could not commit to delta table, too many concurrent commits"))
+}
+
+/// write commit to log, returning false if the version was already claimed
+fn write_commit(log_dir: &Path, version: u64, actions: &[Value]) -> Result<bool, CollectError> {
+    let mut contents = String::new();
+    for action in actions.iter() {
+        contents.push_str(&action.to_string());
+        contents.push('\n');
+    }
+    let path = log_dir.join(format!("{:020}.json", version));
+    let tmp_path = log_dir.join(format!(".{:020}.json.{}.tmp", version, rand::random::<u64>()));
+    std::fs::write(&tmp_path, contents).map_err(|_| err("could not write delta commit"))?;
+    let result = std::fs::hard_link(&tmp_path, &path);
+    let _ = std::fs::remove_file(&tmp_path);
+    match result {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Ok(false),
+        Err(_) => Err(err("could not write delta commit")),
+    }
+}
+
+/// replay delta log to find latest version and active files
+fn read_snapshot(log_dir: &Path) -> Result<Snapshot, CollectError> {
+    let mut versions = Vec::new();
+    let entries = std::fs::read_dir(log_dir).map_err(|_| err("could not read delta log"))?;
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if let Some(version) = name.strip_suffix(".json").and_then(|v| v.parse::<u64>().ok()) {
+            versions.push((version, entry.path()))
+        }
+    }
+    versions.sort();
+
+    let mut active_paths = HashSet::new();
+    for (_, path) in versions.iter() {
+        let contents =
+            std::fs::read_to_string(path).map_err(|_| err("could not read delta log"))?;
+        for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+            let action: Value =
+                serde_json::from_str(line).map_err(|_| err("could not parse delta log"))?;
+            if let Some(path) = action["add"]["path"].as_str() {
+                active_paths.insert(path.to_string());
+            } else if let Some(path) = action["remove"]["path"].as_str() {
+                active_paths.remove(path);
+            }
+        }
+    }
+    Ok(Snapshot { version: versions.last().map(|(version, _)| *version), active_paths })
+}
+
+/// subdirectories below the table directory that become partition columns
+///
+/// subdirectories whose key is already a column of the table are not partition columns
+fn partition_columns(sink: &FileOutput, table: &Table) -> Vec<(String, SubDir)> {
+    sink.subdirs
+        .iter()
+        .skip_while(|subdir| !matches!(subdir, SubDir::Datatype))
+        .skip(1)
+        .filter_map(|subdir| subdir.key().map(|key| (key.to_string(), subdir.clone())))
+        .filter(|(key, _)| !table.has_column(key))
+        .collect()
+}
+
+fn metadata_action(table: &Table, partition_columns: &[(String, SubDir)], now: u64) -> Value {
+    let mut fields = Vec::new();
+    for column in table.columns() {
+        match table.column_type(column) {
+            Some(column_type) if column_type.is_256_bit() => {
+                for u256_type in table.u256_types.iter() {
+                    let name = column.to_string() + u256_type.suffix().as_str();
+                    fields.push(schema_field(&name, &delta_type(u256_type.to_columntype())));
+                }
+            }
+            Some(column_type) => fields.push(schema_field(column, &delta_type(column_type))),
+            None => {}
+        }
+    }
+    for (column, _) in partition_columns.iter() {
+        fields.push(schema_field(column, "string"));
+    }
+    let schema = json!({"type": "struct", "fields": fields});
+    let columns: Vec<&String> = partition_columns.iter().map(|(column, _)| column).collect();
+    json!({
+        "metaData": {
+            "id": table_id(),
+            "name": table.datatype.name(),
+            "format": {"provider": "parquet", "options": {}},
+            "schemaString": schema.to_string(),
+            "partitionColumns": columns,
+            "configuration": {},
+            "createdTime": now,
+        }
+    })
+}
+
+fn schema_field(name: &str, delta_type: &str) -> Value {
+    json!({"name": name, "type": delta_type, "nullable": true, "metadata": {}})
+}
+
+/// delta has no unsigned types, so unsigned integers use the next wider type, see
+/// [`cast_unsigned`]
+fn delta_type(column_type: ColumnType) -> String {
+    match column_type {
+        ColumnType::Boolean => "boolean".to_string(),
+        ColumnType::UInt32 | ColumnType::Int64 => "long".to_string(),
+        ColumnType::UInt64 => format!("decimal({},0)", U64_PRECISION),
+        ColumnType::Int32 => "integer".to_string(),
+        ColumnType::Float32 => "float".to_string(),
+        ColumnType::Float64 => "double".to_string(),
+        ColumnType::Decimal128(scale) => format!("decimal({},{})", DECIMAL128_PRECISION, scale),
+        ColumnType::Decimal256 => format!("decimal({},0)", DECIMAL256_PRECISION),
+        ColumnType::String | ColumnType::Hex | ColumnType::UInt256 | ColumnType::Int256 => {
+            "string".to_string()
+        }
+        ColumnType::Binary => "binary".to_string(),
+    }
+}
+
+/// cast unsigned columns to the types of the delta schema before they are written
+///
+/// u32 values become longs and u64 values become decimal(20,0), so no value is lost
+pub(crate) fn cast_unsigned(df: &mut DataFrame) -> Result<(), CollectError> {
+    let columns = df
+        .get_columns()
+        .iter()
+        .map(|series| match series.dtype() {
+            DataType::UInt32 => series.cast(&DataType::Int64),
+            DataType::UInt64 => series.cast(&DataType::Decimal(Some(U64_PRECISION), Some(0))),
+            _ => Ok(series.clone()),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    *df = DataFrame::new(columns)?;
+    Ok(())
+}
+
+fn relative_path(root: &Path, path: &Path) -> Result<String, CollectError> {
+    let relative = path.strip_prefix(root).map_err(|_| err("file is outside of delta table"))?;
+    let pieces: Vec<String> = relative
+        .components()
+        .map(|piece| piece.as_os_str().to_string_lossy().to_string())
+        .collect();
+    Ok(pieces.join("/"))
+}
+
+fn timestamp_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|t| t.as_millis() as u64).unwrap_or(0)
+}
+
+fn table_id() -> String {
+    let id = format!("{:032x}", rand::random::<u128>());
+    format!("{}-{}-{}-{}-{}", &id[..8], &id[8..12], &id[12..16], &id[16..20], &id[20..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_concurrent_version_is_not_overwritten() {
+        let dir = std::env::temp_dir().join(format!("cryo_delta_test_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let add = |path: &str| json!({"add": {"path": path, "partitionValues": {}}});
+        assert!(write_commit(&dir, 0, &[add("a.parquet")]).unwrap());
+        assert!(!write_commit(&dir, 0, &[add("b.parquet")]).unwrap());
+        let remove = json!({"remove": {"path": "a.parquet"}});
+        assert!(write_commit(&dir, 1, &[remove, add("c.parquet")]).unwrap());
+
+        let snapshot = read_snapshot(&dir).unwrap();
+        assert_eq!(snapshot.version, Some(1));
+        assert_eq!(snapshot.active_paths, HashSet::from(["c.parquet".to_string()]));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_cast_unsigned() {
+        let mut df = DataFrame::new(vec![
+            Series::new("block_number", vec![u32::MAX, 0]),
+            Series::new("gas_used", vec![Some(u64::MAX), None]),
+            Series::new("success", vec![true, false]),
+        ])
+        .unwrap();
+        cast_unsigned(&mut df).unwrap();
+        let block_number = df.column("block_number").unwrap().i64().unwrap().get(0);
+        assert_eq!(block_number, Some(u32::MAX as i64));
+        let gas_used = df.column("gas_used").unwrap();
+        assert_eq!(gas_used.dtype(), &DataType::Decimal(Some(20), Some(0)));
+        assert_eq!(gas_used.decimal().unwrap().get(0), Some(u64::MAX as i128));
+        assert_eq!(gas_used.null_count(), 1);
+        assert_eq!(delta_type(ColumnType::UInt64), "decimal(20,0)");
+    }
+}
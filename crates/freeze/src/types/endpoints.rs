@@ -0,0 +1,326 @@
+use crate::{err, sources::ProviderWrapper, CollectError, RateLimiter};
+use ethers::prelude::*;
+use serde::Serialize;
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// an rpc endpoint that requests can be sent to
+#[derive(Debug)]
+pub struct RpcEndpoint {
+    /// label of endpoint, without credentials
+    pub label: String,
+    /// provider of endpoint
+    pub provider: ProviderWrapper,
+    /// relative share of requests sent to endpoint
+    pub weight: f64,
+    /// rate limiter of endpoint
+    pub rate_limiter: Option<RateLimiter>,
+    n_requests: AtomicU64,
+    n_errors: AtomicU64,
+    n_ejections: AtomicU64,
+    consecutive_errors: AtomicU64,
+    ejected_until: Mutex<Option<Instant>>,
+}
+
+impl RpcEndpoint {
+    /// create endpoint
+    pub fn new(
+        label: String,
+        provider: ProviderWrapper,
+        weight: f64,
+        rate_limiter: Option<RateLimiter>,
+    ) -> RpcEndpoint {
+        RpcEndpoint {
+            label,
+            provider,
+            weight,
+            rate_limiter,
+            n_requests: AtomicU64::new(0),
+            n_errors: AtomicU64::new(0),
+            n_ejections: AtomicU64::new(0),
+            consecutive_errors: AtomicU64::new(0),
+            ejected_until: Mutex::new(None),
+        }
+    }
+
+    fn load(&self) -> f64 {
+        (self.n_requests.load(Ordering::SeqCst) + 1) as f64 / self.weight.max(f64::MIN_POSITIVE)
+    }
+
+    fn ejected_until(&self) -> Option<Instant> {
+        self.ejected_until.lock().ok().and_then(|ejected_until| *ejected_until)
+    }
+
+    fn is_ejected(&self, now: Instant) -> bool {
+        self.ejected_until().map(|until| until > now).unwrap_or(false)
+    }
+
+    fn eject(&self, duration: Duration) {
+        if let Ok(mut ejected_until) = self.ejected_until.lock() {
+            *ejected_until = Some(Instant::now() + duration);
+        }
+        self.n_ejections.fetch_add(1, Ordering::SeqCst);
+        self.consecutive_errors.store(0, Ordering::SeqCst);
+    }
+
+    /// usage statistics of endpoint
+    pub fn usage(&self) -> EndpointUsage {
+        EndpointUsage {
+            label: self.label.clone(),
+            n_requests: self.n_requests.load(Ordering::SeqCst),
+            n_errors: self.n_errors.load(Ordering::SeqCst),
+            n_ejections: self.n_ejections.load(Ordering::SeqCst),
+        }
+    }
+}
+
+/// number of requests served by an endpoint
+#[derive(Clone, Debug, Serialize)]
+pub struct EndpointUsage {
+    /// label of endpoint
+    pub label: String,
+    /// number of requests sent to endpoint
+    pub n_requests: u64,
+    /// number of failed requests
+    pub n_errors: u64,
+    /// number of times endpoint was ejected
+    pub n_ejections: u64,
+}
+
+/// set of rpc endpoints that requests are balanced across
+///
+/// each request goes to the healthy endpoint with the fewest requests relative to its weight.
+/// endpoints that keep erroring or that lag behind the highest endpoint are ejected for a while
+#[derive(Debug)]
+pub struct RpcEndpoints {
+    /// endpoints
+    pub endpoints: Vec<RpcEndpoint>,
+    /// consecutive errors before an endpoint is ejected
+    pub max_errors: u64,
+    /// number of blocks an endpoint may lag behind the highest endpoint
+    pub max_lag: u64,
+    /// duration that ejected endpoints are skipped
+    pub ejection_time: Duration,
+    /// interval between block height checks
+    pub check_interval: Duration,
+    last_check: Mutex<Option<Instant>>,
+}
+
+const DEFAULT_MAX_ERRORS: u64 = 3;
+const DEFAULT_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+impl RpcEndpoints {
+    /// create set of endpoints
+    pub fn new(
+        endpoints: Vec<RpcEndpoint>,
+        max_lag: u64,
+        ejection_time: Duration,
+    ) -> Result<RpcEndpoints, CollectError> {
+        if endpoints.is_empty() {
+            return Err(err("at least one rpc endpoint is required"))
+        }
+        Ok(RpcEndpoints {
+            endpoints,
+            max_errors: DEFAULT_MAX_ERRORS,
+            max_lag,
+            ejection_time,
+            check_interval: DEFAULT_CHECK_INTERVAL,
+            last_check: Mutex::new(None),
+        })
+    }
+
+    /// select endpoint for the next request, waiting for the endpoint's rate limit
+    pub async fn select(&self) -> &RpcEndpoint {
+        if self.endpoints.len() > 1 && self.is_check_due() {
+            self.check_heights().await;
+        }
+
+        let now = Instant::now();
+        let endpoint = self
+            .endpoints
+            .iter()
+            .filter(|endpoint| !endpoint.is_ejected(now))
+            .min_by(|a, b| a.load().total_cmp(&b.load()));
+
+        // if every endpoint is ejected, use the one whose ejection ends first
+        let endpoint = match endpoint {
+            Some(endpoint) => endpoint,
+            None => self
+                .endpoints
+                .iter()
+                .min_by_key(|endpoint| endpoint.ejected_until())
+                .unwrap_or(&self.endpoints[0]),
+        };
+
+        endpoint.n_requests.fetch_add(1, Ordering::SeqCst);
+        if let Some(rate_limiter) = &endpoint.rate_limiter {
+            rate_limiter.until_ready().await;
+        }
+        endpoint
+    }
+
+    /// record outcome of a request sent to endpoint
+    pub fn record<T>(
+        &self,
+        endpoint: &RpcEndpoint,
+        result: &::core::result::Result<T, ProviderError>,
+    ) {
+        match result {
+            // error responses come from a reachable node, so they do not count against the endpoint
+            Err(e) if RpcError::as_error_response(e).is_none() => {
+                endpoint.n_errors.fetch_add(1, Ordering::SeqCst);
+                let errors = endpoint.consecutive_errors.fetch_add(1, Ordering::SeqCst) + 1;
+                if errors >= self.max_errors && self.endpoints.len() > 1 {
+                    endpoint.eject(self.ejection_time)
+                }
+            }
+            _ => endpoint.consecutive_errors.store(0, Ordering::SeqCst),
+        }
+    }
+
+    /// usage statistics of each endpoint
+    pub fn usage(&self) -> Vec<EndpointUsage> {
+        self.endpoints.iter().map(|endpoint| endpoint.usage()).collect()
+    }
+
+    fn is_check_due(&self) -> bool {
+        match self.last_check.lock() {
+            Ok(mut last_check) => match *last_check {
+                Some(t) if t.elapsed() < self.check_interval => false,
+                _ => {
+                    *last_check = Some(Instant::now());
+                    true
+                }
+            },
+            Err(_) => false,
+        }
+    }
+
+    /// eject endpoints that lag behind the highest endpoint or cannot report their height
+    async fn check_heights(&self) {
+        let heights = futures::future::join_all(self.endpoints.iter().map(|endpoint| async move {
+            crate::call_provider!(&endpoint.provider, get_block_number()).await
+        }))
+        .await;
+        let heights: Vec<Option<u64>> =
+            heights.into_iter().map(|height| height.ok().map(|height| height.as_u64())).collect();
+        let max_height = match heights.iter().flatten().max() {
+            Some(max_height) => *max_height,
+            None => return,
+        };
+        for (endpoint, height) in self.endpoints.iter().zip(heights) {
+            match height {
+                Some(height) if height.saturating_add(self.max_lag) >= max_height => {}
+                _ => endpoint.eject(self.ejection_time),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn endpoint(label: &str, weight: f64) -> (RpcEndpoint, MockProvider) {
+        let (provider, mock) = Provider::mocked();
+        let provider = ProviderWrapper::MockProvider(Arc::new(provider));
+        (RpcEndpoint::new(label.to_string(), provider, weight, None), mock)
+    }
+
+    fn endpoints(endpoints: Vec<RpcEndpoint>, max_lag: u64) -> RpcEndpoints {
+        RpcEndpoints::new(endpoints, max_lag, Duration::from_secs(60)).unwrap()
+    }
+
+    async fn select_labels(endpoints: &RpcEndpoints, n: usize) -> Vec<String> {
+        let mut labels = Vec::new();
+        for _ in 0..n {
+            labels.push(endpoints.select().await.label.clone());
+        }
+        labels
+    }
+
+    #[tokio::test]
+    async fn test_select_by_weight() {
+        let (a, mock_a) = endpoint("a", 3.0);
+        let (b, mock_b) = endpoint("b", 1.0);
+        mock_a.push(U64::from(100)).unwrap();
+        mock_b.push(U64::from(100)).unwrap();
+        let endpoints = endpoints(vec![a, b], 0);
+
+        let labels = select_labels(&endpoints, 8).await;
+        assert_eq!(labels.iter().filter(|label| *label == "a").count(), 6);
+        assert_eq!(labels.iter().filter(|label| *label == "b").count(), 2);
+        let n_requests: Vec<_> = endpoints.usage().iter().map(|usage| usage.n_requests).collect();
+        assert_eq!(n_requests, vec![6, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_failover_after_consecutive_errors() {
+        let (a, mock_a) = endpoint("a", 1.0);
+        let (b, mock_b) = endpoint("b", 1.0);
+        mock_a.push(U64::from(100)).unwrap();
+        mock_b.push(U64::from(100)).unwrap();
+        let endpoints = endpoints(vec![a, b], 0);
+        endpoints.select().await;
+
+        // error responses come from a healthy node and do not eject it
+        for _ in 0..endpoints.max_errors {
+            let error = JsonRpcError { code: -32000, message: "reverted".to_string(), data: None };
+            let result: Result<(), ProviderError> = Err(MockError::JsonRpcError(error).into());
+            endpoints.record(&endpoints.endpoints[0], &result);
+        }
+        assert_eq!(endpoints.endpoints[0].usage().n_errors, 0);
+        assert!(!endpoints.endpoints[0].is_ejected(Instant::now()));
+
+        // a success resets the count of consecutive errors
+        let result: Result<(), ProviderError> = Err(MockError::EmptyResponses.into());
+        for _ in 1..endpoints.max_errors {
+            endpoints.record(&endpoints.endpoints[0], &result);
+        }
+        endpoints.record(&endpoints.endpoints[0], &Ok::<(), ProviderError>(()));
+        endpoints.record(&endpoints.endpoints[0], &result);
+        assert!(!endpoints.endpoints[0].is_ejected(Instant::now()));
+
+        for _ in 1..endpoints.max_errors {
+            endpoints.record(&endpoints.endpoints[0], &result);
+        }
+        assert!(endpoints.endpoints[0].is_ejected(Instant::now()));
+        assert_eq!(select_labels(&endpoints, 4).await, vec!["b"; 4]);
+
+        let usage = endpoints.endpoints[0].usage();
+        assert_eq!((usage.n_errors, usage.n_ejections), (2 * endpoints.max_errors - 1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_eject_lagging_endpoints() {
+        let (a, mock_a) = endpoint("a", 1.0);
+        let (b, mock_b) = endpoint("b", 1.0);
+        let (c, mock_c) = endpoint("c", 1.0);
+        mock_a.push(U64::from(100)).unwrap();
+        mock_b.push(U64::from(95)).unwrap();
+        mock_c.push(U64::from(94)).unwrap();
+        let endpoints = endpoints(vec![a, b, c], 5);
+
+        let labels = select_labels(&endpoints, 4).await;
+        assert_eq!(labels, vec!["a", "b", "a", "b"]);
+        assert_eq!(endpoints.endpoints[2].usage().n_ejections, 1);
+    }
+
+    #[tokio::test]
+    async fn test_select_when_every_endpoint_is_ejected() {
+        let (a, _) = endpoint("a", 1.0);
+        let (b, _) = endpoint("b", 1.0);
+        let endpoints = endpoints(vec![a, b], 0);
+        *endpoints.last_check.lock().unwrap() = Some(Instant::now());
+        endpoints.endpoints[1].eject(Duration::from_secs(30));
+        endpoints.endpoints[0].eject(Duration::from_secs(60));
+
+        assert_eq!(select_labels(&endpoints, 2).await, vec!["b", "b"]);
+    }
+}
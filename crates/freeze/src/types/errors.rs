@@ -79,7 +79,7 @@ pub enum CollectError {
     BadSchemaError,
 
     /// Error related to too many requests
-    #[error("try using a rate limit with --requests-per-second or limiting max concurrency with --max-concurrent-requests")]
+    #[error("try using --adaptive-rate, a rate limit with --requests-per-second, or limiting max concurrency with --max-concurrent-requests")]
     TooManyRequestsError,
 
     /// Generic RPC Error
@@ -87,6 +87,36 @@ pub enum CollectError {
     RPCError(String),
 }
 
+impl CollectError {
+    /// names of all error kinds
+    pub const KINDS: [&'static str; 9] = [
+        "collect",
+        "parse",
+        "provider",
+        "task",
+        "polars",
+        "invalid_topics",
+        "bad_schema",
+        "too_many_requests",
+        "rpc",
+    ];
+
+    /// name of error kind, used to select which errors are retried
+    pub fn kind(&self) -> &'static str {
+        match self {
+            CollectError::CollectError(_) => "collect",
+            CollectError::ParseError(_) => "parse",
+            CollectError::ProviderError(_) => "provider",
+            CollectError::TaskFailed(_) => "task",
+            CollectError::PolarsError(_) => "polars",
+            CollectError::InvalidNumberOfTopics => "invalid_topics",
+            CollectError::BadSchemaError => "bad_schema",
+            CollectError::TooManyRequestsError => "too_many_requests",
+            CollectError::RPCError(_) => "rpc",
+        }
+    }
+}
+
 /// Error related to parsing
 #[derive(Error, Debug)]
 pub enum ParseError {
@@ -1,4 +1,4 @@
-use crate::CollectError;
+use crate::{CollectError, RetryPolicy};
 use indicatif::ProgressBar;
 use std::{path::PathBuf, sync::Arc, time::SystemTime};
 
@@ -25,6 +25,8 @@ pub struct ExecutionEnv {
     pub t_end: Option<SystemTime>,
     /// report directory
     pub report_dir: Option<PathBuf>,
+    /// retry policy for errored chunks
+    pub retry_policy: RetryPolicy,
 }
 
 impl ExecutionEnv {
@@ -67,6 +69,7 @@ pub struct ExecutionEnvBuilder {
     t_start: SystemTime,
     t_end: Option<SystemTime>,
     report_dir: Option<PathBuf>,
+    retry_policy: RetryPolicy,
 }
 
 impl Default for ExecutionEnvBuilder {
@@ -82,6 +85,7 @@ impl Default for ExecutionEnvBuilder {
             t_start: SystemTime::now(),
             t_end: None,
             report_dir: None,
+            retry_policy: RetryPolicy::default(),
         }
     }
 }
@@ -116,6 +120,12 @@ impl ExecutionEnvBuilder {
         self
     }
 
+    /// retry policy for errored chunks
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
     /// progress bar size
     pub fn bar(mut self, n: u64) -> Result<Self, CollectError> {
         self.bar = Some(new_bar(n)?);
@@ -147,6 +157,7 @@ impl ExecutionEnvBuilder {
             t_start: self.t_start,
             t_end: self.t_end,
             report_dir: self.report_dir,
+            retry_policy: self.retry_policy,
         }
     }
 }
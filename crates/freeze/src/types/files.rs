@@ -1,5 +1,12 @@
-use crate::{CollectError, Datatype, MetaDatatype, ParseError, Partition, Query};
-use std::{collections::HashMap, path::PathBuf};
+use crate::{
+    err, CollectError, Database, Datatype, MetaDatatype, ParseError, Partition, PostgresSink,
+    Query, RemoteStore, Source,
+};
+use chrono::{DateTime, Utc};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
 /// Options for file output
 #[derive(Clone, Debug)]
@@ -12,8 +19,21 @@ pub struct FileOutput {
     pub suffix: Option<String>,
     /// subdirectories to use
     pub subdirs: Vec<SubDir>,
+    /// Whether to name subdirectories as hive-style `key=value` pairs
+    pub hive: bool,
+    /// Chain id of output files, used for chain_id subdirectories
+    pub chain_id: Option<u64>,
+    /// Timestamps of the first blocks of partitions, used for date subdirectories
+    pub block_timestamps: HashMap<u64, u64>,
     /// Whether to overwrite existing files or skip them
     pub overwrite: bool,
+    /// Whether to record chunk block hashes and re-collect existing files whose block hashes no
+    /// longer match the chain
+    pub verify_reorgs: bool,
+    /// Whether to re-collect existing files written with a different schema or cryo version
+    pub recollect_stale: bool,
+    /// Number of blocks per checkpoint saved while collecting each chunk
+    pub checkpoint_size: Option<u64>,
     /// File format to used for output files
     pub format: FileFormat,
     /// Number of rows per parquet row group
@@ -22,6 +42,18 @@ pub struct FileOutput {
     pub parquet_statistics: bool,
     /// Parquet compression options
     pub parquet_compression: polars::prelude::ParquetCompression,
+    /// Arrow IPC buffer compression, None for uncompressed
+    pub ipc_compression: Option<polars::prelude::IpcCompression>,
+    /// Whether to stream rows to stdout instead of writing files
+    pub stdout: bool,
+    /// Whether to record output files in a delta lake table per dataset
+    pub delta: bool,
+    /// Embedded database to write rows into instead of files
+    pub database: Option<Database>,
+    /// Postgres database to bulk load rows into instead of files
+    pub postgres: Option<PostgresSink>,
+    /// Object storage that output files are uploaded to, staged locally in output_dir
+    pub remote: Option<RemoteStore>,
 }
 
 /// Possible item to use as subdirectory
@@ -31,11 +63,49 @@ pub enum SubDir {
     Datatype,
     /// network
     Network,
+    /// chain id
+    ChainId,
+    /// bucket of block numbers of given size, named after first block of bucket
+    BlockBucket(u64),
+    /// utc date of first block of partition
+    Date,
+    /// utc month of first block of partition
+    Month,
     /// custom string
     Custom(String),
 }
 
+impl SubDir {
+    /// key of subdirectory in hive-style layouts
+    pub fn key(&self) -> Option<&'static str> {
+        match self {
+            SubDir::Datatype => Some("datatype"),
+            SubDir::Network => Some("network"),
+            SubDir::ChainId => Some("chain_id"),
+            SubDir::BlockBucket(_) => Some("block_bucket"),
+            SubDir::Date => Some("date"),
+            SubDir::Month => Some("month"),
+            SubDir::Custom(_) => None,
+        }
+    }
+
+    /// whether subdirectory depends on the partition of each file
+    pub fn is_partition_dependent(&self) -> bool {
+        matches!(self, SubDir::BlockBucket(_) | SubDir::Date | SubDir::Month)
+    }
+
+    /// whether subdirectory requires block timestamps
+    pub fn uses_timestamps(&self) -> bool {
+        matches!(self, SubDir::Date | SubDir::Month)
+    }
+}
+
 impl FileOutput {
+    /// whether rows are written to output files, rather than to stdout or a database
+    pub fn writes_files(&self) -> bool {
+        !self.stdout && self.database.is_none() && self.postgres.is_none()
+    }
+
     /// get output file paths
     pub fn get_paths(
         &self,
@@ -85,23 +155,157 @@ impl FileOutput {
             )
         };
         let filename = std::path::Path::new(&filename).to_path_buf();
+        let output_dir = self.get_partition_dir(datatype, partition)?;
+
+        if self.writes_files() {
+            std::fs::create_dir_all(output_dir.clone())
+                .map_err(|_| ParseError::ParseError("could not create dir".to_string()))?;
+        }
+
+        Ok(output_dir.join(filename))
+    }
+
+    /// get path of table decoded from output file of datatype, such as the table of an abi event
+    ///
+    /// the decoded file is placed next to the output file, with the table name as its datatype
+    pub fn get_decoded_path(&self, path: &Path, datatype: Datatype, table_name: &str) -> PathBuf {
+        let filename = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+        let filename = filename.replacen(
+            &format!("__{}__", datatype.name()),
+            &format!("__{}__", table_name),
+            1,
+        );
+        path.with_file_name(filename)
+    }
+
+    /// get directory containing all output files of datatype
+    ///
+    /// the directory stops before the first subdirectory that depends on the partition, so
+    /// output files can be nested in further subdirectories below it
+    pub fn get_dir(&self, datatype: Datatype) -> PathBuf {
         let mut output_dir = std::path::Path::new(&self.output_dir).to_path_buf();
         for subdir in self.subdirs.iter() {
-            let subdir_str: String = match subdir {
-                SubDir::Network => self.prefix.clone(),
-                SubDir::Datatype => match &self.suffix {
-                    Some(suffix) => datatype.name() + "__" + suffix.as_str(),
-                    None => datatype.name(),
-                },
-                SubDir::Custom(subdir_str) => subdir_str.to_string(),
-            };
-            output_dir = output_dir.join(std::path::Path::new(&subdir_str));
+            if subdir.is_partition_dependent() {
+                break
+            }
+            match self.subdir_value(subdir, datatype, None) {
+                Ok(value) => output_dir = output_dir.join(self.subdir_name(subdir, value)),
+                Err(_) => break,
+            }
         }
+        output_dir
+    }
 
-        std::fs::create_dir_all(output_dir.clone())
-            .map_err(|_| ParseError::ParseError("could not create dir".to_string()))?;
+    /// get directory of output file of datatype and partition
+    pub fn get_partition_dir(
+        &self,
+        datatype: Datatype,
+        partition: &Partition,
+    ) -> Result<PathBuf, CollectError> {
+        let mut output_dir = std::path::Path::new(&self.output_dir).to_path_buf();
+        for subdir in self.subdirs.iter() {
+            let value = self.subdir_value(subdir, datatype, Some(partition))?;
+            output_dir = output_dir.join(self.subdir_name(subdir, value));
+        }
+        Ok(output_dir)
+    }
 
-        Ok(output_dir.join(filename))
+    fn subdir_name(&self, subdir: &SubDir, value: String) -> String {
+        match (self.hive, subdir.key()) {
+            (true, Some(key)) => format!("{}={}", key, value),
+            _ => value,
+        }
+    }
+
+    /// get root directory of the delta table of datatype, which is its datatype subdirectory
+    pub fn get_table_dir(&self, datatype: Datatype) -> Result<PathBuf, CollectError> {
+        let mut output_dir = std::path::Path::new(&self.output_dir).to_path_buf();
+        for subdir in self.subdirs.iter() {
+            let value = self.subdir_value(subdir, datatype, None)?;
+            output_dir = output_dir.join(self.subdir_name(subdir, value));
+            if let SubDir::Datatype = subdir {
+                return Ok(output_dir)
+            }
+        }
+        Err(err("delta tables require a datatype subdirectory"))
+    }
+
+    pub(crate) fn subdir_value(
+        &self,
+        subdir: &SubDir,
+        datatype: Datatype,
+        partition: Option<&Partition>,
+    ) -> Result<String, CollectError> {
+        let first_block = || {
+            partition
+                .and_then(|partition| partition.first_block())
+                .ok_or_else(|| err("block subdirectories require block-partitioned chunks"))
+        };
+        let datetime = || -> Result<DateTime<Utc>, CollectError> {
+            let first_block = first_block()?;
+            let timestamp = self
+                .block_timestamps
+                .get(&first_block)
+                .ok_or_else(|| err(&format!("timestamp of block {} missing", first_block)))?;
+            DateTime::from_timestamp(*timestamp as i64, 0)
+                .ok_or_else(|| err(&format!("invalid timestamp of block {}", first_block)))
+        };
+        let value = match subdir {
+            SubDir::Network => self.prefix.clone(),
+            SubDir::Datatype => match &self.suffix {
+                Some(suffix) => datatype.name() + "__" + suffix.as_str(),
+                None => datatype.name(),
+            },
+            SubDir::ChainId => match self.chain_id {
+                Some(chain_id) => chain_id.to_string(),
+                None => return Err(err("chain_id subdirectories require a chain id")),
+            },
+            SubDir::BlockBucket(bucket_size) => {
+                let bucket_size = (*bucket_size).max(1);
+                (first_block()? / bucket_size * bucket_size).to_string()
+            }
+            SubDir::Date => datetime()?.format("%Y-%m-%d").to_string(),
+            SubDir::Month => datetime()?.format("%Y-%m").to_string(),
+            SubDir::Custom(subdir_str) => subdir_str.to_string(),
+        };
+        Ok(value)
+    }
+
+    /// fetch timestamps of the first blocks of partitions, as needed by date subdirectories
+    pub async fn with_block_timestamps(
+        &self,
+        query: &Query,
+        source: &Source,
+    ) -> Result<FileOutput, CollectError> {
+        if !self.subdirs.iter().any(|subdir| subdir.uses_timestamps()) {
+            return Ok(self.clone())
+        }
+        let mut block_numbers: Vec<u64> = query
+            .partitions
+            .iter()
+            .filter_map(|partition| partition.first_block())
+            .filter(|block_number| !self.block_timestamps.contains_key(block_number))
+            .collect();
+        block_numbers.sort();
+        block_numbers.dedup();
+
+        let blocks = futures::future::try_join_all(
+            block_numbers.iter().map(|block_number| source.get_block(*block_number)),
+        )
+        .await?;
+        let mut block_timestamps = self.block_timestamps.clone();
+        for (block_number, block) in block_numbers.into_iter().zip(blocks) {
+            let block =
+                block.ok_or_else(|| err(&format!("could not get block {}", block_number)))?;
+            block_timestamps.insert(block_number, block.timestamp.as_u64());
+        }
+        Ok(FileOutput { block_timestamps, ..self.clone() })
+    }
+
+    /// get path of output file relative to output directory
+    pub fn relative_path(&self, path: &Path) -> PathBuf {
+        let relative = path.strip_prefix(&self.output_dir).unwrap_or(path);
+        relative.strip_prefix("/").unwrap_or(relative).to_path_buf()
     }
 }
 
@@ -111,18 +315,89 @@ pub enum FileFormat {
     /// Parquet file format
     Parquet,
     /// Csv file format
-    Csv,
+    Csv(TextCompression),
     /// Json file format
-    Json,
+    Json(TextCompression),
+    /// Newline-delimited json file format
+    NdJson(TextCompression),
+    /// Arrow IPC file format, also known as Feather v2
+    Arrow,
 }
 
 impl FileFormat {
-    /// convert FileFormat to str
+    /// convert FileFormat to str, used as file extension
     pub fn as_str(&self) -> &'static str {
         match *self {
             FileFormat::Parquet => "parquet",
-            FileFormat::Csv => "csv",
-            FileFormat::Json => "json",
+            FileFormat::Csv(compression) => match compression {
+                TextCompression::Uncompressed => "csv",
+                TextCompression::Gzip(_) => "csv.gz",
+                TextCompression::Zstd(_) => "csv.zst",
+                TextCompression::Bzip2(_) => "csv.bz2",
+            },
+            FileFormat::Json(compression) => match compression {
+                TextCompression::Uncompressed => "json",
+                TextCompression::Gzip(_) => "json.gz",
+                TextCompression::Zstd(_) => "json.zst",
+                TextCompression::Bzip2(_) => "json.bz2",
+            },
+            FileFormat::NdJson(compression) => match compression {
+                TextCompression::Uncompressed => "ndjson",
+                TextCompression::Gzip(_) => "ndjson.gz",
+                TextCompression::Zstd(_) => "ndjson.zst",
+                TextCompression::Bzip2(_) => "ndjson.bz2",
+            },
+            FileFormat::Arrow => "arrow",
+        }
+    }
+
+    /// compression of text file format
+    pub fn text_compression(&self) -> TextCompression {
+        match *self {
+            FileFormat::Csv(compression) |
+            FileFormat::Json(compression) |
+            FileFormat::NdJson(compression) => compression,
+            FileFormat::Parquet | FileFormat::Arrow => TextCompression::Uncompressed,
+        }
+    }
+
+    /// whether file format can store raw binary columns
+    pub fn supports_binary(&self) -> bool {
+        matches!(*self, FileFormat::Parquet | FileFormat::Arrow)
+    }
+
+    /// whether file format can be streamed to stdout
+    pub fn supports_stdout(&self) -> bool {
+        matches!(
+            *self,
+            FileFormat::Csv(TextCompression::Uncompressed) |
+                FileFormat::NdJson(TextCompression::Uncompressed) |
+                FileFormat::Arrow
+        )
+    }
+}
+
+/// compression of csv and json files
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum TextCompression {
+    /// no compression
+    Uncompressed,
+    /// gzip compression with level
+    Gzip(u32),
+    /// zstd compression with level
+    Zstd(i32),
+    /// bzip2 compression with level
+    Bzip2(u32),
+}
+
+impl TextCompression {
+    /// file extension appended after the extension of the file format
+    pub fn extension(&self) -> Option<&'static str> {
+        match self {
+            TextCompression::Uncompressed => None,
+            TextCompression::Gzip(_) => Some("gz"),
+            TextCompression::Zstd(_) => Some("zst"),
+            TextCompression::Bzip2(_) => Some("bz2"),
         }
     }
 }
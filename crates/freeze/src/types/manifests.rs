@@ -0,0 +1,282 @@
+use crate::{
+    err, CollectError, FileOutput, Partition, RemoteStore, Table, U256Type, CRYO_VERSION,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+/// record of a written output file
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// path of file, relative to output directory
+    pub path: PathBuf,
+    /// name of datatype
+    pub datatype: String,
+    /// label of partition
+    pub partition: String,
+    /// fingerprint of schema used to write file
+    pub schema_fingerprint: String,
+    /// version of cryo used to write file
+    pub cryo_version: String,
+    /// number of rows in file
+    pub n_rows: u64,
+    /// first block of file
+    pub first_block: Option<u64>,
+    /// last block of file
+    pub last_block: Option<u64>,
+    /// size of file in bytes
+    pub n_bytes: u64,
+    /// keccak256 checksum of file contents
+    pub checksum: String,
+    /// paths of chunk files that were compacted into this file
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub replaces: Vec<PathBuf>,
+}
+
+impl ManifestEntry {
+    /// whether file was written with the given schema by the current cryo version
+    pub fn is_current(&self, table: &Table) -> bool {
+        self.schema_fingerprint == schema_fingerprint(table) && self.cryo_version == CRYO_VERSION
+    }
+}
+
+/// output manifest, keyed by path relative to output directory
+pub type Manifest = HashMap<PathBuf, ManifestEntry>;
+
+/// compute fingerprint of the columns, types, and encodings of a table
+pub fn schema_fingerprint(table: &Table) -> String {
+    let columns: Vec<String> = table
+        .columns()
+        .into_iter()
+        .map(|column| match table.column_type(column) {
+            Some(column_type) => format!("{}:{}", column, column_type.as_str()),
+            None => column.to_string(),
+        })
+        .collect();
+    let u256_types: Vec<String> = table
+        .u256_types
+        .iter()
+        .map(|t| match t {
+            U256Type::Decimal128(scale) if *scale > 0 => format!("{}({})", t.suffix(), scale),
+            _ => t.suffix(),
+        })
+        .collect();
+    let sort = table.sort_columns.clone().unwrap_or_default();
+    let description = format!(
+        "datatype={};columns={};u256_types={};binary_type={};sort={}",
+        table.datatype.name(),
+        columns.join(","),
+        u256_types.join(","),
+        table.binary_type.as_str(),
+        sort.join(","),
+    );
+    prefix_hex::encode(ethers::utils::keccak256(description.as_bytes())[..8].to_vec())
+}
+
+/// path of the output manifest
+pub fn get_manifest_path(sink: &FileOutput) -> PathBuf {
+    sink.output_dir.join(".cryo/manifest.jsonl")
+}
+
+/// download manifest of remote output location, unless the staging directory already has one
+///
+/// the manifest is uploaded along with output files, so a lost staging directory is restored
+pub(crate) async fn download_manifest(
+    sink: &FileOutput,
+    remote: &RemoteStore,
+) -> Result<(), CollectError> {
+    let path = get_manifest_path(sink);
+    if !path.exists() {
+        remote.download(&sink.relative_path(&path), &path).await?;
+    }
+    Ok(())
+}
+
+/// upload manifest to remote output location, replacing the previously uploaded manifest
+pub(crate) async fn upload_manifest(
+    sink: &FileOutput,
+    remote: &RemoteStore,
+) -> Result<(), CollectError> {
+    let path = get_manifest_path(sink);
+    match path.exists() {
+        true => remote.upload(&path, &sink.relative_path(&path)).await,
+        false => Ok(()),
+    }
+}
+
+/// load output manifest, later entries for a path replace earlier ones
+///
+/// paths of compacted chunk files map to the entry of the file they were compacted into, unless
+/// they have their own entry
+pub fn read_manifest(sink: &FileOutput) -> Result<Manifest, CollectError> {
+    let path = get_manifest_path(sink);
+    if !path.exists() {
+        return Ok(HashMap::new())
+    }
+    let contents = std::fs::read_to_string(path).map_err(|_| err("could not read manifest"))?;
+    let mut manifest = HashMap::new();
+    for line in contents.lines() {
+        // lines truncated by an interrupted write are ignored
+        if let Ok(entry) = serde_json::from_str::<ManifestEntry>(line) {
+            manifest.insert(entry.path.clone(), entry);
+        }
+    }
+    let compacted: Vec<ManifestEntry> =
+        manifest.values().filter(|entry| !entry.replaces.is_empty()).cloned().collect();
+    for entry in compacted.into_iter() {
+        for replaced in entry.replaces.iter() {
+            manifest.entry(replaced.clone()).or_insert_with(|| entry.clone());
+        }
+    }
+    Ok(manifest)
+}
+
+/// rewrite output manifest without the entries of the given paths
+pub(crate) fn remove_manifest_entries(
+    sink: &FileOutput,
+    paths: &HashSet<PathBuf>,
+) -> Result<(), CollectError> {
+    let path = get_manifest_path(sink);
+    if !path.exists() {
+        return Ok(())
+    }
+    let contents = std::fs::read_to_string(&path).map_err(|_| err("could not read manifest"))?;
+    let mut kept = String::new();
+    for line in contents.lines() {
+        match serde_json::from_str::<ManifestEntry>(line) {
+            Ok(entry) if paths.contains(&entry.path) => {}
+            Ok(_) => {
+                kept.push_str(line);
+                kept.push('\n');
+            }
+            Err(_) => {}
+        }
+    }
+
+    // the manifest is replaced atomically so an interrupted rewrite keeps the previous manifest
+    let tmp_path = path.with_extension("jsonl_tmp");
+    std::fs::write(&tmp_path, kept).map_err(|_| err("could not write manifest"))?;
+    std::fs::rename(&tmp_path, &path).map_err(|_| err("could not write manifest"))
+}
+
+/// append entry to output manifest
+pub(crate) fn append_manifest_entry(
+    sink: &FileOutput,
+    entry: &ManifestEntry,
+) -> Result<(), CollectError> {
+    let path = get_manifest_path(sink);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|_| err("could not create manifest directory"))?;
+    }
+    let mut line =
+        serde_json::to_string(entry).map_err(|_| err("could not serialize manifest entry"))?;
+    line.push('\n');
+
+    // each entry is appended with a single write so concurrent chunks do not interleave
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|_| err("could not open manifest"))?;
+    file.write_all(line.as_bytes()).map_err(|_| err("could not write manifest"))
+}
+
+/// create manifest entry for a written file
+pub(crate) fn create_manifest_entry(
+    sink: &FileOutput,
+    path: &Path,
+    datatype: String,
+    partition_label: String,
+    partition: &Partition,
+    table: &Table,
+    n_rows: u64,
+) -> Result<ManifestEntry, CollectError> {
+    let contents = std::fs::read(path).map_err(|_| err("could not read written file"))?;
+    let block_stats = partition.stats().block_numbers;
+    Ok(ManifestEntry {
+        path: sink.relative_path(path),
+        datatype,
+        partition: partition_label,
+        schema_fingerprint: schema_fingerprint(table),
+        cryo_version: CRYO_VERSION.to_string(),
+        n_rows,
+        first_block: block_stats.as_ref().and_then(|stats| stats.min_value),
+        last_block: block_stats.as_ref().and_then(|stats| stats.max_value),
+        n_bytes: contents.len() as u64,
+        checksum: prefix_hex::encode(ethers::utils::keccak256(&contents).to_vec()),
+        replaces: Vec::new(),
+    })
+}
+
+/// whether an existing output file can be skipped
+///
+/// files missing from the manifest were written by older versions and are kept unless stale
+/// files are being re-collected
+pub(crate) fn is_file_complete(
+    sink: &FileOutput,
+    manifest: &Manifest,
+    path: &Path,
+    table: Option<&Table>,
+) -> bool {
+    if !path.exists() {
+        return is_compacted(sink, manifest, path, table)
+    }
+    let n_bytes = std::fs::metadata(path).map(|metadata| metadata.len()).ok();
+    is_entry_complete(sink, manifest, path, n_bytes, table)
+}
+
+/// whether an uploaded output file can be skipped, given the sizes of listed remote objects
+pub(crate) fn is_remote_file_complete(
+    sink: &FileOutput,
+    manifest: &Manifest,
+    path: &Path,
+    remote_sizes: &HashMap<PathBuf, u64>,
+    table: Option<&Table>,
+) -> bool {
+    match remote_sizes.get(&sink.relative_path(path)) {
+        Some(n_bytes) => is_entry_complete(sink, manifest, path, Some(*n_bytes), table),
+        None => false,
+    }
+}
+
+/// whether a missing chunk file was compacted into a file that still exists
+fn is_compacted(
+    sink: &FileOutput,
+    manifest: &Manifest,
+    path: &Path,
+    table: Option<&Table>,
+) -> bool {
+    let relative = sink.relative_path(path);
+    match manifest.get(&relative) {
+        Some(entry) if entry.replaces.contains(&relative) => {
+            let compacted = sink.output_dir.join(&entry.path);
+            let n_bytes = std::fs::metadata(&compacted).map(|metadata| metadata.len()).ok();
+            is_entry_complete(sink, manifest, &compacted, n_bytes, table)
+        }
+        _ => false,
+    }
+}
+
+fn is_entry_complete(
+    sink: &FileOutput,
+    manifest: &Manifest,
+    path: &Path,
+    n_bytes: Option<u64>,
+    table: Option<&Table>,
+) -> bool {
+    match manifest.get(&sink.relative_path(path)) {
+        Some(entry) => {
+            if n_bytes != Some(entry.n_bytes) {
+                return false
+            }
+            match table {
+                Some(table) if sink.recollect_stale => entry.is_current(table),
+                _ => true,
+            }
+        }
+        None => !sink.recollect_stale,
+    }
+}
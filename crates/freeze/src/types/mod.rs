@@ -38,31 +38,68 @@ pub mod dataframes;
 /// function and event signatures
 pub mod signatures;
 
+/// on-disk cache of rpc responses
+pub mod cache;
+/// checkpoints of partially collected chunks
+pub(crate) mod checkpoints;
+/// embedded database sinks
+pub mod databases;
+/// delta lake tables of output files
+pub(crate) mod delta;
+/// load balancing across rpc endpoints
+pub mod endpoints;
 /// error specifications
 pub mod errors;
 /// type specifications for output data formats
 pub mod files;
+/// output manifests
+pub mod manifests;
+/// postgres sink
+pub mod postgres;
 /// queries
 pub mod queries;
+/// adaptive rate limiting
+pub mod rate_control;
+/// recording and replaying of rpc requests
+pub mod recordings;
+/// object storage output
+pub mod remote;
+/// reorg detection
+pub mod reorgs;
+/// retries of errored chunks
+pub mod retries;
 /// type specifications for data schemas
 pub mod schemas;
 /// types related to summaries
 pub mod summaries;
 
+pub use cache::RpcCache;
 pub use chunks::{
     AddressChunk, BlockChunk, CallDataChunk, Chunk, ChunkData, ChunkStats, SlotChunk, Subchunk,
     TopicChunk, TransactionChunk,
 };
 pub use conversions::{bytes_to_u32, ToVecHex, ToVecU8};
+pub use databases::{Database, DatabaseKind};
 pub use dataframes::*;
 pub use datatypes::*;
-pub use files::{ColumnEncoding, FileFormat, FileOutput, SubDir};
+pub use endpoints::{EndpointUsage, RpcEndpoint, RpcEndpoints};
+pub use files::{ColumnEncoding, FileFormat, FileOutput, SubDir, TextCompression};
+pub use manifests::{Manifest, ManifestEntry};
+pub use postgres::{LoadedChunks, PostgresSink};
 pub use queries::{Query, QueryLabels, TimeDimension};
+pub use rate_control::AdaptiveRateLimiter;
+pub use recordings::{FixtureEntry, FixtureError, FixtureRpcError, RecordingClient, ReplayClient};
+pub use remote::RemoteStore;
+pub use reorgs::{ChunkBlockHashes, ReorgedChunk};
+pub use retries::{ChunkAttempt, RetryPolicy};
 pub use schemas::{ColumnType, SchemaFunctions, Schemas, Table, U256Type};
 pub use sources::{Fetcher, RateLimiter, Source, SourceLabels};
 // pub(crate) use summaries::FreezeSummaryAgg;
 // pub use summaries::{FreezeChunkSummary, FreezeSummary};
-pub use summaries::{print_all_datasets, print_dataset_info, FreezeSummary};
+pub use summaries::{
+    print_all_datasets, print_compaction_report, print_dataset_info, print_validation_report,
+    FreezeSummary,
+};
 
 pub use errors::{err, ChunkError, CollectError, FileError, FreezeError, ParseError, R};
 
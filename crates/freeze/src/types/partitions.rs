@@ -286,6 +286,12 @@ impl Partition {
         Ok(self.label_pieces(partitioned_by)?.join("__"))
     }
 
+    /// get lowest block number of partition
+    pub fn first_block(&self) -> Option<u64> {
+        let chunks: &Vec<BlockChunk> = self.block_numbers.as_ref()?;
+        chunks.iter().filter_map(|chunk| chunk.min_value()).min()
+    }
+
     /// partition Partition along given partition dimensions
     pub fn partition(&self, partition_by: Vec<Dim>) -> Result<Vec<Partition>, CollectError> {
         let mut outputs = vec![self.clone()];
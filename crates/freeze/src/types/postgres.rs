@@ -0,0 +1,380 @@
+use crate::{
+    databases::{column_type, quote, CHUNK_COLUMN},
+    err,
+    manifests::schema_fingerprint,
+    CollectError, ColumnType, Table, CRYO_VERSION, DECIMAL128_PRECISION, DECIMAL256_PRECISION,
+};
+use polars::prelude::*;
+use std::{collections::HashMap, sync::Arc};
+
+/// postgres database that datatypes are bulk loaded into, one table per datatype
+///
+/// each chunk is loaded with COPY inside a transaction that also records the chunk in a
+/// `_cryo_chunks` table, so a chunk is either fully loaded and recorded or not at all
+#[derive(Clone)]
+pub struct PostgresSink {
+    /// connection string of database
+    pub url: String,
+    client: Arc<tokio::sync::Mutex<Option<Client>>>,
+}
+
+impl std::fmt::Debug for PostgresSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // connection strings can contain credentials
+        f.debug_struct("PostgresSink").field("url", &"<redacted>").finish()
+    }
+}
+
+#[cfg(feature = "postgres")]
+type Client = tokio_postgres::Client;
+
+#[cfg(not(feature = "postgres"))]
+enum Client {}
+
+/// schema fingerprint and cryo version of each loaded chunk, keyed by table name and chunk
+pub type LoadedChunks = HashMap<(String, String), (String, String)>;
+
+impl PostgresSink {
+    /// create postgres sink from connection string
+    pub fn new(url: String) -> PostgresSink {
+        PostgresSink { url, client: Arc::new(tokio::sync::Mutex::new(None)) }
+    }
+
+    /// read chunks recorded in bookkeeping table
+    pub async fn loaded_chunks(&self) -> Result<LoadedChunks, CollectError> {
+        let mut guard = self.connect().await?;
+        let client = guard.as_mut().ok_or_else(|| err("could not connect to postgres"))?;
+        query_loaded_chunks(client).await
+    }
+
+    /// whether chunk was already loaded into table
+    ///
+    /// chunks loaded with a different schema or cryo version are only complete when stale
+    /// chunks are not being re-collected
+    pub fn is_chunk_complete(
+        loaded: &LoadedChunks,
+        table_name: &str,
+        chunk: &str,
+        table: Option<&Table>,
+        recollect_stale: bool,
+    ) -> bool {
+        match (loaded.get(&(table_name.to_string(), chunk.to_string())), table) {
+            (None, _) => false,
+            (Some((fingerprint, version)), Some(table)) if recollect_stale => {
+                *fingerprint == schema_fingerprint(table) && version == CRYO_VERSION
+            }
+            (Some(_), _) => true,
+        }
+    }
+
+    /// load rows of chunk into table of datatype, replacing previous rows of chunk
+    pub(crate) async fn write_chunk(
+        &self,
+        table_name: &str,
+        chunk: &str,
+        table: &Table,
+        df: &DataFrame,
+    ) -> Result<(), CollectError> {
+        let columns: Vec<String> =
+            df.get_column_names().into_iter().map(|name| name.to_string()).collect();
+        let create_sql = create_table_sql(table_name, table, &columns);
+        let names: Vec<String> = columns.iter().map(|column| quote(column)).collect();
+        let copy_sql = format!(
+            "COPY {} ({}, {}) FROM STDIN WITH (FORMAT csv)",
+            quote(table_name),
+            names.join(", "),
+            CHUNK_COLUMN
+        );
+        let data = dataframe_to_csv(df, chunk)?;
+
+        let load = ChunkLoad {
+            table_name,
+            chunk,
+            create_sql,
+            copy_sql,
+            data,
+            n_rows: df.height() as i64,
+            fingerprint: schema_fingerprint(table),
+        };
+        let mut guard = self.connect().await?;
+        let client = guard.as_mut().ok_or_else(|| err("could not connect to postgres"))?;
+        load_chunk(client, load).await
+    }
+
+    async fn connect(&self) -> Result<tokio::sync::MutexGuard<'_, Option<Client>>, CollectError> {
+        let mut guard = self.client.lock().await;
+        if guard.is_none() {
+            *guard = Some(connect(&self.url).await?);
+        }
+        Ok(guard)
+    }
+}
+
+#[cfg(feature = "postgres")]
+async fn connect(url: &str) -> Result<Client, CollectError> {
+    let (client, connection) =
+        tokio_postgres::connect(url, tokio_postgres::NoTls).await.map_err(postgres_err)?;
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            eprintln!("postgres connection error: {}", e);
+        }
+    });
+    Ok(client)
+}
+
+#[cfg(not(feature = "postgres"))]
+async fn connect(_url: &str) -> Result<Client, CollectError> {
+    Err(err("cryo was built without postgres support, rebuild with `--features postgres`"))
+}
+
+/// statements and data that load one chunk into a table
+#[cfg_attr(not(feature = "postgres"), allow(dead_code))]
+struct ChunkLoad<'a> {
+    table_name: &'a str,
+    chunk: &'a str,
+    create_sql: String,
+    copy_sql: String,
+    data: Vec<u8>,
+    n_rows: i64,
+    fingerprint: String,
+}
+
+#[cfg(feature = "postgres")]
+async fn query_loaded_chunks(client: &mut Client) -> Result<LoadedChunks, CollectError> {
+    use crate::databases::{chunks_table_sql, CHUNKS_TABLE};
+
+    client.batch_execute(&chunks_table_sql()).await.map_err(postgres_err)?;
+    let sql =
+        format!("SELECT table_name, chunk, schema_fingerprint, cryo_version FROM {}", CHUNKS_TABLE);
+    let mut loaded = HashMap::new();
+    for row in client.query(&sql, &[]).await.map_err(postgres_err)? {
+        let fingerprint: Option<String> = row.get(2);
+        let version: Option<String> = row.get(3);
+        loaded.insert(
+            (row.get(0), row.get(1)),
+            (fingerprint.unwrap_or_default(), version.unwrap_or_default()),
+        );
+    }
+    Ok(loaded)
+}
+
+#[cfg(not(feature = "postgres"))]
+async fn query_loaded_chunks(client: &mut Client) -> Result<LoadedChunks, CollectError> {
+    match *client {}
+}
+
+/// delete previous rows of chunk, copy its new rows, and record it, in a single transaction
+#[cfg(feature = "postgres")]
+async fn load_chunk(client: &mut Client, load: ChunkLoad<'_>) -> Result<(), CollectError> {
+    use crate::databases::{chunks_table_sql, CHUNKS_TABLE};
+    use futures::SinkExt;
+
+    client.batch_execute(&chunks_table_sql()).await.map_err(postgres_err)?;
+    client.batch_execute(&load.create_sql).await.map_err(postgres_err)?;
+
+    let transaction = client.transaction().await.map_err(postgres_err)?;
+    let sql = format!("DELETE FROM {} WHERE {} = $1", quote(load.table_name), CHUNK_COLUMN);
+    transaction.execute(&sql, &[&load.chunk]).await.map_err(postgres_err)?;
+    let sink = transaction.copy_in(&load.copy_sql).await.map_err(postgres_err)?;
+    futures::pin_mut!(sink);
+    sink.as_mut().send(bytes::Bytes::from(load.data)).await.map_err(postgres_err)?;
+    sink.finish().await.map_err(postgres_err)?;
+    let sql = format!(
+        "INSERT INTO {} VALUES ($1, $2, $3, $4, $5) ON CONFLICT (table_name, chunk) \
+         DO UPDATE SET n_rows = EXCLUDED.n_rows, \
+         schema_fingerprint = EXCLUDED.schema_fingerprint, cryo_version = EXCLUDED.cryo_version",
+        CHUNKS_TABLE
+    );
+    let params: [&(dyn tokio_postgres::types::ToSql + Sync); 5] =
+        [&load.table_name, &load.chunk, &load.n_rows, &load.fingerprint, &CRYO_VERSION];
+    transaction.execute(&sql, &params).await.map_err(postgres_err)?;
+    transaction.commit().await.map_err(postgres_err)
+}
+
+#[cfg(not(feature = "postgres"))]
+async fn load_chunk(client: &mut Client, _load: ChunkLoad<'_>) -> Result<(), CollectError> {
+    match *client {}
+}
+
+#[cfg(feature = "postgres")]
+fn postgres_err(e: tokio_postgres::Error) -> CollectError {
+    err(&format!("postgres error: {}", e))
+}
+
+fn create_table_sql(table_name: &str, table: &Table, columns: &[String]) -> String {
+    let mut definitions: Vec<String> = columns
+        .iter()
+        .map(|column| format!("{} {}", quote(column), postgres_type(table, column)))
+        .collect();
+    definitions.push(format!("{} TEXT NOT NULL", CHUNK_COLUMN));
+    format!(
+        "CREATE TABLE IF NOT EXISTS {} ({}); CREATE INDEX IF NOT EXISTS {} ON {} ({})",
+        quote(table_name),
+        definitions.join(", "),
+        quote(&format!("{}{}", table_name, CHUNK_COLUMN)),
+        quote(table_name),
+        CHUNK_COLUMN,
+    )
+}
+
+/// postgres type of column
+///
+/// postgres has no unsigned integers, so unsigned columns use the next wider type, and decimal
+/// strings of u256 values are stored as numerics
+fn postgres_type(table: &Table, column: &str) -> String {
+    let is_u256 = table.column_type(column).is_none();
+    let postgres_type = match column_type(table, column) {
+        Some(ColumnType::Boolean) => "BOOLEAN",
+        Some(ColumnType::Int32) => "INTEGER",
+        Some(ColumnType::UInt32 | ColumnType::Int64) => "BIGINT",
+        Some(ColumnType::UInt64) => "NUMERIC(20, 0)",
+        Some(ColumnType::Float32) => "REAL",
+        Some(ColumnType::Float64) => "DOUBLE PRECISION",
+        Some(ColumnType::Decimal128(scale)) => {
+            return format!("NUMERIC({}, {})", DECIMAL128_PRECISION, scale)
+        }
+        Some(ColumnType::Decimal256) => return format!("NUMERIC({}, 0)", DECIMAL256_PRECISION),
+        Some(ColumnType::String) if is_u256 => "NUMERIC(78, 0)",
+        Some(ColumnType::Binary) => "BYTEA",
+        _ => "TEXT",
+    };
+    postgres_type.to_string()
+}
+
+/// encode rows of dataframe as csv for COPY, followed by the chunk label
+fn dataframe_to_csv(df: &DataFrame, chunk: &str) -> Result<Vec<u8>, CollectError> {
+    let mut data = String::new();
+    let chunk = csv_string(chunk);
+    for i in 0..df.height() {
+        for series in df.get_columns() {
+            let value = series.get(i).map_err(CollectError::PolarsError)?;
+            data.push_str(&csv_value(value));
+            data.push(',');
+        }
+        data.push_str(&chunk);
+        data.push('\n');
+    }
+    Ok(data.into_bytes())
+}
+
+/// unquoted empty fields are null, so strings are always quoted
+fn csv_value(value: AnyValue<'_>) -> String {
+    match value {
+        AnyValue::Null => String::new(),
+        AnyValue::String(value) => csv_string(value),
+        AnyValue::StringOwned(value) => csv_string(&value),
+        AnyValue::Binary(value) => format!("\\x{}", hex_string(value)),
+        AnyValue::BinaryOwned(value) => format!("\\x{}", hex_string(&value)),
+        value @ (AnyValue::List(_) | AnyValue::Struct(..) | AnyValue::StructOwned(_)) => {
+            csv_string(&json_value(value).to_string())
+        }
+        value => value.to_string(),
+    }
+}
+
+/// list and struct values are stored as json text, with binary values as hex strings
+fn json_value(value: AnyValue<'_>) -> serde_json::Value {
+    match value {
+        AnyValue::Boolean(value) => value.into(),
+        AnyValue::UInt8(value) => value.into(),
+        AnyValue::UInt16(value) => value.into(),
+        AnyValue::UInt32(value) => value.into(),
+        AnyValue::UInt64(value) => value.into(),
+        AnyValue::Int8(value) => value.into(),
+        AnyValue::Int16(value) => value.into(),
+        AnyValue::Int32(value) => value.into(),
+        AnyValue::Int64(value) => value.into(),
+        AnyValue::Float32(value) => value.into(),
+        AnyValue::Float64(value) => value.into(),
+        AnyValue::String(value) => value.into(),
+        AnyValue::StringOwned(value) => value.as_str().into(),
+        AnyValue::Binary(value) => format!("0x{}", hex_string(value)).into(),
+        AnyValue::BinaryOwned(value) => format!("0x{}", hex_string(&value)).into(),
+        AnyValue::List(series) => series.iter().map(json_value).collect(),
+        value @ AnyValue::Struct(_, _, fields) => {
+            let names = fields.iter().map(|field| field.name().to_string());
+            names.zip(value._iter_struct_av().map(json_value)).collect()
+        }
+        AnyValue::StructOwned(payload) => {
+            let (values, fields) = *payload;
+            let names = fields.iter().map(|field| field.name().to_string());
+            names.zip(values.into_iter().map(json_value)).collect()
+        }
+        AnyValue::Null => serde_json::Value::Null,
+        value => value.to_string().into(),
+    }
+}
+
+fn csv_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
+
+fn hex_string(value: &[u8]) -> String {
+    value.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_encoding() {
+        let df = DataFrame::new(vec![
+            Series::new("n", vec![Some(1u64), None]),
+            Series::new("s", vec!["a\"b", ""]),
+            Series::new("b", vec![vec![0u8, 255u8], vec![]]),
+        ])
+        .unwrap();
+        let data = String::from_utf8(dataframe_to_csv(&df, "chunk").unwrap()).unwrap();
+        assert_eq!(data, "1,\"a\"\"b\",\\x00ff,\"chunk\"\n,\"\",\\x,\"chunk\"\n");
+
+        // list and struct values are quoted json, so their commas do not split rows
+        let lists = [Series::new("", vec![1u64, 2, 3, 4]), Series::new("", Vec::<u64>::new())];
+        let lists = Series::new("l", lists);
+        let fields = [Series::new("x", vec![vec![0u8, 255u8]; 2]), Series::new("y", ["a", "b"])];
+        let structs = StructChunked::new("t", &fields).unwrap().into_series();
+        let df = DataFrame::new(vec![lists, structs]).unwrap();
+        let data = String::from_utf8(dataframe_to_csv(&df, "chunk").unwrap()).unwrap();
+        assert_eq!(
+            data,
+            "\"[1,2,3,4]\",\"{\"\"x\"\":\"\"0x00ff\"\",\"\"y\"\":\"\"a\"\"}\",\"chunk\"\n\
+             \"[]\",\"{\"\"x\"\":\"\"0x00ff\"\",\"\"y\"\":\"\"b\"\"}\",\"chunk\"\n"
+        );
+    }
+
+    /// loads a chunk twice into the database at `CRYO_TEST_POSTGRES_URL`, if set
+    #[cfg(feature = "postgres")]
+    #[tokio::test]
+    async fn test_reload_replaces_chunk() {
+        let url = match std::env::var("CRYO_TEST_POSTGRES_URL") {
+            Ok(url) => url,
+            Err(_) => return,
+        };
+        let sink = PostgresSink::new(url);
+        let columns = Some(vec!["block_number".to_string(), "author".to_string()]);
+        let table = crate::Datatype::Blocks
+            .table_schema(&[], &crate::ColumnEncoding::Binary, &None, &None, &columns, None, None)
+            .unwrap();
+        let df = DataFrame::new(vec![
+            Series::new("block_number", vec![1u32, 2u32]),
+            Series::new("author", vec![vec![1u8], vec![2u8]]),
+        ])
+        .unwrap();
+        let table_name = format!("cryo_test_{}", std::process::id());
+        for _ in 0..2 {
+            sink.write_chunk(&table_name, "test__1_to_2", &table, &df).await.unwrap();
+        }
+        let loaded = sink.loaded_chunks().await.unwrap();
+        assert!(PostgresSink::is_chunk_complete(&loaded, &table_name, "test__1_to_2", None, false));
+
+        let mut guard = sink.connect().await.unwrap();
+        let client = guard.as_mut().unwrap();
+        let sql = format!("SELECT COUNT(*) FROM {}", quote(&table_name));
+        let count: i64 = client.query_one(&sql, &[]).await.unwrap().get(0);
+        assert_eq!(count, 2);
+        let sql = format!("DROP TABLE {}", quote(&table_name));
+        client.batch_execute(&sql).await.unwrap();
+        let sql = format!("DELETE FROM {} WHERE table_name = $1", crate::databases::CHUNKS_TABLE);
+        client.execute(&sql, &[&table_name]).await.unwrap();
+    }
+}
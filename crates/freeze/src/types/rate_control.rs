@@ -0,0 +1,221 @@
+use ethers::prelude::*;
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// request rate limiter that adapts its rate to provider feedback
+///
+/// the rate grows additively while requests succeed and is cut multiplicatively when the
+/// provider returns rate limit errors or when request latency rises above its baseline
+#[derive(Debug)]
+pub struct AdaptiveRateLimiter {
+    /// lowest allowed rate, in requests per second
+    pub min_rate: f64,
+    /// highest allowed rate, in requests per second
+    pub max_rate: Option<f64>,
+    /// increase of rate per second of successful requests, in requests per second
+    pub increase: f64,
+    /// factor that the rate is multiplied by when backing off
+    pub decrease: f64,
+    /// ratio of latency to baseline latency that triggers a backoff
+    pub latency_threshold: f64,
+    state: Mutex<RateState>,
+}
+
+#[derive(Debug)]
+struct RateState {
+    rate: f64,
+    next_slot: Instant,
+    last_update: Instant,
+    last_decrease: Option<Instant>,
+    latency: Option<f64>,
+    baseline_latency: Option<f64>,
+    steady_requests: f64,
+    steady_seconds: f64,
+}
+
+impl AdaptiveRateLimiter {
+    /// create limiter starting at initial rate
+    pub fn new(initial_rate: f64, max_rate: Option<f64>) -> AdaptiveRateLimiter {
+        let now = Instant::now();
+        AdaptiveRateLimiter {
+            min_rate: 1.0,
+            max_rate,
+            increase: 5.0,
+            decrease: 0.5,
+            latency_threshold: 2.0,
+            state: Mutex::new(RateState {
+                rate: initial_rate.max(1.0),
+                next_slot: now,
+                last_update: now,
+                last_decrease: None,
+                latency: None,
+                baseline_latency: None,
+                steady_requests: 0.0,
+                steady_seconds: 0.0,
+            }),
+        }
+    }
+
+    /// current rate, in requests per second
+    pub fn rate(&self) -> f64 {
+        self.state.lock().map(|state| state.rate).unwrap_or(self.min_rate)
+    }
+
+    /// average rate since the limiter first backed off, or the current rate if it never has
+    pub fn steady_state_rate(&self) -> f64 {
+        match self.state.lock() {
+            Ok(mut state) => {
+                state.integrate(Instant::now());
+                if state.steady_seconds > 0.0 {
+                    state.steady_requests / state.steady_seconds
+                } else {
+                    state.rate
+                }
+            }
+            Err(_) => self.min_rate,
+        }
+    }
+
+    /// wait until the next request is allowed
+    pub async fn until_ready(&self) {
+        let wait = match self.state.lock() {
+            Ok(mut state) => {
+                let now = Instant::now();
+                let slot = state.next_slot.max(now);
+                state.next_slot = slot + Duration::from_secs_f64(1.0 / state.rate);
+                slot - now
+            }
+            Err(_) => Duration::ZERO,
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await
+        }
+    }
+
+    /// adjust rate according to the latency and outcome of a completed request
+    pub fn record(&self, latency: Duration, rate_limited: bool) {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return,
+        };
+        let now = Instant::now();
+        state.integrate(now);
+
+        // track smoothed latency and a slowly rising baseline of the lowest latency
+        let sample = latency.as_secs_f64();
+        let latency = match state.latency {
+            Some(latency) => 0.9 * latency + 0.1 * sample,
+            None => sample,
+        };
+        let baseline = match state.baseline_latency {
+            Some(baseline) if baseline <= latency => 0.999 * baseline + 0.001 * latency,
+            _ => latency,
+        };
+        state.latency = Some(latency);
+        state.baseline_latency = Some(baseline);
+
+        let congested = rate_limited || latency > self.latency_threshold * baseline;
+        if congested {
+            // back off at most once per cooldown so a burst of errors only counts once
+            let cooldown = Duration::from_secs_f64((2.0 * latency).max(1.0));
+            let cooled_down = match state.last_decrease {
+                Some(last_decrease) => now.duration_since(last_decrease) >= cooldown,
+                None => true,
+            };
+            if cooled_down {
+                state.rate = (state.rate * self.decrease).max(self.min_rate);
+                state.last_decrease = Some(now);
+            }
+        } else {
+            let rate = state.rate + self.increase / state.rate;
+            state.rate = match self.max_rate {
+                Some(max_rate) => rate.min(max_rate),
+                None => rate,
+            };
+        }
+    }
+}
+
+impl RateState {
+    fn integrate(&mut self, now: Instant) {
+        let dt = now.duration_since(self.last_update).as_secs_f64();
+        if self.last_decrease.is_some() {
+            self.steady_requests += self.rate * dt;
+            self.steady_seconds += dt;
+        }
+        self.last_update = now;
+    }
+}
+
+/// whether provider error indicates that requests are being rate limited
+pub fn is_rate_limit_error(error: &ProviderError) -> bool {
+    if let Some(response) = RpcError::as_error_response(error) {
+        if response.code == 429 || response.code == -32005 {
+            return true
+        }
+    }
+    let message = error.to_string().to_lowercase();
+    message.contains("429") ||
+        message.contains("rate limit") ||
+        message.contains("too many requests")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LATENCY: Duration = Duration::from_millis(10);
+
+    #[test]
+    fn test_increase_while_requests_succeed() {
+        let limiter = AdaptiveRateLimiter::new(10.0, Some(11.0));
+        limiter.record(LATENCY, false);
+        assert_eq!(limiter.rate(), 10.5);
+        for _ in 0..10 {
+            limiter.record(LATENCY, false);
+        }
+        assert_eq!(limiter.rate(), 11.0);
+    }
+
+    #[test]
+    fn test_decrease_when_rate_limited() {
+        let limiter = AdaptiveRateLimiter::new(10.0, None);
+        limiter.record(LATENCY, true);
+        assert_eq!(limiter.rate(), 5.0);
+
+        // errors within the cooldown only back off once
+        limiter.record(LATENCY, true);
+        assert_eq!(limiter.rate(), 5.0);
+
+        // after the cooldown the rate backs off again, down to the minimum rate
+        for _ in 0..5 {
+            limiter.state.lock().unwrap().last_decrease = Some(Instant::now() - LATENCY * 1000);
+            limiter.record(LATENCY, true);
+        }
+        assert_eq!(limiter.rate(), limiter.min_rate);
+    }
+
+    #[test]
+    fn test_decrease_when_latency_rises() {
+        let limiter = AdaptiveRateLimiter::new(10.0, None);
+        for _ in 0..10 {
+            limiter.record(LATENCY, false);
+        }
+        let rate = limiter.rate();
+        assert!(rate > 10.0);
+        limiter.record(LATENCY * 100, false);
+        assert_eq!(limiter.rate(), rate * limiter.decrease);
+    }
+
+    #[test]
+    fn test_is_rate_limit_error() {
+        let response = |code| JsonRpcError { code, message: "error".to_string(), data: None };
+        assert!(is_rate_limit_error(&MockError::JsonRpcError(response(429)).into()));
+        assert!(is_rate_limit_error(&MockError::JsonRpcError(response(-32005)).into()));
+        assert!(!is_rate_limit_error(&MockError::JsonRpcError(response(-32000)).into()));
+        assert!(is_rate_limit_error(&ProviderError::CustomError("Too Many Requests".to_string())));
+        assert!(!is_rate_limit_error(&ProviderError::CustomError("timed out".to_string())));
+    }
+}
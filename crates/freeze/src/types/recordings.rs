@@ -0,0 +1,237 @@
+use async_trait::async_trait;
+use ethers::providers::{JsonRpcClient, JsonRpcError, ProviderError, RpcError};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+/// a recorded json-rpc request and its response
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FixtureEntry {
+    /// json-rpc method
+    pub method: String,
+    /// json-rpc params
+    pub params: serde_json::Value,
+    /// result of request
+    #[serde(default)]
+    pub result: serde_json::Value,
+    /// error response of request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<FixtureRpcError>,
+}
+
+/// a recorded json-rpc error response
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FixtureRpcError {
+    /// error code
+    pub code: i64,
+    /// error message
+    pub message: String,
+    /// additional error data
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+}
+
+impl From<&JsonRpcError> for FixtureRpcError {
+    fn from(value: &JsonRpcError) -> FixtureRpcError {
+        FixtureRpcError {
+            code: value.code,
+            message: value.message.clone(),
+            data: value.data.clone(),
+        }
+    }
+}
+
+impl From<&FixtureRpcError> for JsonRpcError {
+    fn from(value: &FixtureRpcError) -> JsonRpcError {
+        JsonRpcError { code: value.code, message: value.message.clone(), data: value.data.clone() }
+    }
+}
+
+impl FixtureEntry {
+    fn key(&self) -> String {
+        fixture_key(&self.method, &self.params)
+    }
+}
+
+fn fixture_key(method: &str, params: &serde_json::Value) -> String {
+    format!("{} {}", method, params)
+}
+
+/// error of recording or replaying json-rpc fixtures
+#[derive(Debug, thiserror::Error)]
+pub enum FixtureError {
+    /// request has no recorded response
+    #[error("no recorded response for {0}")]
+    MissingResponse(String),
+    /// recorded json-rpc error response
+    #[error(transparent)]
+    JsonRpcError(JsonRpcError),
+    /// error of underlying provider
+    #[error(transparent)]
+    ProviderError(ProviderError),
+    /// serialization error
+    #[error(transparent)]
+    SerdeJson(#[from] serde_json::Error),
+    /// error reading or writing fixture files
+    #[error("fixture io error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl RpcError for FixtureError {
+    fn as_error_response(&self) -> Option<&JsonRpcError> {
+        match self {
+            FixtureError::JsonRpcError(e) => Some(e),
+            FixtureError::ProviderError(e) => e.as_error_response(),
+            _ => None,
+        }
+    }
+
+    fn as_serde_error(&self) -> Option<&serde_json::Error> {
+        match self {
+            FixtureError::SerdeJson(e) => Some(e),
+            FixtureError::ProviderError(e) => e.as_serde_error(),
+            _ => None,
+        }
+    }
+}
+
+impl From<FixtureError> for ProviderError {
+    fn from(value: FixtureError) -> ProviderError {
+        ProviderError::JsonRpcClientError(Box::new(value))
+    }
+}
+
+/// json-rpc client that records every request and response of an inner client to a fixture file
+#[derive(Debug)]
+pub struct RecordingClient<C> {
+    inner: C,
+    path: PathBuf,
+}
+
+impl<C> RecordingClient<C> {
+    /// record requests of client to fixture file at path
+    pub fn new(inner: C, path: PathBuf) -> RecordingClient<C> {
+        RecordingClient { inner, path }
+    }
+
+    fn record(&self, entry: &FixtureEntry) -> Result<(), FixtureError> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+
+        // each entry is appended with a single write so concurrent requests do not interleave
+        let mut file = std::fs::OpenOptions::new().create(true).append(true).open(&self.path)?;
+        file.write_all(line.as_bytes())?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<C: JsonRpcClient> JsonRpcClient for RecordingClient<C> {
+    type Error = FixtureError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, FixtureError>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let params = serde_json::to_value(params)?;
+        let response: Result<serde_json::Value, C::Error> =
+            self.inner.request(method, params.clone()).await;
+        match response {
+            Ok(result) => {
+                let entry =
+                    FixtureEntry { method: method.to_string(), params, result, error: None };
+                self.record(&entry)?;
+                Ok(serde_json::from_value(entry.result)?)
+            }
+            Err(e) => {
+                let e: ProviderError = e.into();
+                if let Some(error) = e.as_error_response() {
+                    let entry = FixtureEntry {
+                        method: method.to_string(),
+                        params,
+                        result: serde_json::Value::Null,
+                        error: Some(error.into()),
+                    };
+                    self.record(&entry)?;
+                }
+                Err(FixtureError::ProviderError(e))
+            }
+        }
+    }
+}
+
+/// json-rpc client that serves responses from recorded fixture files
+#[derive(Debug, Default)]
+pub struct ReplayClient {
+    entries: HashMap<String, FixtureEntry>,
+}
+
+impl ReplayClient {
+    /// load fixtures from a fixture file or from every `.jsonl` file in a directory
+    pub fn load(path: &Path) -> Result<ReplayClient, FixtureError> {
+        let mut paths = Vec::new();
+        if path.is_dir() {
+            for entry in std::fs::read_dir(path)? {
+                let entry_path = entry?.path();
+                if entry_path.extension().and_then(|ext| ext.to_str()) == Some("jsonl") {
+                    paths.push(entry_path)
+                }
+            }
+            paths.sort();
+        } else {
+            paths.push(path.to_path_buf())
+        }
+
+        let mut client = ReplayClient::default();
+        for path in paths.iter() {
+            let contents = std::fs::read_to_string(path)?;
+            for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+                client.insert(serde_json::from_str(line)?);
+            }
+        }
+        Ok(client)
+    }
+
+    /// add recorded entry, replacing any previous entry of the same request
+    pub fn insert(&mut self, entry: FixtureEntry) {
+        self.entries.insert(entry.key(), entry);
+    }
+
+    /// number of recorded requests
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// whether no requests are recorded
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[async_trait]
+impl JsonRpcClient for ReplayClient {
+    type Error = FixtureError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, FixtureError>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let key = fixture_key(method, &serde_json::to_value(params)?);
+        match self.entries.get(&key) {
+            Some(FixtureEntry { error: Some(error), .. }) => {
+                Err(FixtureError::JsonRpcError(error.into()))
+            }
+            Some(entry) => Ok(serde_json::from_value(entry.result.clone())?),
+            None => Err(FixtureError::MissingResponse(key)),
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use crate::{CollectError, ParseError};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+/// object storage location that output files are uploaded to
+///
+/// files are first written to a local staging directory, then uploaded with a multipart upload.
+/// an object only becomes visible once its upload completes, so readers never see partial files.
+/// the output manifest is uploaded along with the files, so later runs can skip uploaded files
+/// without keeping the staging directory
+#[derive(Clone)]
+pub struct RemoteStore {
+    /// url of output location, such as `s3://bucket/prefix`
+    pub url: String,
+    /// bucket of output location
+    pub bucket: String,
+    /// key prefix of output location, without trailing slash
+    pub prefix: String,
+    store: Store,
+}
+
+impl std::fmt::Debug for RemoteStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RemoteStore").field("url", &self.url).finish()
+    }
+}
+
+#[cfg(feature = "s3")]
+type Store = std::sync::Arc<dyn object_store::ObjectStore>;
+
+#[cfg(not(feature = "s3"))]
+#[derive(Clone)]
+enum Store {}
+
+/// size in bytes of each uploaded part
+#[cfg(feature = "s3")]
+const PART_SIZE: usize = 16 * 1024 * 1024;
+
+impl RemoteStore {
+    /// whether output directory refers to object storage
+    pub fn is_remote(output_dir: &str) -> bool {
+        output_dir.starts_with("s3://")
+    }
+
+    /// create remote store from `s3://bucket/prefix` url
+    ///
+    /// credentials, region, and endpoint are read from the standard `AWS_*` environment
+    /// variables, so S3-compatible stores can be used by setting `AWS_ENDPOINT`
+    pub fn new(url: &str) -> Result<RemoteStore, ParseError> {
+        let (bucket, prefix) = parse_url(url)?;
+        let store = build_store(&bucket)?;
+        Ok(RemoteStore { url: url.to_string(), bucket, prefix, store })
+    }
+
+    /// default local directory where files of url are staged before upload
+    pub fn default_staging_dir(url: &str) -> Result<PathBuf, ParseError> {
+        let (bucket, prefix) = parse_url(url)?;
+        let mut dir = std::env::temp_dir().join("cryo_staging").join(bucket);
+        for piece in prefix.split('/').filter(|piece| !piece.is_empty()) {
+            dir = dir.join(piece);
+        }
+        Ok(dir)
+    }
+
+    /// key of path relative to output location
+    pub fn key(&self, relative_path: &Path) -> String {
+        let pieces: Vec<String> = relative_path
+            .components()
+            .map(|piece| piece.as_os_str().to_string_lossy().to_string())
+            .collect();
+        match self.prefix.is_empty() {
+            true => pieces.join("/"),
+            false => format!("{}/{}", self.prefix, pieces.join("/")),
+        }
+    }
+
+    /// sizes of existing objects below output location, keyed by path relative to it
+    pub async fn list_sizes(&self) -> Result<HashMap<PathBuf, u64>, CollectError> {
+        list_sizes(&self.store, &self.prefix).await
+    }
+
+    /// upload local file to key of relative path
+    pub async fn upload(
+        &self,
+        local_path: &Path,
+        relative_path: &Path,
+    ) -> Result<(), CollectError> {
+        upload(&self.store, local_path, &self.key(relative_path)).await
+    }
+
+    /// download object of relative path to local file, returning whether the object exists
+    pub async fn download(
+        &self,
+        relative_path: &Path,
+        local_path: &Path,
+    ) -> Result<bool, CollectError> {
+        download(&self.store, &self.key(relative_path), local_path).await
+    }
+}
+
+fn parse_url(url: &str) -> Result<(String, String), ParseError> {
+    let rest = url
+        .strip_prefix("s3://")
+        .ok_or_else(|| ParseError::ParseError(format!("unsupported output url: {}", url)))?;
+    let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+    if bucket.is_empty() {
+        return Err(ParseError::ParseError(format!("no bucket in output url: {}", url)))
+    }
+    Ok((bucket.to_string(), prefix.trim_matches('/').to_string()))
+}
+
+#[cfg(feature = "s3")]
+fn build_store(bucket: &str) -> Result<Store, ParseError> {
+    let store = object_store::aws::AmazonS3Builder::from_env()
+        .with_bucket_name(bucket)
+        .build()
+        .map_err(|e| ParseError::ParseError(format!("could not configure s3: {}", e)))?;
+    Ok(std::sync::Arc::new(store))
+}
+
+#[cfg(not(feature = "s3"))]
+fn build_store(_bucket: &str) -> Result<Store, ParseError> {
+    Err(ParseError::ParseError(
+        "cryo was built without s3 support, rebuild with `--features s3`".to_string(),
+    ))
+}
+
+#[cfg(feature = "s3")]
+async fn list_sizes(store: &Store, prefix: &str) -> Result<HashMap<PathBuf, u64>, CollectError> {
+    use crate::err;
+    use futures::TryStreamExt;
+
+    let location = object_store::path::Path::from(prefix);
+    let objects: Vec<object_store::ObjectMeta> = store
+        .list(Some(&location))
+        .try_collect()
+        .await
+        .map_err(|e| err(&format!("could not list remote objects: {}", e)))?;
+    let mut sizes = HashMap::new();
+    for object in objects.into_iter() {
+        let key = object.location.as_ref();
+        let relative = match prefix.is_empty() {
+            true => Some(key),
+            false => key.strip_prefix(prefix).and_then(|key| key.strip_prefix('/')),
+        };
+        if let Some(relative) = relative {
+            sizes.insert(PathBuf::from(relative), object.size as u64);
+        }
+    }
+    Ok(sizes)
+}
+
+#[cfg(not(feature = "s3"))]
+async fn list_sizes(store: &Store, _prefix: &str) -> Result<HashMap<PathBuf, u64>, CollectError> {
+    match *store {}
+}
+
+#[cfg(feature = "s3")]
+async fn upload(store: &Store, local_path: &Path, key: &str) -> Result<(), CollectError> {
+    use crate::err;
+    use std::io::Read;
+    use tokio::io::AsyncWriteExt;
+
+    let location = object_store::path::Path::from(key);
+    let (id, mut writer) = store
+        .put_multipart(&location)
+        .await
+        .map_err(|e| err(&format!("could not start upload of {}: {}", key, e)))?;
+    let result = async {
+        let mut file = std::fs::File::open(local_path)?;
+        let mut buffer = vec![0u8; PART_SIZE];
+        loop {
+            let n_bytes = file.read(&mut buffer)?;
+            if n_bytes == 0 {
+                break
+            }
+            writer.write_all(&buffer[..n_bytes]).await?;
+        }
+        // the object is only created once the upload is completed by shutting down the writer
+        writer.shutdown().await
+    }
+    .await;
+    if let Err(e) = result {
+        let _ = store.abort_multipart(&location, &id).await;
+        return Err(err(&format!("could not upload {}: {}", key, e)))
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "s3"))]
+async fn upload(store: &Store, _local_path: &Path, _key: &str) -> Result<(), CollectError> {
+    match *store {}
+}
+
+#[cfg(feature = "s3")]
+async fn download(store: &Store, key: &str, local_path: &Path) -> Result<bool, CollectError> {
+    use crate::err;
+
+    let location = object_store::path::Path::from(key);
+    let bytes = match store.get(&location).await {
+        Ok(result) => result.bytes().await,
+        Err(object_store::Error::NotFound { .. }) => return Ok(false),
+        Err(e) => Err(e),
+    }
+    .map_err(|e| err(&format!("could not download {}: {}", key, e)))?;
+    if let Some(parent) = local_path.parent() {
+        std::fs::create_dir_all(parent).map_err(|_| err("could not create staging directory"))?;
+    }
+    std::fs::write(local_path, bytes).map_err(|_| err(&format!("could not write {}", key)))?;
+    Ok(true)
+}
+
+#[cfg(not(feature = "s3"))]
+async fn download(store: &Store, _key: &str, _local_path: &Path) -> Result<bool, CollectError> {
+    match *store {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_url() {
+        let parsed = parse_url("s3://bucket/some/prefix/").unwrap();
+        assert_eq!(parsed, ("bucket".to_string(), "some/prefix".to_string()));
+        let parsed = parse_url("s3://bucket").unwrap();
+        assert_eq!(parsed, ("bucket".to_string(), "".to_string()));
+        assert!(parse_url("s3:///prefix").is_err());
+        assert!(parse_url("/local/dir").is_err());
+    }
+
+    /// uploads a file to the location at `CRYO_TEST_S3_URL`, if set, such as a local MinIO
+    /// configured through `AWS_ENDPOINT`, `AWS_ALLOW_HTTP`, and credentials
+    #[cfg(feature = "s3")]
+    #[tokio::test]
+    async fn test_upload_is_listed() {
+        let url = match std::env::var("CRYO_TEST_S3_URL") {
+            Ok(url) => url,
+            Err(_) => return,
+        };
+        let remote = RemoteStore::new(&url).unwrap();
+        let local_path = std::env::temp_dir().join(format!("cryo_s3_test_{}", std::process::id()));
+        std::fs::write(&local_path, vec![7u8; 1000]).unwrap();
+        let relative = PathBuf::from(format!("test/{}.parquet", std::process::id()));
+        remote.upload(&local_path, &relative).await.unwrap();
+        let sizes = remote.list_sizes().await.unwrap();
+        assert_eq!(sizes.get(&relative), Some(&1000));
+
+        let location = object_store::path::Path::from(remote.key(&relative));
+        remote.store.delete(&location).await.unwrap();
+        let _ = std::fs::remove_file(local_path);
+    }
+}
@@ -0,0 +1,174 @@
+use crate::{err, BlockChunk, ChunkData, CollectError, FileOutput, Partition, Query, Source};
+use ethers::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// block hashes at the boundaries of a written chunk
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkBlockHashes {
+    /// first block of chunk
+    pub first_block: u64,
+    /// hash of first block of chunk
+    pub first_block_hash: H256,
+    /// parent hash of first block of chunk
+    pub first_parent_hash: H256,
+    /// last block of chunk
+    pub last_block: u64,
+    /// hash of last block of chunk
+    pub last_block_hash: H256,
+    /// parent hash of last block of chunk
+    pub last_parent_hash: H256,
+}
+
+/// chunk whose recorded block hashes no longer match the canonical chain
+#[derive(Clone, Debug, Serialize)]
+pub struct ReorgedChunk {
+    /// path of chunk file
+    pub path: PathBuf,
+    /// first block of chunk
+    pub first_block: u64,
+    /// last block of chunk
+    pub last_block: u64,
+    /// number of blocks between the oldest known orphaned block and the chain tip
+    pub reorg_depth: u64,
+}
+
+/// get the boundary block hashes of a partition's block range
+pub(crate) async fn get_chunk_block_hashes(
+    partition: &Partition,
+    source: &Source,
+) -> Result<Option<ChunkBlockHashes>, CollectError> {
+    let (first_block, last_block) = match block_bounds(partition) {
+        Some(bounds) => bounds,
+        None => return Ok(None),
+    };
+    let (first_block_hash, first_parent_hash) = get_block_hashes(source, first_block).await?;
+    let (last_block_hash, last_parent_hash) = get_block_hashes(source, last_block).await?;
+    Ok(Some(ChunkBlockHashes {
+        first_block,
+        first_block_hash,
+        first_parent_hash,
+        last_block,
+        last_block_hash,
+        last_parent_hash,
+    }))
+}
+
+fn block_bounds(partition: &Partition) -> Option<(u64, u64)> {
+    let chunks: &Vec<BlockChunk> = partition.block_numbers.as_ref()?;
+    let first_block = chunks.iter().filter_map(|chunk| chunk.min_value()).min()?;
+    let last_block = chunks.iter().filter_map(|chunk| chunk.max_value()).max()?;
+    Some((first_block, last_block))
+}
+
+async fn get_block_hashes(
+    source: &Source,
+    block_number: u64,
+) -> Result<(H256, H256), CollectError> {
+    // cached responses are bypassed because they may predate the reorg being detected
+    let block = source
+        .without_cache()
+        .get_block(block_number)
+        .await?
+        .ok_or_else(|| err(format!("could not get block {}", block_number).as_str()))?;
+    let hash = block.hash.ok_or_else(|| err("block hash missing"))?;
+    Ok((hash, block.parent_hash))
+}
+
+/// path where block hashes of a chunk file are recorded
+pub(crate) fn get_block_hashes_path(sink: &FileOutput, path: &Path) -> PathBuf {
+    let relative = sink.relative_path(path);
+    sink.output_dir.join(".cryo/block_hashes").join(relative).with_extension("json")
+}
+
+/// record block hashes of a written chunk file
+pub(crate) fn write_chunk_block_hashes(
+    sink: &FileOutput,
+    path: &Path,
+    hashes: &ChunkBlockHashes,
+) -> Result<(), CollectError> {
+    let hashes_path = get_block_hashes_path(sink, path);
+    if let Some(parent) = hashes_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|_| err("could not create block hashes directory"))?;
+    }
+    let serialized =
+        serde_json::to_string(hashes).map_err(|_| err("could not serialize block hashes"))?;
+    std::fs::write(hashes_path, serialized).map_err(|_| err("could not write block hashes"))
+}
+
+/// load recorded block hashes of a chunk file
+pub(crate) fn read_chunk_block_hashes(sink: &FileOutput, path: &Path) -> Option<ChunkBlockHashes> {
+    let contents = std::fs::read_to_string(get_block_hashes_path(sink, path)).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+/// find existing chunk files of query whose block hashes no longer match the chain
+pub(crate) async fn find_reorged_chunks(
+    query: &Query,
+    source: &Source,
+    sink: &FileOutput,
+) -> Result<Vec<ReorgedChunk>, CollectError> {
+    let latest_block = source.get_block_number().await?.as_u64();
+
+    let mut records = Vec::new();
+    for datatype in query.datatypes.iter() {
+        for partition in query.partitions.iter() {
+            let paths = sink.get_paths(query, partition, Some(vec![datatype.clone()]))?;
+            for path in paths.into_values() {
+                if !path.exists() {
+                    continue
+                }
+                if let Some(hashes) = read_chunk_block_hashes(sink, &path) {
+                    records.push((path, hashes))
+                }
+            }
+        }
+    }
+
+    let checks = records.into_iter().map(|(path, recorded)| async move {
+        verify_chunk(path, recorded, source, latest_block).await
+    });
+    let mut reorged = Vec::new();
+    for result in futures::future::join_all(checks).await {
+        if let Some(chunk) = result? {
+            reorged.push(chunk)
+        }
+    }
+    Ok(reorged)
+}
+
+async fn verify_chunk(
+    path: PathBuf,
+    recorded: ChunkBlockHashes,
+    source: &Source,
+    latest_block: u64,
+) -> Result<Option<ReorgedChunk>, CollectError> {
+    let (last_block_hash, _) = get_block_hashes(source, recorded.last_block).await?;
+    let (first_block_hash, _) = get_block_hashes(source, recorded.first_block).await?;
+    if last_block_hash == recorded.last_block_hash && first_block_hash == recorded.first_block_hash
+    {
+        return Ok(None)
+    }
+
+    // determine the oldest block that is known to have been orphaned
+    let oldest_orphaned = if first_block_hash != recorded.first_block_hash {
+        recorded.first_block
+    } else if recorded.last_block > recorded.first_block {
+        let (parent_hash, _) = get_block_hashes(source, recorded.last_block - 1).await?;
+        if parent_hash == recorded.last_parent_hash {
+            recorded.last_block
+        } else {
+            recorded.first_block + 1
+        }
+    } else {
+        recorded.last_block
+    };
+
+    Ok(Some(ReorgedChunk {
+        path,
+        first_block: recorded.first_block,
+        last_block: recorded.last_block,
+        reorg_depth: latest_block.saturating_sub(oldest_orphaned) + 1,
+    }))
+}
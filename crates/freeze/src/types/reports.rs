@@ -1,4 +1,7 @@
-use crate::{err, CollectError, ExecutionEnv, FileOutput, FreezeSummary, Query};
+use crate::{
+    err, ChunkAttempt, CollectError, EndpointUsage, ExecutionEnv, FileOutput, FreezeSummary, Query,
+    ReorgedChunk,
+};
 use chrono::{DateTime, Local};
 use std::{
     fs::File,
@@ -20,6 +23,10 @@ struct SerializedFreezeSummary {
     completed_paths: Vec<PathBuf>,
     errored_paths: Vec<PathBuf>,
     n_skipped: u64,
+    reorged_chunks: Vec<ReorgedChunk>,
+    chunk_attempts: Vec<ChunkAttempt>,
+    endpoints: Vec<EndpointUsage>,
+    steady_state_request_rate: Option<f64>,
 }
 
 pub(crate) fn get_report_path(
@@ -123,6 +130,10 @@ fn serialize_summary(
         completed_paths,
         errored_paths,
         n_skipped: summary.skipped.len() as u64,
+        reorged_chunks: summary.reorged.clone(),
+        chunk_attempts: summary.attempts.clone(),
+        endpoints: summary.endpoints.clone(),
+        steady_state_request_rate: summary.request_rate,
     })
 }
 
@@ -0,0 +1,188 @@
+use crate::CollectError;
+use rand::Rng;
+use serde::Serialize;
+use std::{future::Future, time::Duration};
+
+/// policy for retrying chunks that fail to collect
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// maximum number of attempts per chunk, including the first attempt
+    pub max_attempts: u32,
+    /// backoff before the first retry, doubled after each subsequent retry
+    pub initial_backoff: Duration,
+    /// maximum backoff between attempts
+    pub max_backoff: Duration,
+    /// fraction of each backoff that is randomized, between 0 and 1
+    pub jitter: f64,
+    /// kinds of errors that are retried, as given by `CollectError::kind()`
+    pub retryable_errors: Vec<String>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 1,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            jitter: 0.5,
+            retryable_errors: default_retryable_errors(),
+        }
+    }
+}
+
+/// kinds of errors retried by default
+pub fn default_retryable_errors() -> Vec<String> {
+    ["provider", "rpc", "too_many_requests", "task"].iter().map(|s| s.to_string()).collect()
+}
+
+impl RetryPolicy {
+    /// whether error should be retried
+    pub fn is_retryable(&self, error: &CollectError) -> bool {
+        self.retryable_errors.iter().any(|kind| kind == error.kind())
+    }
+
+    /// backoff to wait after a failed attempt, attempts are numbered from 1
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(31);
+        let backoff = self.initial_backoff.saturating_mul(1 << exponent).min(self.max_backoff);
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        if jitter > 0.0 {
+            let scale = 1.0 - jitter * rand::thread_rng().gen::<f64>();
+            backoff.mul_f64(scale)
+        } else {
+            backoff
+        }
+    }
+
+    /// run attempts until one succeeds, its error is not retryable, or attempts are exhausted
+    ///
+    /// attempts are only returned for chunks that failed at least once
+    pub(crate) async fn run<T, F, Fut>(
+        &self,
+        datatype: &str,
+        chunk: &str,
+        mut run_attempt: F,
+    ) -> (Result<T, CollectError>, Vec<ChunkAttempt>)
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, CollectError>>,
+    {
+        let mut attempts = Vec::new();
+        let mut attempt = 1;
+        loop {
+            let result = run_attempt().await;
+            let retry = match &result {
+                Ok(_) => false,
+                Err(e) => attempt < self.max_attempts && self.is_retryable(e),
+            };
+            attempts.push(ChunkAttempt {
+                datatype: datatype.to_string(),
+                chunk: chunk.to_string(),
+                attempt,
+                error: result.as_ref().err().map(|e| e.to_string()),
+                retried: retry,
+            });
+            if !retry {
+                if attempt == 1 && result.is_ok() {
+                    attempts.clear();
+                }
+                return (result, attempts)
+            }
+            tokio::time::sleep(self.backoff(attempt)).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// outcome of a single attempt at collecting a chunk
+#[derive(Clone, Debug, Serialize)]
+pub struct ChunkAttempt {
+    /// name of datatype
+    pub datatype: String,
+    /// label of chunk
+    pub chunk: String,
+    /// attempt number, starting from 1
+    pub attempt: u32,
+    /// error of attempt, if attempt failed
+    pub error: Option<String>,
+    /// whether attempt was followed by a retry
+    pub retried: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(max_attempts: u32, jitter: f64) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(500),
+            jitter,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = policy(5, 0.0);
+        let backoffs: Vec<_> = (1..=5).map(|attempt| policy.backoff(attempt).as_millis()).collect();
+        assert_eq!(backoffs, vec![100, 200, 400, 500, 500]);
+
+        let policy = RetryPolicy { jitter: 0.5, ..policy };
+        for _ in 0..100 {
+            let backoff = policy.backoff(2);
+            assert!(backoff >= Duration::from_millis(100) && backoff <= Duration::from_millis(200));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_retries_until_success() {
+        let policy = RetryPolicy { initial_backoff: Duration::ZERO, ..policy(3, 0.0) };
+        let mut n_calls = 0;
+        let (result, attempts) = policy
+            .run("blocks", "1_to_10", || {
+                n_calls += 1;
+                let result = match n_calls {
+                    1 | 2 => Err(CollectError::TooManyRequestsError),
+                    _ => Ok(n_calls),
+                };
+                async move { result }
+            })
+            .await;
+        assert_eq!(result.unwrap(), 3);
+        let retried: Vec<_> = attempts.iter().map(|a| (a.attempt, a.retried)).collect();
+        assert_eq!(retried, vec![(1, true), (2, true), (3, false)]);
+        assert!(attempts[2].error.is_none());
+
+        // attempts are not reported for chunks that succeed on the first attempt
+        let (result, attempts) = policy.run("blocks", "1_to_10", || async { Ok(()) }).await;
+        assert!(result.is_ok() && attempts.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_run_exhausts_attempts() {
+        let policy = RetryPolicy { initial_backoff: Duration::ZERO, ..policy(2, 0.0) };
+        let mut n_calls = 0;
+        let (result, attempts) = policy
+            .run("blocks", "1_to_10", || {
+                n_calls += 1;
+                async { Err::<(), _>(CollectError::TooManyRequestsError) }
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(n_calls, 2);
+        let retried: Vec<_> = attempts.iter().map(|a| (a.attempt, a.retried)).collect();
+        assert_eq!(retried, vec![(1, true), (2, false)]);
+
+        // errors of kinds that are not retryable fail immediately
+        let (result, attempts) = policy
+            .run("blocks", "1_to_10", || async {
+                Err::<(), _>(CollectError::CollectError("bad".to_string()))
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.len(), 1);
+        assert!(!attempts[0].retried);
+    }
+}
@@ -1,7 +1,7 @@
 /// types and functions related to schemas
 use std::collections::HashMap;
 
-use crate::{err, CollectError, ColumnEncoding, Datatype, LogDecoder};
+use crate::{err, AbiDecoder, CollectError, ColumnEncoding, Datatype, FunctionDecoder, LogDecoder};
 use indexmap::{IndexMap, IndexSet};
 use thiserror::Error;
 
@@ -23,7 +23,7 @@ impl SchemaFunctions for HashMap<Datatype, Table> {
 /// Schema for a particular table
 #[derive(Clone, Debug, PartialEq)]
 pub struct Table {
-    columns: IndexMap<String, ColumnType>,
+    pub(crate) columns: IndexMap<String, ColumnType>,
 
     /// datatype of Table
     pub datatype: Datatype,
@@ -39,6 +39,12 @@ pub struct Table {
 
     /// log decoder for table
     pub log_decoder: Option<LogDecoder>,
+
+    /// decoder of logs into a table per abi event
+    pub abi_decoder: Option<AbiDecoder>,
+
+    /// decoder of call inputs and outputs
+    pub function_decoder: Option<FunctionDecoder>,
 }
 
 impl Table {
@@ -61,7 +67,7 @@ impl Table {
 /// representation of a U256 datum
 #[derive(Hash, Clone, Debug, Eq, PartialEq)]
 pub enum U256Type {
-    /// Binary representation
+    /// Binary representation, 32 big-endian bytes in two's complement
     Binary,
     /// String representation
     String,
@@ -73,8 +79,12 @@ pub enum U256Type {
     U32,
     /// U64 representation
     U64,
-    /// Decimal128 representation
-    Decimal128,
+    /// Decimal128 representation with the given scale, null for values of more than 38 digits
+    Decimal128(u32),
+    /// Decimal256 representation, null for values of more than 76 digits
+    ///
+    /// held as decimal strings in dataframes and written as decimal(76, 0) to parquet files
+    Decimal256,
 }
 
 impl U256Type {
@@ -87,7 +97,8 @@ impl U256Type {
             U256Type::F64 => ColumnType::Float64,
             U256Type::U32 => ColumnType::UInt32,
             U256Type::U64 => ColumnType::UInt64,
-            U256Type::Decimal128 => ColumnType::Decimal128,
+            U256Type::Decimal128(scale) => ColumnType::Decimal128(*scale),
+            U256Type::Decimal256 => ColumnType::Decimal256,
         }
     }
 
@@ -100,7 +111,8 @@ impl U256Type {
             U256Type::F64 => "_f64".to_string(),
             U256Type::U32 => "_u32".to_string(),
             U256Type::U64 => "_u64".to_string(),
-            U256Type::Decimal128 => "_d128".to_string(),
+            U256Type::Decimal128(_) => "_d128".to_string(),
+            U256Type::Decimal256 => "_d256".to_string(),
         }
     }
 }
@@ -116,6 +128,8 @@ pub enum ColumnType {
     UInt64,
     /// U256 column type
     UInt256,
+    /// I256 column type
+    Int256,
     /// Int32 column type
     Int32,
     /// Int64 column type
@@ -124,8 +138,10 @@ pub enum ColumnType {
     Float32,
     /// Float64 column type
     Float64,
-    /// Decimal128 column type
-    Decimal128,
+    /// Decimal128 column type with the given scale
+    Decimal128(u32),
+    /// Decimal256 column type, with a scale of zero
+    Decimal256,
     /// String column type
     String,
     /// Binary column type
@@ -142,16 +158,23 @@ impl ColumnType {
             ColumnType::UInt32 => "uint32",
             ColumnType::UInt64 => "uint64",
             ColumnType::UInt256 => "uint256",
+            ColumnType::Int256 => "int256",
             ColumnType::Int32 => "int32",
             ColumnType::Int64 => "int64",
             ColumnType::Float32 => "float32",
             ColumnType::Float64 => "float64",
-            ColumnType::Decimal128 => "decimal128",
+            ColumnType::Decimal128(_) => "decimal128",
+            ColumnType::Decimal256 => "decimal256",
             ColumnType::String => "string",
             ColumnType::Binary => "binary",
             ColumnType::Hex => "hex",
         }
     }
+
+    /// whether column holds 256-bit integers, which are written in each of the u256 types
+    pub fn is_256_bit(&self) -> bool {
+        matches!(self, ColumnType::UInt256 | ColumnType::Int256)
+    }
 }
 
 /// Error related to Schemas
@@ -201,6 +224,8 @@ impl Datatype {
             u256_types: u256_types.to_owned(),
             binary_type: binary_column_format.clone(),
             log_decoder,
+            abi_decoder: None,
+            function_decoder: None,
         };
         Ok(schema)
     }
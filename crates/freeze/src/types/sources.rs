@@ -11,7 +11,12 @@ use tokio::{
     task,
 };
 
-use crate::CollectError;
+use crate::{
+    rate_control::is_rate_limit_error, AdaptiveRateLimiter, CollectError, EndpointUsage,
+    RecordingClient, ReplayClient, RpcCache, RpcEndpoints,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::future::Future;
 
 /// RateLimiter based on governor crate
 pub type RateLimiter = governor::RateLimiter<NotKeyed, InMemoryState, DefaultClock, NoOpMiddleware>;
@@ -33,6 +38,12 @@ pub struct Source {
     pub semaphore: Arc<Option<Semaphore>>,
     /// rate limiter for controlling request rate
     pub rate_limiter: Arc<Option<RateLimiter>>,
+    /// rate limiter that adapts request rate to provider feedback
+    pub adaptive_rate_limiter: Option<Arc<AdaptiveRateLimiter>>,
+    /// on-disk cache of rpc responses
+    pub cache: Option<Arc<RpcCache>>,
+    /// endpoints that requests are balanced across, if not set requests use `provider`
+    pub endpoints: Option<Arc<RpcEndpoints>>,
     /// Labels (these are non-functional)
     pub labels: SourceLabels,
 }
@@ -48,6 +59,10 @@ pub enum ProviderWrapper {
     WsClient(Arc<Provider<Ws>>),
     /// ipc client
     IpcClient(Arc<Provider<Ipc>>),
+    /// http client that records requests to fixture files
+    RecordingClientHttp(Arc<Provider<RecordingClient<RetryClient<Http>>>>),
+    /// client that replays recorded fixture files
+    ReplayClient(Arc<Provider<ReplayClient>>),
 }
 
 impl From<Provider<MockProvider>> for ProviderWrapper {
@@ -74,15 +89,57 @@ impl From<Provider<Ipc>> for ProviderWrapper {
     }
 }
 
-/// extract the provider from a source and run specified method
+impl From<Provider<RecordingClient<RetryClient<Http>>>> for ProviderWrapper {
+    fn from(value: Provider<RecordingClient<RetryClient<Http>>>) -> ProviderWrapper {
+        ProviderWrapper::RecordingClientHttp(Arc::new(value))
+    }
+}
+
+impl From<Provider<ReplayClient>> for ProviderWrapper {
+    fn from(value: Provider<ReplayClient>) -> ProviderWrapper {
+        ProviderWrapper::ReplayClient(Arc::new(value))
+    }
+}
+
+/// run specified method on a provider wrapper
 #[macro_export]
-macro_rules! source_provider {
-    ($source:expr, $method:ident($($arg:expr),*)) => {
-        match &$source.provider {
+macro_rules! call_provider {
+    ($provider:expr, $method:ident($($arg:expr),*)) => {
+        match $provider {
             ProviderWrapper::MockProvider(provider) => provider.$method($($arg),*),
             ProviderWrapper::RetryClientHttp(provider) => provider.$method($($arg),*),
             ProviderWrapper::WsClient(provider) => provider.$method($($arg),*),
             ProviderWrapper::IpcClient(provider) => provider.$method($($arg),*),
+            ProviderWrapper::RecordingClientHttp(provider) => provider.$method($($arg),*),
+            ProviderWrapper::ReplayClient(provider) => provider.$method($($arg),*),
+        }
+    };
+}
+
+/// extract the provider from a source and run specified method
+///
+/// when the source has multiple endpoints, the request is sent to the endpoint selected by the
+/// load balancer. the outcome of the request is recorded for load balancing and rate limiting
+#[macro_export]
+macro_rules! source_provider {
+    ($source:expr, $method:ident($($arg:expr),*)) => {
+        async {
+            match &$source.endpoints {
+                Some(endpoints) => {
+                    let endpoint = endpoints.select().await;
+                    let t_start = std::time::Instant::now();
+                    let result = $crate::call_provider!(&endpoint.provider, $method($($arg),*)).await;
+                    endpoints.record(endpoint, &result);
+                    $source.record_request(t_start, &result);
+                    result
+                }
+                None => {
+                    let t_start = std::time::Instant::now();
+                    let result = $crate::call_provider!(&$source.provider, $method($($arg),*)).await;
+                    $source.record_request(t_start, &result);
+                    result
+                }
+            }
         }
     };
 }
@@ -181,6 +238,9 @@ impl Source {
             },
             rate_limiter: rate_limiter.into(),
             semaphore: semaphore.into(),
+            adaptive_rate_limiter: None,
+            cache: None,
+            endpoints: None,
         };
 
         Ok(source)
@@ -264,8 +324,12 @@ type Result<T> = ::core::result::Result<T, CollectError>;
 impl Source {
     /// Returns an array (possibly empty) of logs that match the filter
     pub async fn get_logs(&self, filter: &Filter) -> Result<Vec<Log>> {
-        let _permit = self.permit_request().await;
-        Self::map_err(source_provider!(self, get_logs(filter)).await)
+        let block = filter.get_to_block().map(|block| block.as_u64());
+        self.cached("eth_getLogs", filter, block, async {
+            let _permit = self.permit_request().await;
+            Self::map_err(source_provider!(self, get_logs(filter)).await)
+        })
+        .await
     }
 
     /// Replays all transactions in a block returning the requested traces for each transaction
@@ -274,10 +338,16 @@ impl Source {
         block: BlockNumber,
         trace_types: Vec<TraceType>,
     ) -> Result<Vec<BlockTrace>> {
-        let _permit = self.permit_request().await;
-        Self::map_err(
-            source_provider!(self, trace_replay_block_transactions(block, trace_types)).await,
-        )
+        let block_number = block.as_number().map(|block| block.as_u64());
+        let params = (block, &trace_types);
+        self.cached("trace_replayBlockTransactions", params, block_number, async {
+            let _permit = self.permit_request().await;
+            Self::map_err(
+                source_provider!(self, trace_replay_block_transactions(block, trace_types.clone()))
+                    .await,
+            )
+        })
+        .await
     }
 
     /// Get state diff traces of block
@@ -376,8 +446,11 @@ impl Source {
 
     /// Gets the block at `block_num` (transaction hashes only)
     pub async fn get_block(&self, block_num: u64) -> Result<Option<Block<TxHash>>> {
-        let _permit = self.permit_request().await;
-        Self::map_err(source_provider!(self, get_block(block_num)).await)
+        self.cached("eth_getBlockByNumber", (block_num, false), Some(block_num), async {
+            let _permit = self.permit_request().await;
+            Self::map_err(source_provider!(self, get_block(block_num)).await)
+        })
+        .await
     }
 
     /// Gets the block at `block_num` (transaction hashes only)
@@ -388,8 +461,11 @@ impl Source {
 
     /// Gets the block at `block_num` (full transactions included)
     pub async fn get_block_with_txs(&self, block_num: u64) -> Result<Option<Block<Transaction>>> {
-        let _permit = self.permit_request().await;
-        Self::map_err(source_provider!(self, get_block_with_txs(block_num)).await)
+        self.cached("eth_getBlockByNumber", (block_num, true), Some(block_num), async {
+            let _permit = self.permit_request().await;
+            Self::map_err(source_provider!(self, get_block_with_txs(block_num)).await)
+        })
+        .await
     }
 
     /// Returns all receipts for a block.
@@ -397,14 +473,21 @@ impl Source {
     /// Consider using `FetcherExt::get_tx_receipts_in_block` which takes a block, and falls back to
     /// `eth_getTransactionReceipt` if `eth_getBlockReceipts` is not supported.
     pub async fn get_block_receipts(&self, block_num: u64) -> Result<Vec<TransactionReceipt>> {
-        let _permit = self.permit_request().await;
-        Self::map_err(source_provider!(self, get_block_receipts(block_num)).await)
+        self.cached("eth_getBlockReceipts", block_num, Some(block_num), async {
+            let _permit = self.permit_request().await;
+            Self::map_err(source_provider!(self, get_block_receipts(block_num)).await)
+        })
+        .await
     }
 
     /// Returns traces created at given block
     pub async fn trace_block(&self, block_num: BlockNumber) -> Result<Vec<Trace>> {
-        let _permit = self.permit_request().await;
-        Self::map_err(source_provider!(self, trace_block(block_num)).await)
+        let block = block_num.as_number().map(|block| block.as_u64());
+        self.cached("trace_block", block_num, block, async {
+            let _permit = self.permit_request().await;
+            Self::map_err(source_provider!(self, trace_block(block_num)).await)
+        })
+        .await
     }
 
     /// Returns all traces of a given transaction
@@ -553,12 +636,18 @@ impl Source {
         options: GethDebugTracingOptions,
         include_transaction_hashes: bool,
     ) -> Result<(Option<u32>, Vec<Option<Vec<u8>>>, Vec<GethTrace>)> {
-        let traces = {
-            let _permit = self.permit_request().await;
-            source_provider!(self, debug_trace_block_by_number(Some(block_number.into()), options))
+        let params = (block_number, &options);
+        let traces: Vec<GethTrace> = self
+            .cached("debug_traceBlockByNumber", params, Some(block_number as u64), async {
+                let _permit = self.permit_request().await;
+                source_provider!(
+                    self,
+                    debug_trace_block_by_number(Some(block_number.into()), options.clone())
+                )
                 .await
-                .map_err(CollectError::ProviderError)?
-        };
+                .map_err(CollectError::ProviderError)
+            })
+            .await?;
 
         let txs = if include_transaction_hashes {
             match self.get_block(block_number as u64).await? {
@@ -917,6 +1006,83 @@ impl Source {
         Ok((block, txs, diffs))
     }
 
+    /// record latency and outcome of a request for adaptive rate limiting
+    pub fn record_request<T>(
+        &self,
+        t_start: std::time::Instant,
+        result: &::core::result::Result<T, ProviderError>,
+    ) {
+        if let Some(limiter) = &self.adaptive_rate_limiter {
+            let rate_limited = match result {
+                Err(e) => is_rate_limit_error(e),
+                Ok(_) => false,
+            };
+            limiter.record(t_start.elapsed(), rate_limited)
+        }
+    }
+
+    /// steady-state request rate of the adaptive rate limiter
+    pub fn steady_state_rate(&self) -> Option<f64> {
+        self.adaptive_rate_limiter.as_ref().map(|limiter| limiter.steady_state_rate())
+    }
+
+    /// usage statistics of each endpoint
+    pub fn endpoint_usage(&self) -> Vec<EndpointUsage> {
+        match &self.endpoints {
+            Some(endpoints) => endpoints.usage(),
+            None => Vec::new(),
+        }
+    }
+
+    /// skip the response cache, used for requests that must reflect the current chain
+    pub fn without_cache(&self) -> Source {
+        Source { cache: None, ..self.clone() }
+    }
+
+    /// serve request from the response cache if possible, caching responses of finalized blocks
+    async fn cached<P, T, F>(
+        &self,
+        method: &str,
+        params: P,
+        block_number: Option<u64>,
+        request: F,
+    ) -> Result<T>
+    where
+        P: Serialize,
+        T: Serialize + DeserializeOwned,
+        F: Future<Output = Result<T>>,
+    {
+        let (cache, block_number) = match (&self.cache, block_number) {
+            (Some(cache), Some(block_number)) => (cache, block_number),
+            _ => return request.await,
+        };
+        let key = match RpcCache::key(method, &params, self.chain_id) {
+            Some(key) => key,
+            None => return request.await,
+        };
+        if let Some(value) = cache.get(&key) {
+            return Ok(value)
+        }
+        let value = request.await?;
+        if self.is_finalized(cache, block_number).await {
+            cache.insert(&key, &value);
+        }
+        Ok(value)
+    }
+
+    async fn is_finalized(&self, cache: &RpcCache, block_number: u64) -> bool {
+        if cache.is_finalized(block_number) {
+            return true
+        }
+        match self.get_block_number().await {
+            Ok(latest_block) => {
+                cache.set_latest_block(latest_block.as_u64());
+                cache.is_finalized(block_number)
+            }
+            Err(_) => false,
+        }
+    }
+
     async fn permit_request(
         &self,
     ) -> Option<::core::result::Result<SemaphorePermit<'_>, AcquireError>> {
@@ -927,6 +1093,9 @@ impl Source {
         if let Some(limiter) = &*self.rate_limiter {
             limiter.until_ready().await;
         }
+        if let Some(limiter) = &self.adaptive_rate_limiter {
+            limiter.until_ready().await;
+        }
         permit
     }
 
@@ -5,10 +5,14 @@ use colored::Colorize;
 use thousands::Separable;
 
 use crate::{
-    chunks::chunk_ops::ValueToString, ChunkData, ChunkStats, CollectError, ColumnType, Datatype,
-    Dim, ExecutionEnv, FileOutput, MetaDatatype, MultiDatatype, Partition, Query, Source, Table,
+    chunks::chunk_ops::ValueToString, ChunkAttempt, ChunkData, ChunkStats, CollectError,
+    CompactTarget, CompactionReport, Datatype, Dim, EndpointUsage, ExecutionEnv, FileOutput,
+    MetaDatatype, MultiDatatype, Partition, Query, ReorgedChunk, Source, Table, ValidationReport,
+};
+use std::{
+    path::PathBuf,
+    sync::atomic::{AtomicBool, Ordering},
 };
-use std::path::PathBuf;
 
 const TITLE_R: u8 = 0;
 const TITLE_G: u8 = 225;
@@ -17,6 +21,24 @@ const ERROR_R: u8 = 225;
 const ERROR_G: u8 = 0;
 const ERROR_B: u8 = 0;
 
+pub(crate) static PRINT_TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// print summaries to stderr instead of stdout, used when data is streamed to stdout
+pub fn print_to_stderr(enabled: bool) {
+    PRINT_TO_STDERR.store(enabled, Ordering::SeqCst)
+}
+
+macro_rules! summary_println {
+    ($($arg:tt)*) => {
+        if $crate::summaries::PRINT_TO_STDERR.load(std::sync::atomic::Ordering::SeqCst) {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
+pub(crate) use summary_println;
+
 /// summary of a freeze
 #[derive(Debug, Default)]
 pub struct FreezeSummary {
@@ -28,6 +50,14 @@ pub struct FreezeSummary {
     pub errored: Vec<(Option<Partition>, CollectError)>,
     /// rows written
     pub n_rows: u64,
+    /// existing chunks re-collected because of reorgs
+    pub reorged: Vec<ReorgedChunk>,
+    /// attempts of chunks that failed at least once
+    pub attempts: Vec<ChunkAttempt>,
+    /// requests served by each rpc endpoint
+    pub endpoints: Vec<EndpointUsage>,
+    /// steady-state request rate of adaptive rate limiting, in requests per second
+    pub request_rate: Option<f64>,
 }
 
 /// print all datasets
@@ -49,7 +79,7 @@ pub fn print_all_datasets() {
             print_bullet_key(datatype.name())
         }
     }
-    println!();
+    summary_println!();
     print_header("dataset group names");
     for datatype in MultiDatatype::variants().iter() {
         let name = heck::AsSnakeCase(format!("{:?}", datatype)).to_string();
@@ -57,8 +87,11 @@ pub fn print_all_datasets() {
             datatype.datatypes().iter().map(|dt| dt.name()).collect::<Vec<_>>().join(", ");
         print_bullet(name, subtypes)
     }
-    println!();
-    println!("use {} to print info about a specific dataset", "cryo help <DATASET>".bold().white())
+    summary_println!();
+    summary_println!(
+        "use {} to print info about a specific dataset",
+        "cryo help <DATASET>".bold().white()
+    )
 }
 
 /// print info about datasets
@@ -102,32 +135,112 @@ pub fn print_dataset_info(datatype: Datatype, schema: &Table) {
     } else {
         print_bullet("parameter aliases", "");
         for (key, value) in arg_aliases.iter() {
-            println!("    - {} -> {}", key.plural_name(), value.plural_name());
+            summary_println!("    - {} -> {}", key.plural_name(), value.plural_name());
         }
     }
-    println!();
-    println!();
+    summary_println!();
+    summary_println!();
     print_schema(&datatype, schema);
 }
 
+/// print results of validating an output directory
+pub fn print_validation_report(report: &ValidationReport) {
+    print_header("validation summary");
+    print_bullet("output dir", report.output_dir.to_string_lossy());
+    print_bullet("datasets", report.datasets.len().separate_with_commas());
+    for dataset in report.datasets.iter() {
+        let block_range = match (dataset.first_block, dataset.last_block) {
+            (Some(first_block), Some(last_block)) => format!(
+                ", blocks {} to {}",
+                first_block.separate_with_commas(),
+                last_block.separate_with_commas()
+            ),
+            _ => "".to_string(),
+        };
+        print_bullet_indent(
+            format!("{}__{}", dataset.network, dataset.datatype),
+            format!(
+                "{} files, {} rows{}, {} issues",
+                dataset.n_files.separate_with_commas(),
+                dataset.n_rows.separate_with_commas(),
+                block_range,
+                dataset.issues.len().separate_with_commas(),
+            ),
+            4,
+        );
+    }
+
+    if !report.is_valid() {
+        summary_println!();
+        print_header_error("validation issues");
+        for dataset in report.datasets.iter() {
+            for issue in dataset.issues.iter() {
+                let paths: Vec<String> = issue
+                    .paths
+                    .iter()
+                    .filter_map(|path| path.file_name())
+                    .map(|name| name.to_string_lossy().to_string())
+                    .collect();
+                print_bullet(
+                    format!("{:?}", issue.kind),
+                    format!("{} ({})", issue.message, paths.join(", ")),
+                );
+            }
+        }
+    }
+}
+
+/// print summary of compaction
+pub fn print_compaction_report(report: &CompactionReport) {
+    let title = match report.dry {
+        true => "compaction plan",
+        false => "compaction summary",
+    };
+    print_header(title);
+    print_bullet("output dir", report.output_dir.to_string_lossy());
+    let target = match report.target {
+        CompactTarget::Blocks(n) => format!("{} blocks", n.separate_with_commas()),
+        CompactTarget::Rows(n) => format!("{} rows", n.separate_with_commas()),
+        CompactTarget::Bytes(n) => format!("{} bytes", n.separate_with_commas()),
+    };
+    print_bullet("target size", target);
+    let n_replaced: usize = report.merges.iter().map(|merge| merge.replaces.len()).sum();
+    print_bullet("files merged", n_replaced.separate_with_commas());
+    print_bullet("files created", report.merges.len().separate_with_commas());
+    for merge in report.merges.iter() {
+        let name = merge.path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        print_bullet_indent(
+            name,
+            format!(
+                "{} files, blocks {} to {}, {} rows",
+                merge.replaces.len().separate_with_commas(),
+                merge.first_block.separate_with_commas(),
+                merge.last_block.separate_with_commas(),
+                merge.n_rows.separate_with_commas(),
+            ),
+            4,
+        );
+    }
+}
+
 pub(crate) fn print_header<A: AsRef<str>>(header: A) {
     let header_str = header.as_ref().white().bold();
     let underline = "─".repeat(header_str.len()).truecolor(TITLE_R, TITLE_G, TITLE_B);
-    println!("{}", header_str);
-    println!("{}", underline);
+    summary_println!("{}", header_str);
+    summary_println!("{}", underline);
 }
 
 pub(crate) fn print_header_error<A: AsRef<str>>(header: A) {
     let header_str = header.as_ref().white().bold();
     let underline = "─".repeat(header_str.len()).truecolor(ERROR_R, ERROR_G, ERROR_B);
-    println!("{}", header_str);
-    println!("{}", underline);
+    summary_println!("{}", header_str);
+    summary_println!("{}", underline);
 }
 
 fn print_bullet_key<A: AsRef<str>>(key: A) {
     let bullet_str = "- ".truecolor(TITLE_R, TITLE_G, TITLE_B);
     let key_str = key.as_ref().white().bold();
-    println!("{}{}", bullet_str, key_str);
+    summary_println!("{}{}", bullet_str, key_str);
 }
 
 fn print_bullet<A: AsRef<str>, B: AsRef<str>>(key: A, value: B) {
@@ -135,14 +248,14 @@ fn print_bullet<A: AsRef<str>, B: AsRef<str>>(key: A, value: B) {
     let key_str = key.as_ref().white().bold();
     let value_str = value.as_ref().truecolor(170, 170, 170);
     let colon_str = ": ".truecolor(TITLE_R, TITLE_G, TITLE_B);
-    println!("{}{}{}{}", bullet_str, key_str, colon_str, value_str);
+    summary_println!("{}{}{}{}", bullet_str, key_str, colon_str, value_str);
 }
 
 fn print_bullet_parenthetical<A: AsRef<str>, B: AsRef<str>>(key: A, value: B) {
     let bullet_str = "- ".truecolor(TITLE_R, TITLE_G, TITLE_B);
     let key_str = key.as_ref().white().bold();
     let value_str = value.as_ref().truecolor(170, 170, 170);
-    println!("{}{} ({})", bullet_str, key_str, value_str);
+    summary_println!("{}{} ({})", bullet_str, key_str, value_str);
 }
 
 fn print_bullet_indent<A: AsRef<str>, B: AsRef<str>>(key: A, value: B, indent: usize) {
@@ -150,7 +263,7 @@ fn print_bullet_indent<A: AsRef<str>, B: AsRef<str>>(key: A, value: B, indent: u
     let key_str = key.as_ref().white().bold();
     let value_str = value.as_ref().truecolor(170, 170, 170);
     let colon_str = ": ".truecolor(TITLE_R, TITLE_G, TITLE_B);
-    println!("{}{}{}{}{}", " ".repeat(indent), bullet_str, key_str, colon_str, value_str);
+    summary_println!("{}{}{}{}{}", " ".repeat(indent), bullet_str, key_str, colon_str, value_str);
 }
 
 pub(crate) fn print_cryo_intro(
@@ -177,7 +290,14 @@ pub(crate) fn print_cryo_intro(
 
     print_bullet("source", "");
     print_bullet_indent("network", &sink.prefix, 4);
-    print_bullet_indent("rpc url", &source.rpc_url, 4);
+    match &source.endpoints {
+        Some(endpoints) if endpoints.endpoints.len() > 1 => {
+            let labels: Vec<String> =
+                endpoints.endpoints.iter().map(|endpoint| endpoint.label.clone()).collect();
+            print_bullet_indent("rpc endpoints", labels.join(", "), 4);
+        }
+        _ => print_bullet_indent("rpc url", &source.rpc_url, 4),
+    }
     match source.labels.max_requests_per_second {
         Some(max_requests_per_second) => print_bullet_indent(
             "max requests per second",
@@ -243,7 +363,11 @@ pub(crate) fn print_cryo_intro(
     );
     print_bullet_indent("chunks to collect", chunk_text, 4);
     print_bullet_indent("output format", sink.format.as_str(), 4);
-    print_bullet_indent("output dir", sink.output_dir.clone().to_string_lossy(), 4);
+    if sink.stdout {
+        print_bullet_indent("output dir", "stdout", 4);
+    } else {
+        print_bullet_indent("output dir", sink.output_dir.clone().to_string_lossy(), 4);
+    }
 
     // print report path
     let report_path = if env.report && n_chunks_remaining > 0 {
@@ -270,10 +394,10 @@ pub(crate) fn print_cryo_intro(
     print_schemas(&query.datatypes, &query.schemas)?;
 
     if env.dry {
-        println!("\n\n[dry run, exiting]");
+        summary_println!("\n\n[dry run, exiting]");
     } else {
-        println!();
-        println!();
+        summary_println!();
+        summary_println!();
         print_header("collecting data");
     }
 
@@ -359,8 +483,8 @@ fn print_schemas(
     for meta_datatype in datatypes.iter() {
         for datatype in meta_datatype.datatypes().iter() {
             if let Some(schema) = schemas.get(datatype) {
-                println!();
-                println!();
+                summary_println!();
+                summary_println!();
                 print_schema(datatype, &schema.clone())
             }
         }
@@ -372,7 +496,7 @@ fn print_schema(name: &Datatype, schema: &Table) {
     print_header("schema for ".to_string() + name.name().as_str());
     for column in schema.columns() {
         if let Some(column_type) = schema.column_type(column) {
-            if column_type == ColumnType::UInt256 {
+            if column_type.is_256_bit() {
                 for uint256_type in schema.u256_types.iter() {
                     print_bullet(
                         column.to_owned() + uint256_type.suffix().as_str(),
@@ -384,17 +508,17 @@ fn print_schema(name: &Datatype, schema: &Table) {
             }
         }
     }
-    println!();
+    summary_println!();
     if let Some(sort_cols) = schema.sort_columns.clone() {
-        println!("sorting {} by: {}", name.name(), sort_cols.join(", "));
+        summary_println!("sorting {} by: {}", name.name(), sort_cols.join(", "));
     } else {
-        println!("sorting disabled for {}", name.name());
+        summary_println!("sorting disabled for {}", name.name());
     }
     let other_columns =
         name.column_types().keys().copied().filter(|x| !schema.has_column(x)).collect::<Vec<_>>();
     let other_columns =
         if other_columns.is_empty() { "[none]".to_string() } else { other_columns.join(", ") };
-    println!("\nother available columns: {}", other_columns);
+    summary_println!("\nother available columns: {}", other_columns);
 }
 
 pub(crate) fn print_cryo_conclusion(
@@ -417,32 +541,35 @@ pub(crate) fn print_cryo_conclusion(
     let dt_data_done: DateTime<Local> = t_end.into();
     let dt_start: DateTime<Local> = env.t_start.into();
 
-    println!("started at {}", dt_start.format("%Y-%m-%d %H:%M:%S%.3f"));
-    println!("   done at {}", dt_data_done.format("%Y-%m-%d %H:%M:%S%.3f").to_string().as_str());
-    println!();
-    println!();
+    summary_println!("started at {}", dt_start.format("%Y-%m-%d %H:%M:%S%.3f"));
+    summary_println!(
+        "   done at {}",
+        dt_data_done.format("%Y-%m-%d %H:%M:%S%.3f").to_string().as_str()
+    );
+    summary_println!();
+    summary_println!();
 
     if !freeze_summary.errored.is_empty() {
         print_header_error("error summary");
-        println!("(errors in {} chunks)", freeze_summary.errored.len());
+        summary_println!("(errors in {} chunks)", freeze_summary.errored.len());
         let mut error_counts: HashMap<String, usize> = HashMap::new();
         for (_partition, error) in freeze_summary.errored.iter() {
             *error_counts.entry(error.to_string()).or_insert(0) += 1;
         }
         for (error, count) in error_counts.iter().take(10) {
-            println!("- {} ({}x)", error, count);
+            summary_println!("- {} ({}x)", error, count);
         }
         if error_counts.len() > 10 {
-            println!("...")
+            summary_println!("...")
         }
-        println!();
-        println!();
+        summary_println!();
+        summary_println!();
     }
 
     let duration = match t_end.duration_since(env.t_start) {
         Ok(duration) => duration,
         Err(_e) => {
-            println!("error computing system time, aborting");
+            summary_println!("error computing system time, aborting");
             return
         }
     };
@@ -490,6 +617,41 @@ pub(crate) fn print_cryo_conclusion(
         ),
         4,
     );
+    if !freeze_summary.reorged.is_empty() {
+        let max_depth =
+            freeze_summary.reorged.iter().map(|chunk| chunk.reorg_depth).max().unwrap_or(0);
+        print_bullet_indent(
+            "chunks reorged",
+            format!(
+                "  {:>width$} / {} (max depth {})",
+                freeze_summary.reorged.len().separate_with_commas(),
+                n_chunks_str,
+                max_depth.separate_with_commas(),
+                width = width
+            ),
+            4,
+        );
+    }
+    let n_retries = freeze_summary.attempts.iter().filter(|attempt| attempt.retried).count();
+    if n_retries > 0 {
+        print_bullet_indent("chunk retries", n_retries.separate_with_commas(), 4);
+    }
+    if let Some(request_rate) = freeze_summary.request_rate {
+        print_bullet_indent("steady-state rate", format!("{:.1} requests/s", request_rate), 4);
+    }
+    if freeze_summary.endpoints.len() > 1 {
+        print_bullet_indent("requests by endpoint", "", 4);
+        for usage in freeze_summary.endpoints.iter() {
+            let mut text = format!("{} requests", usage.n_requests.separate_with_commas());
+            if usage.n_errors > 0 {
+                text = format!("{}, {} errors", text, usage.n_errors.separate_with_commas());
+            }
+            if usage.n_ejections > 0 {
+                text = format!("{}, {} ejections", text, usage.n_ejections.separate_with_commas());
+            }
+            print_bullet_indent(&usage.label, text, 8);
+        }
+    }
 
     print_chunks_speeds(
         freeze_summary.completed.clone(),
@@ -0,0 +1,435 @@
+use crate::{
+    dataframes::{read_parquet, read_text_file}, err, CollectError, Datatype, FileOutput, Table, CRYO_VERSION,
+};
+use chrono::{DateTime, Local};
+use polars::prelude::*;
+use regex::Regex;
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+/// result of validating the files of an output directory
+#[derive(Clone, Debug, Serialize)]
+pub struct ValidationReport {
+    /// version of cryo used to validate
+    pub cryo_version: String,
+    /// output directory that was validated
+    pub output_dir: PathBuf,
+    /// validation of each dataset found in output directory
+    pub datasets: Vec<DatasetValidation>,
+}
+
+impl ValidationReport {
+    /// total number of issues found
+    pub fn n_issues(&self) -> usize {
+        self.datasets.iter().map(|dataset| dataset.issues.len()).sum()
+    }
+
+    /// whether no issues were found
+    pub fn is_valid(&self) -> bool {
+        self.n_issues() == 0
+    }
+}
+
+/// validation of the files of a single datatype and network
+#[derive(Clone, Debug, Serialize)]
+pub struct DatasetValidation {
+    /// name of datatype
+    pub datatype: String,
+    /// network prefix of files
+    pub network: String,
+    /// number of files
+    pub n_files: usize,
+    /// number of rows across readable files
+    pub n_rows: u64,
+    /// first block covered by files
+    pub first_block: Option<u64>,
+    /// last block covered by files
+    pub last_block: Option<u64>,
+    /// issues found
+    pub issues: Vec<ValidationIssue>,
+}
+
+/// kind of validation issue
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ValidationIssueKind {
+    /// file could not be read
+    Unreadable,
+    /// file is missing columns of current schema
+    MissingColumns,
+    /// file has columns not in current schema
+    ExtraColumns,
+    /// rows of file share a primary key
+    DuplicateKeys,
+    /// blocks between two files are not covered
+    Gap,
+    /// blocks are covered by multiple files
+    Overlap,
+}
+
+/// problem found while validating files
+#[derive(Clone, Debug, Serialize)]
+pub struct ValidationIssue {
+    /// kind of issue
+    pub kind: ValidationIssueKind,
+    /// files involved in issue
+    pub paths: Vec<PathBuf>,
+    /// description of issue
+    pub message: String,
+}
+
+/// validate existing output files of datatypes without re-collecting them
+pub fn validate(
+    datatypes: &[Datatype],
+    schemas: &HashMap<Datatype, Table>,
+    sink: &FileOutput,
+) -> Result<ValidationReport, CollectError> {
+    let mut datasets = Vec::new();
+    for datatype in datatypes.iter() {
+        let table = schemas
+            .get(datatype)
+            .ok_or_else(|| err(format!("schema not provided for {}", datatype.name()).as_str()))?;
+        for (network, files) in find_files(sink, *datatype)?.into_iter() {
+            datasets.push(validate_dataset(*datatype, table, network, files));
+        }
+    }
+    Ok(ValidationReport {
+        cryo_version: CRYO_VERSION.to_string(),
+        output_dir: sink.output_dir.clone(),
+        datasets,
+    })
+}
+
+/// output file along with its partition label
+pub(crate) struct LabeledFile {
+    pub(crate) path: PathBuf,
+    pub(crate) label: String,
+}
+
+/// block range in the partition label of a file
+pub(crate) struct LabelBlockRange {
+    /// label with the block range replaced by `*`, shared by files that differ only in blocks
+    pub(crate) group: String,
+    /// first block of range
+    pub(crate) start: u64,
+    /// last block of range
+    pub(crate) end: u64,
+    /// number of digits used for block numbers in label
+    pub(crate) width: usize,
+}
+
+impl LabelBlockRange {
+    /// parse first `{start}_to_{end}` piece of label
+    pub(crate) fn parse(label: &str) -> Option<LabelBlockRange> {
+        let re = Regex::new(r"^(\d+)_to_(\d+)$").expect("valid regex");
+        let pieces: Vec<&str> = label.split("__").collect();
+        for (index, piece) in pieces.iter().enumerate() {
+            let captures = match re.captures(piece) {
+                Some(captures) => captures,
+                None => continue,
+            };
+            if let (Ok(start), Ok(end)) = (captures[1].parse::<u64>(), captures[2].parse::<u64>()) {
+                let mut group = pieces.clone();
+                group[index] = "*";
+                let width = captures[1].len();
+                return Some(LabelBlockRange { group: group.join("__"), start, end, width })
+            }
+        }
+        None
+    }
+
+    /// label of group with the given block range
+    pub(crate) fn label(&self, start: u64, end: u64) -> String {
+        let range = format!("{:0width$}_to_{:0width$}", start, end, width = self.width);
+        self.group.replacen('*', &range, 1)
+    }
+}
+
+/// find output files of datatype, grouped by network prefix
+pub(crate) fn find_files(
+    sink: &FileOutput,
+    datatype: Datatype,
+) -> Result<BTreeMap<String, Vec<LabeledFile>>, CollectError> {
+    let dir = sink.get_dir(datatype);
+    let mut files: BTreeMap<String, Vec<LabeledFile>> = BTreeMap::new();
+    if !dir.exists() {
+        return Ok(files)
+    }
+
+    let marker = match &sink.suffix {
+        Some(suffix) => format!("__{}__{}__", datatype.name(), suffix),
+        None => format!("__{}__", datatype.name()),
+    };
+    let extension = format!(".{}", sink.format.as_str());
+    for path in list_files(&dir)? {
+        let filename = match path.file_name().and_then(|name| name.to_str()) {
+            Some(filename) => filename,
+            None => continue,
+        };
+        let stem = match filename.strip_suffix(extension.as_str()) {
+            Some(stem) => stem,
+            None => continue,
+        };
+        let (network, label) = match stem.find(marker.as_str()) {
+            Some(index) => (&stem[..index], &stem[index + marker.len()..]),
+            None => continue,
+        };
+        if !sink.prefix.is_empty() && network != sink.prefix {
+            continue
+        }
+        let file = LabeledFile { path: path.clone(), label: label.to_string() };
+        files.entry(network.to_string()).or_default().push(file);
+    }
+    Ok(files)
+}
+
+/// list files in directory and in its subdirectories, skipping hidden directories
+fn list_files(dir: &Path) -> Result<Vec<PathBuf>, CollectError> {
+    let mut files = Vec::new();
+    let entries = std::fs::read_dir(dir).map_err(|_| err("could not read output directory"))?;
+    for entry in entries {
+        let path = entry.map_err(|_| err("could not read output directory"))?.path();
+        let hidden =
+            path.file_name().and_then(|name| name.to_str()).map(|name| name.starts_with('.'));
+        if path.is_dir() && hidden != Some(true) {
+            files.extend(list_files(&path)?);
+        } else if path.is_file() {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+fn validate_dataset(
+    datatype: Datatype,
+    table: &Table,
+    network: String,
+    mut files: Vec<LabeledFile>,
+) -> DatasetValidation {
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    let mut issues = Vec::new();
+    let mut n_rows = 0;
+    for file in files.iter() {
+        match read_file(&file.path) {
+            Ok(df) => {
+                n_rows += df.height() as u64;
+                issues.extend(check_columns(&file.path, &df, table));
+                issues.extend(check_duplicates(&file.path, &df, datatype));
+            }
+            Err(e) => issues.push(ValidationIssue {
+                kind: ValidationIssueKind::Unreadable,
+                paths: vec![file.path.clone()],
+                message: e.to_string(),
+            }),
+        }
+    }
+    let (coverage_issues, first_block, last_block) = check_coverage(&files);
+    issues.extend(coverage_issues);
+
+    DatasetValidation {
+        datatype: datatype.name(),
+        network,
+        n_files: files.len(),
+        n_rows,
+        first_block,
+        last_block,
+        issues,
+    }
+}
+
+pub(crate) fn read_file(path: &Path) -> Result<DataFrame, CollectError> {
+    // text files are read into memory, decompressing them if needed
+    if let Some((extension, contents)) =
+        read_text_file(path).map_err(|_| err("could not read file"))?
+    {
+        let contents = std::io::Cursor::new(contents);
+        return match extension.as_deref() {
+            Some("csv") => CsvReader::new(contents).finish(),
+            Some("json") => JsonReader::new(contents).with_json_format(JsonFormat::Json).finish(),
+            _ => JsonReader::new(contents).with_json_format(JsonFormat::JsonLines).finish(),
+        }
+        .map_err(CollectError::PolarsError)
+    }
+    let file = std::fs::File::open(path).map_err(|_| err("could not open file"))?;
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("parquet") => read_parquet(file).map_err(CollectError::PolarsError),
+        Some("arrow") => IpcReader::new(file).finish().map_err(CollectError::PolarsError),
+        _ => Err(err("unknown file format")),
+    }
+}
+
+/// names of the columns that the current schema writes to files
+fn expected_columns(table: &Table) -> Vec<String> {
+    let mut columns = Vec::new();
+    for column in table.columns() {
+        match table.column_type(column) {
+            Some(column_type) if column_type.is_256_bit() => {
+                for u256_type in table.u256_types.iter() {
+                    columns.push(column.to_string() + u256_type.suffix().as_str())
+                }
+            }
+            _ => columns.push(column.to_string()),
+        }
+    }
+    columns
+}
+
+fn check_columns(path: &Path, df: &DataFrame, table: &Table) -> Vec<ValidationIssue> {
+    let expected = expected_columns(table);
+    let actual: Vec<String> = df.get_column_names().iter().map(|name| name.to_string()).collect();
+    let expected_set: HashSet<&String> = expected.iter().collect();
+    let actual_set: HashSet<&String> = actual.iter().collect();
+
+    let mut issues = Vec::new();
+    let missing: Vec<&str> =
+        expected.iter().filter(|c| !actual_set.contains(c)).map(|c| c.as_str()).collect();
+    if !missing.is_empty() {
+        issues.push(ValidationIssue {
+            kind: ValidationIssueKind::MissingColumns,
+            paths: vec![path.to_path_buf()],
+            message: format!("missing columns: {}", missing.join(", ")),
+        })
+    }
+
+    // decoded columns depend on the event or function signatures, so they are not checked
+    if table.log_decoder.is_none() && table.function_decoder.is_none() {
+        let extra: Vec<&str> =
+            actual.iter().filter(|c| !expected_set.contains(c)).map(|c| c.as_str()).collect();
+        if !extra.is_empty() {
+            issues.push(ValidationIssue {
+                kind: ValidationIssueKind::ExtraColumns,
+                paths: vec![path.to_path_buf()],
+                message: format!("extra columns: {}", extra.join(", ")),
+            })
+        }
+    }
+    issues
+}
+
+fn check_duplicates(path: &Path, df: &DataFrame, datatype: Datatype) -> Vec<ValidationIssue> {
+    let primary_key = match datatype.primary_key() {
+        Some(primary_key) => primary_key,
+        None => return Vec::new(),
+    };
+    let names = df.get_column_names();
+    if !primary_key.iter().all(|column| names.contains(column)) {
+        return Vec::new()
+    }
+    let n_duplicated = df
+        .select(primary_key.clone())
+        .and_then(|keys| keys.is_duplicated())
+        .map(|mask| mask.into_iter().filter(|value| *value == Some(true)).count());
+    match n_duplicated {
+        Ok(0) => Vec::new(),
+        Ok(n_duplicated) => vec![ValidationIssue {
+            kind: ValidationIssueKind::DuplicateKeys,
+            paths: vec![path.to_path_buf()],
+            message: format!("{} rows share a ({}) key", n_duplicated, primary_key.join(", ")),
+        }],
+        Err(e) => vec![ValidationIssue {
+            kind: ValidationIssueKind::Unreadable,
+            paths: vec![path.to_path_buf()],
+            message: format!("could not check primary key: {}", e),
+        }],
+    }
+}
+
+/// check that block ranges in file labels have no gaps or overlaps
+///
+/// files whose labels differ outside of the block range (e.g. by address) are checked separately
+fn check_coverage(files: &[LabeledFile]) -> (Vec<ValidationIssue>, Option<u64>, Option<u64>) {
+    let mut groups: BTreeMap<String, Vec<(u64, u64, &Path)>> = BTreeMap::new();
+    for file in files.iter() {
+        if let Some(range) = LabelBlockRange::parse(&file.label) {
+            groups.entry(range.group).or_default().push((range.start, range.end, &file.path));
+        }
+    }
+
+    let mut issues = Vec::new();
+    let mut first_block: Option<u64> = None;
+    let mut last_block: Option<u64> = None;
+    for ranges in groups.values_mut() {
+        ranges.sort();
+        for window in ranges.windows(2) {
+            let (_, previous_end, previous_path) = window[0];
+            let (start, _, path) = window[1];
+            let paths = vec![previous_path.to_path_buf(), path.to_path_buf()];
+            if start > previous_end + 1 {
+                issues.push(ValidationIssue {
+                    kind: ValidationIssueKind::Gap,
+                    paths,
+                    message: format!("blocks {} to {} are missing", previous_end + 1, start - 1),
+                })
+            } else if start <= previous_end {
+                issues.push(ValidationIssue {
+                    kind: ValidationIssueKind::Overlap,
+                    paths,
+                    message: format!("blocks {} to {} are duplicated", start, previous_end),
+                })
+            }
+        }
+        let group_first = ranges.iter().map(|(start, _, _)| *start).min();
+        let group_last = ranges.iter().map(|(_, end, _)| *end).max();
+        first_block = match (first_block, group_first) {
+            (Some(first_block), Some(group_first)) => Some(first_block.min(group_first)),
+            (first_block, group_first) => first_block.or(group_first),
+        };
+        last_block = last_block.max(group_last);
+    }
+    (issues, first_block, last_block)
+}
+
+/// write validation report to report directory, or to `.cryo/reports` of output directory
+pub fn write_validation_report(
+    report: &ValidationReport,
+    sink: &FileOutput,
+    report_dir: Option<PathBuf>,
+) -> Result<PathBuf, CollectError> {
+    let report_dir = report_dir.unwrap_or_else(|| sink.output_dir.join(".cryo/reports"));
+    std::fs::create_dir_all(&report_dir).map_err(|_| err("could not create report dir"))?;
+    let now: DateTime<Local> = Local::now();
+    let filename = format!("validate_{}.json", now.format("%Y-%m-%d_%H-%M-%S%.6f"));
+    let path = report_dir.join(filename);
+    let serialized =
+        serde_json::to_string(report).map_err(|_| err("could not serialize report"))?;
+    std::fs::write(&path, serialized).map_err(|_| err("could not write report"))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labeled(label: &str) -> LabeledFile {
+        LabeledFile { path: PathBuf::from(format!("{}.parquet", label)), label: label.to_string() }
+    }
+
+    #[test]
+    fn test_check_coverage() {
+        let files = vec![
+            labeled("00000000_to_00000999"),
+            labeled("00001000_to_00001999"),
+            labeled("00003000_to_00003999"),
+            labeled("00003500_to_00004999"),
+        ];
+        let (issues, first_block, last_block) = check_coverage(&files);
+        let kinds: Vec<_> = issues.iter().map(|issue| issue.kind.clone()).collect();
+        assert_eq!(kinds, vec![ValidationIssueKind::Gap, ValidationIssueKind::Overlap]);
+        assert_eq!(issues[0].message, "blocks 2000 to 2999 are missing");
+        assert_eq!(issues[1].message, "blocks 3500 to 3999 are duplicated");
+        assert_eq!((first_block, last_block), (Some(0), Some(4999)));
+    }
+
+    #[test]
+    fn test_check_coverage_groups_other_dimensions() {
+        let files = vec![
+            labeled("00000000_to_00000999__0xaaaa_to_0xaaaa"),
+            labeled("00000000_to_00000999__0xbbbb_to_0xbbbb"),
+        ];
+        let (issues, _, _) = check_coverage(&files);
+        assert!(issues.is_empty());
+    }
+}
@@ -31,11 +31,18 @@ def parse_cli_args(
         kwargs['json'] = True
     elif file_format == 'csv':
         kwargs['csv'] = True
+    elif file_format == 'ndjson':
+        kwargs['ndjson'] = True
+    elif file_format == 'arrow':
+        kwargs['arrow'] = True
     # elif file_format == 'avro':
     #     kwargs['avro'] = True
     else:
         raise Exception('unknown file_format')
 
+    if isinstance(kwargs.get('rpc'), str):
+        kwargs['rpc'] = [kwargs['rpc']]  # type: ignore
+
     kwargs['no_verbose'] = not kwargs.get('verbose', True)
 
     return kwargs
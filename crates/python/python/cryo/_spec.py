@@ -11,6 +11,8 @@ if typing.TYPE_CHECKING:
         Literal['parquet'],
         Literal['csv'],
         Literal['json'],
+        Literal['ndjson'],
+        Literal['arrow'],
         Literal['avro'],
     ]
     PythonOutput = Union[
@@ -30,7 +32,7 @@ if typing.TYPE_CHECKING:
         columns: typing.Sequence[str] | None
         hex: bool
         sort: typing.Sequence[str] | None
-        rpc: str | None
+        rpc: str | typing.Sequence[str] | None
         network_name: str | None
         requests_per_second: int | None
         max_concurrent_requests: int | None
@@ -77,3 +79,39 @@ if typing.TYPE_CHECKING:
         js_tracer: str | None
         verbose: bool
         event_signature: str | None
+        follow: bool
+        poll_interval: int
+        verify_reorgs: bool
+        recollect_stale: bool
+        checkpoint_size: int | None
+        chunk_retries: int
+        chunk_backoff: int
+        chunk_jitter: float
+        retry_errors: typing.Sequence[str] | None
+        cache: bool
+        cache_dir: str | None
+        cache_size: int
+        record_rpc: str | None
+        replay_rpc: str | None
+        rpc_weights: typing.Sequence[float] | None
+        rpc_rate_limits: typing.Sequence[int] | None
+        rpc_max_lag: int
+        rpc_ejection_time: int
+        adaptive_rate: bool
+        max_requests_per_second: int | None
+        arrow: bool
+        ipc_compression: str
+        ndjson: bool
+        stdout: bool
+        hive: bool
+        bucket_size: int
+        delta: bool
+        db: str | None
+        postgres: str | None
+        staging_dir: str | None
+        target_blocks: int | None
+        target_rows: int | None
+        target_bytes: int | None
+        text_compression: list[str]
+        abi: str | None
+        function_signature: typing.Sequence[str] | None
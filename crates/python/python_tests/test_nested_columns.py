@@ -0,0 +1,44 @@
+import os
+import tempfile
+
+import polars as pl
+
+import cryo
+
+
+fixture = os.path.join(
+    os.path.dirname(__file__),
+    '../../cli/tests/fixtures/devnet_block_1.jsonl',
+)
+query = {
+    'blocks': ['1'],
+    'event_signature': 'Swap(string indexed pool, uint256[] amounts, (address,uint8) side)',
+    'topic0': ['0x67b15ec7971e6851866251c56ff5799ba99efdb33e592ea69b92d78c596e5650'],
+    'u256_types': ['string'],
+    'replay_rpc': fixture,
+}
+
+
+def test_nested_columns():
+    df = cryo.collect('logs', **query)
+    assert df.schema['event__pool'] == pl.Binary
+    assert df.schema['event__amounts_string'] == pl.List(pl.Utf8)
+    assert df.schema['event__side'] == pl.Struct(
+        {'field_0': pl.Binary, 'field_1': pl.UInt64}
+    )
+
+    row = df.to_dicts()[0]
+    assert row['event__amounts_string'] == [
+        '1000000000000000000',
+        '2000000000000000000',
+    ]
+    assert row['event__side']['field_1'] == 1
+
+
+def test_nested_columns_parquet():
+    output_dir = tempfile.mkdtemp()
+    cryo.freeze('logs', output_dir=output_dir, no_report=True, **query)
+    df_freeze = pl.read_parquet(os.path.join(output_dir, '*.parquet'))
+    df_collect = cryo.collect('logs', **query)
+    assert df_freeze.schema == df_collect.schema
+    assert df_freeze.frame_equal(df_collect)
@@ -61,6 +61,42 @@ use cryo_cli::{run, Args};
         verbose = false,
         no_verbose = false,
         event_signature = None,
+        follow = false,
+        poll_interval = 12,
+        verify_reorgs = false,
+        recollect_stale = false,
+        checkpoint_size = None,
+        chunk_retries = 0,
+        chunk_backoff = 1000,
+        chunk_jitter = 0.5,
+        retry_errors = None,
+        cache = false,
+        cache_dir = None,
+        cache_size = 10000,
+        record_rpc = None,
+        replay_rpc = None,
+        rpc_weights = None,
+        rpc_rate_limits = None,
+        rpc_max_lag = 5,
+        rpc_ejection_time = 30,
+        adaptive_rate = false,
+        max_requests_per_second = None,
+        arrow = false,
+        ipc_compression = "uncompressed".to_string(),
+        ndjson = false,
+        stdout = false,
+        hive = false,
+        bucket_size = 1000000,
+        delta = false,
+        db = None,
+        postgres = None,
+        staging_dir = None,
+        target_blocks = None,
+        target_rows = None,
+        target_bytes = None,
+        text_compression = vec!["uncompressed".to_string()],
+        abi = None,
+        function_signature = None,
     )
 )]
 #[allow(clippy::too_many_arguments)]
@@ -81,7 +117,7 @@ pub fn _freeze(
     hex: bool,
     sort: Option<Vec<String>>,
     exclude_failed: bool,
-    rpc: Option<String>,
+    rpc: Option<Vec<String>>,
     network_name: Option<String>,
     requests_per_second: Option<u32>,
     max_concurrent_requests: Option<u64>,
@@ -122,6 +158,42 @@ pub fn _freeze(
     verbose: bool,
     no_verbose: bool,
     event_signature: Option<String>,
+    follow: bool,
+    poll_interval: u64,
+    verify_reorgs: bool,
+    recollect_stale: bool,
+    checkpoint_size: Option<u64>,
+    chunk_retries: u32,
+    chunk_backoff: u64,
+    chunk_jitter: f64,
+    retry_errors: Option<Vec<String>>,
+    cache: bool,
+    cache_dir: Option<String>,
+    cache_size: u64,
+    record_rpc: Option<String>,
+    replay_rpc: Option<String>,
+    rpc_weights: Option<Vec<f64>>,
+    rpc_rate_limits: Option<Vec<u32>>,
+    rpc_max_lag: u64,
+    rpc_ejection_time: u64,
+    adaptive_rate: bool,
+    max_requests_per_second: Option<u32>,
+    arrow: bool,
+    ipc_compression: String,
+    ndjson: bool,
+    stdout: bool,
+    hive: bool,
+    bucket_size: u64,
+    delta: bool,
+    db: Option<String>,
+    postgres: Option<String>,
+    staging_dir: Option<String>,
+    target_blocks: Option<u64>,
+    target_rows: Option<u64>,
+    target_bytes: Option<u64>,
+    text_compression: Vec<String>,
+    abi: Option<String>,
+    function_signature: Option<Vec<String>>,
 ) -> PyResult<&PyAny> {
     if let Some(command) = command {
         freeze_command(py, command)
@@ -182,6 +254,42 @@ pub fn _freeze(
             verbose,
             no_verbose,
             event_signature,
+            follow,
+            poll_interval,
+            verify_reorgs,
+            recollect_stale,
+            checkpoint_size,
+            chunk_retries,
+            chunk_backoff,
+            chunk_jitter,
+            retry_errors,
+            cache,
+            cache_dir,
+            cache_size,
+            record_rpc,
+            replay_rpc,
+            rpc_weights,
+            rpc_rate_limits,
+            rpc_max_lag,
+            rpc_ejection_time,
+            adaptive_rate,
+            max_requests_per_second,
+            arrow,
+            ipc_compression,
+            ndjson,
+            stdout,
+            hive,
+            bucket_size,
+            delta,
+            db: db.map(std::path::PathBuf::from),
+            postgres,
+            staging_dir,
+            target_blocks,
+            target_rows,
+            target_bytes,
+            text_compression,
+            abi: abi.map(std::path::PathBuf::from),
+            function_signature,
         };
 
         pyo3_asyncio::tokio::future_into_py(py, async move {
@@ -42,8 +42,10 @@ pub fn to_df(attrs: TokenStream, input: TokenStream) -> TokenStream {
                 "Vec < Option < Vec < u8 > > >" => {
                     syn::Ident::new("with_series_binary", Span::call_site())
                 }
-                "Vec < U256 >" => syn::Ident::new("with_series_u256", Span::call_site()),
-                "Vec < Option < U256 > >" => {
+                "Vec < U256 >" | "Vec < I256 >" => {
+                    syn::Ident::new("with_series_u256", Span::call_site())
+                }
+                "Vec < Option < U256 > >" | "Vec < Option < I256 > >" => {
                     syn::Ident::new("with_series_option_u256", Span::call_site())
                 }
                 _ => syn::Ident::new("with_series", Span::call_site()),
@@ -67,80 +69,17 @@ pub fn to_df(attrs: TokenStream, input: TokenStream) -> TokenStream {
             let u256_types: Vec<_> = schema.u256_types.clone().into_iter().collect();
             if let Some(decoder) = decoder {
 
-                fn create_empty_u256_columns(
-                    cols: &mut Vec<Series>,
-                    name: &str,
-                    u256_types: &[U256Type],
-                    column_encoding: &ColumnEncoding
-                ) {
-                    for u256_type in u256_types.iter() {
-                        let full_name = name.to_string() + u256_type.suffix().as_str();
-                        let full_name = full_name.as_str();
-
-                        match u256_type {
-                            U256Type::Binary => {
-                                match column_encoding {
-                                    ColumnEncoding::Binary => {
-                                        cols.push(Series::new(full_name, Vec::<Vec<u8>>::new()))
-                                    },
-                                    ColumnEncoding::Hex => {
-                                        cols.push(Series::new(full_name, Vec::<String>::new()))
-                                    },
-                                }
-                            },
-                            U256Type::String => cols.push(Series::new(full_name, Vec::<String>::new())),
-                            U256Type::F32 => cols.push(Series::new(full_name, Vec::<f32>::new())),
-                            U256Type::F64 => cols.push(Series::new(full_name, Vec::<f64>::new())),
-                            U256Type::U32 => cols.push(Series::new(full_name, Vec::<u32>::new())),
-                            U256Type::U64 => cols.push(Series::new(full_name, Vec::<u64>::new())),
-                            U256Type::Decimal128 => cols.push(Series::new(full_name, Vec::<Vec<u8>>::new())),
-                        }
-                    }
-                }
-
-                use ethers_core::abi::ParamType;
-
                 // Write columns even if there are no values decoded - indicates empty dataframe
                 let chunk_len = self.n_rows;
                 if self.event_cols.is_empty() {
                     for param in decoder.event.inputs.iter() {
-                        let name = "event__".to_string() + param.name.as_str();
-                        let name = name.as_str();
-                        match param.kind {
-                            ParamType::Address => {
-                                match schema.binary_type {
-                                    ColumnEncoding::Binary => cols.push(Series::new(name, Vec::<Vec<u8>>::new())),
-                                    ColumnEncoding::Hex => cols.push(Series::new(name, Vec::<String>::new())),
-                                }
-                            },
-                            ParamType::Bytes => {
-                                match schema.binary_type {
-                                    ColumnEncoding::Binary => cols.push(Series::new(name, Vec::<Vec<u8>>::new())),
-                                    ColumnEncoding::Hex => cols.push(Series::new(name, Vec::<String>::new())),
-                                }
-                            },
-                            ParamType::Int(bits) => {
-                                if bits <= 64 {
-                                    cols.push(Series::new(name, Vec::<i64>::new()))
-                                } else {
-                                    create_empty_u256_columns(&mut cols, name, &u256_types, &schema.binary_type)
-                                }
-                            },
-                            ParamType::Uint(bits) => {
-                                if bits <= 64 {
-                                    cols.push(Series::new(name, Vec::<u64>::new()))
-                                } else {
-                                    create_empty_u256_columns(&mut cols, name, &u256_types, &schema.binary_type)
-                                }
-                            },
-                            ParamType::Bool => cols.push(Series::new(name, Vec::<bool>::new())),
-                            ParamType::String => cols.push(Series::new(name, Vec::<String>::new())),
-                            ParamType::Array(_) => return Err(err("could not generate Array column")),
-                            ParamType::FixedBytes(_) => return Err(err("could not generate FixedBytes column")),
-                            ParamType::FixedArray(_, _) => return Err(err("could not generate FixedArray column")),
-                            ParamType::Tuple(_) => return Err(err("could not generate Tuple column")),
-                            _ => (),
-                        }
+                        cols.extend(decoder.make_series(
+                            param.name.clone(),
+                            Vec::new(),
+                            0,
+                            &u256_types,
+                            &schema.binary_type,
+                        )?);
                     }
                 } else {
                     for (name, data) in self.event_cols {
@@ -177,6 +116,7 @@ pub fn to_df(attrs: TokenStream, input: TokenStream) -> TokenStream {
             "Vec < u32 >" => Some(quote! { ColumnType::UInt32 }),
             "Vec < u64 >" => Some(quote! { ColumnType::UInt64 }),
             "Vec < U256 >" => Some(quote! { ColumnType::UInt256 }),
+            "Vec < I256 >" => Some(quote! { ColumnType::Int256 }),
             "Vec < i32 >" => Some(quote! { ColumnType::Int32 }),
             "Vec < i64 >" => Some(quote! { ColumnType::Int64 }),
             "Vec < f32 >" => Some(quote! { ColumnType::Float32 }),
@@ -188,6 +128,7 @@ pub fn to_df(attrs: TokenStream, input: TokenStream) -> TokenStream {
             "Vec < Option < u32 > >" => Some(quote! { ColumnType::UInt32 }),
             "Vec < Option < u64 > >" => Some(quote! { ColumnType::UInt64 }),
             "Vec < Option < U256 > >" => Some(quote! { ColumnType::UInt256 }),
+            "Vec < Option < I256 > >" => Some(quote! { ColumnType::Int256 }),
             "Vec < Option < i32 > >" => Some(quote! { ColumnType::Int32 }),
             "Vec < Option < i64 > >" => Some(quote! { ColumnType::Int64 }),
             "Vec < Option < f32 > >" => Some(quote! { ColumnType::Float32 }),
@@ -243,6 +184,10 @@ pub fn to_df(attrs: TokenStream, input: TokenStream) -> TokenStream {
                 #event_code
 
                 let df = DataFrame::new(cols).map_err(CollectError::PolarsError).sort_by_schema(schema)?;
+                let df = match &schema.function_decoder {
+                    Some(decoder) => decoder.decode_calls(df, schema)?,
+                    None => df,
+                };
                 let mut output = std::collections::HashMap::new();
                 output.insert(datatype, df);
                 Ok(output)